@@ -0,0 +1,66 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// Raises the write-through level after an ERROR.
+///
+/// Normally only events at `base` or more severe are written. For `window` after an
+/// ERROR was observed, events up to `boosted` are written as well; every further ERROR
+/// extends the window. Afterwards the level decays back to `base`.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveVerbosity {
+    base: Level,
+    boosted: Level,
+    window: Duration,
+}
+
+impl AdaptiveVerbosity {
+    #[must_use]
+    pub const fn new(base: Level, boosted: Level, window: Duration) -> Self {
+        Self {
+            base,
+            boosted,
+            window,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AdaptiveFilter {
+    config: AdaptiveVerbosity,
+    boosted_until: Mutex<Option<Instant>>,
+}
+
+impl AdaptiveFilter {
+    pub const fn new(config: AdaptiveVerbosity) -> Self {
+        Self {
+            config,
+            boosted_until: Mutex::new(None),
+        }
+    }
+
+    pub const fn config(&self) -> &AdaptiveVerbosity {
+        &self.config
+    }
+
+    pub fn admit(&self, level: Level) -> bool {
+        let now = Instant::now();
+        let Ok(mut boosted_until) = self.boosted_until.lock() else {
+            return true;
+        };
+
+        if level == Level::ERROR {
+            *boosted_until = Some(now + self.config.window);
+        }
+
+        let threshold = match *boosted_until {
+            Some(until) if now < until => self.config.boosted,
+            _ => {
+                *boosted_until = None;
+                self.config.base
+            }
+        };
+
+        level <= threshold
+    }
+}
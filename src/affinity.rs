@@ -0,0 +1,45 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::source::EventSource;
+
+thread_local! {
+    static THREAD_SOURCE: RefCell<Option<Arc<EventSource>>> = const { RefCell::new(None) };
+}
+
+/// Writes the events of the current thread through `source` instead of the layer's own
+/// source until the guard is dropped, e.g. in plugin hosts that dedicate threads to
+/// tenants. Guards nest; dropping one restores the previous binding.
+///
+/// Only the source changes; the layer's configuration (templates, flood protection,
+/// error handler) still applies.
+pub fn bind_thread_source(source: Arc<EventSource>) -> ThreadSourceGuard {
+    let previous = THREAD_SOURCE.with(|bound| bound.borrow_mut().replace(source));
+    ThreadSourceGuard {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// The source bound to the current thread, if any.
+pub(crate) fn thread_source() -> Option<Arc<EventSource>> {
+    THREAD_SOURCE.with(|bound| bound.borrow().clone())
+}
+
+/// Restores the previous source binding of the thread when dropped, see
+/// [`bind_thread_source`].
+#[derive(Debug)]
+#[must_use = "the binding ends when the guard is dropped"]
+pub struct ThreadSourceGuard {
+    previous: Option<Arc<EventSource>>,
+    /// The binding belongs to the thread that created the guard.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ThreadSourceGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        THREAD_SOURCE.with(|bound| *bound.borrow_mut() = previous);
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt::Write as _;
+use winapi::um::winbase::GetComputerNameW;
+
+/// A single well-structured INFO event describing the application at startup.
+///
+/// Version, build hash, configuration digest and enabled features are provided by the
+/// application; host name, process ID, executable and architecture are added
+/// automatically.
+#[derive(Debug, Clone)]
+pub struct StartupBanner {
+    id: u32,
+    name: String,
+    version: String,
+    build: Option<String>,
+    config_digest: Option<String>,
+    features: Vec<String>,
+}
+
+impl StartupBanner {
+    pub const DEFAULT_ID: u32 = 1000;
+
+    #[must_use]
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            id: Self::DEFAULT_ID,
+            name: name.into(),
+            version: version.into(),
+            build: None,
+            config_digest: None,
+            features: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    #[must_use]
+    pub fn build(mut self, build: impl Into<String>) -> Self {
+        self.build = Some(build.into());
+        self
+    }
+
+    #[must_use]
+    pub fn config_digest(mut self, digest: impl Into<String>) -> Self {
+        self.config_digest = Some(digest.into());
+        self
+    }
+
+    #[must_use]
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub(crate) const fn event_id(&self) -> u32 {
+        self.id
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let mut msg = format!(
+            "ID: {}\n\nmessage: {} {} started\nversion: {}\n",
+            self.id, self.name, self.version, self.version
+        );
+
+        if let Some(build) = &self.build {
+            let _ = writeln!(msg, "build: {build}");
+        }
+        if let Some(digest) = &self.config_digest {
+            let _ = writeln!(msg, "config_digest: {digest}");
+        }
+        if !self.features.is_empty() {
+            let _ = writeln!(msg, "features: {}", self.features.join(", "));
+        }
+        if let Some(host) = computer_name() {
+            let _ = writeln!(msg, "host: {host}");
+        }
+        let _ = writeln!(msg, "pid: {}", std::process::id());
+        if let Ok(exe) = std::env::current_exe() {
+            let _ = writeln!(msg, "executable: {}", exe.display());
+        }
+        let _ = writeln!(msg, "arch: {}", std::env::consts::ARCH);
+
+        msg
+    }
+}
+
+fn computer_name() -> Option<String> {
+    let mut buffer = [0u16; 256];
+    let mut len = u32::try_from(buffer.len()).unwrap_or_default();
+
+    if unsafe { GetComputerNameW(buffer.as_mut_ptr(), &mut len) } == 0 {
+        return None;
+    }
+
+    Some(String::from_utf16_lossy(&buffer[..len as usize]))
+}
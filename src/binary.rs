@@ -0,0 +1,59 @@
+use std::fmt::Write;
+
+/// Field whose value becomes the raw data of the event instead of part of the description,
+/// e.g. a dump or a serialized message.
+///
+/// Record it as bytes (`Value` for `&[u8]`) or as a hex or base64 string.
+pub const DATA_FIELD: &str = "eventlog.data";
+
+/// Decodes a hex (whitespace allowed between bytes) or standard base64 string. Strings
+/// that are valid hex are taken as hex.
+pub fn decode_binary(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let hex: String = text.split_whitespace().collect();
+    decode_hex(&hex).or_else(|| decode_base64(text))
+}
+
+pub fn encode_hex(data: &[u8]) -> String {
+    data.iter()
+        .fold(String::with_capacity(data.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let sextet = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+
+    let text = text.trim_end_matches('=').as_bytes();
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut bits = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            bits |= u32::from(sextet(*c)?) << (18 - 6 * i);
+        }
+        let bytes = bits.to_be_bytes();
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(out)
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::record::{EventRecord, FieldValue};
+
+/// Leading bytes of every data blob written by this crate.
+pub const BLOB_MAGIC: [u8; 4] = *b"TLWE";
+/// Blob layout holding the fields as length-prefixed names and type-tagged values.
+pub const BLOB_VERSION: u8 = 3;
+/// Former blob layout holding names and values as length-prefixed strings.
+const BLOB_VERSION_STRINGS: u8 = 1;
+/// Blob layout holding the [`EventRecord`] as JSON, written with the `serde` feature.
+pub const BLOB_VERSION_JSON: u8 = 2;
+
+const HEADER_LEN: usize = BLOB_MAGIC.len() + 2;
+const UNCOMPRESSED: u8 = 0;
+
+/// A compression codec for the data blob.
+///
+/// The blob header records the codec by its [`id`](Compression::id), so readers can pick
+/// the matching codec when decoding.
+pub trait Compression: fmt::Debug + Send + Sync {
+    /// Identifier of the codec in the blob header; `0` is reserved for uncompressed blobs.
+    fn id(&self) -> u8;
+
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Returns `None` if `data` is not valid for the codec.
+    fn decompress(&self, data: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Raw deflate, codec ID `1`.
+#[cfg(feature = "deflate")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deflate;
+
+#[cfg(feature = "deflate")]
+impl Compression for Deflate {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec(data, 6)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Option<Vec<u8>> {
+        miniz_oxide::inflate::decompress_to_vec(data).ok()
+    }
+}
+
+/// Writes the fields of an event as structured raw data next to the description.
+///
+/// The blob starts with [`BLOB_MAGIC`], the layout version and the codec ID. With layout
+/// [`BLOB_VERSION`] the fields follow as length-prefixed (`u32`, little endian) UTF-8
+/// names, each followed by a type tag and the value: `0` and a length-prefixed string,
+/// `1` and a `u64`, `2` and an `i64`, `3` and an `f64` (all little endian) or `4` and a
+/// `bool` byte. With the `serde` feature, layout [`BLOB_VERSION_JSON`] is
+/// written instead: the whole [`EventRecord`] as JSON, see its schema. Use
+/// [`reader::decode_data`](crate::reader::decode_data) to read either back.
+#[derive(Debug, Clone, Default)]
+pub struct DataBlob {
+    compression: Option<(usize, Arc<dyn Compression>)>,
+}
+
+impl DataBlob {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compresses blobs larger than `threshold` bytes with `codec`.
+    ///
+    /// The compressed form is only kept if it is smaller.
+    #[must_use]
+    pub fn with_compression(mut self, threshold: usize, codec: impl Compression + 'static) -> Self {
+        self.compression = Some((threshold, Arc::new(codec)));
+        self
+    }
+
+    pub(crate) fn encode(&self, record: &EventRecord) -> Vec<u8> {
+        #[cfg(feature = "serde")]
+        let (version, mut payload) = (
+            BLOB_VERSION_JSON,
+            serde_json::to_vec(record).unwrap_or_default(),
+        );
+        #[cfg(not(feature = "serde"))]
+        let (version, mut payload) = {
+            let mut payload = Vec::new();
+            for (name, value) in &record.fields {
+                push_str(&mut payload, name);
+                push_value(&mut payload, value);
+            }
+            (BLOB_VERSION, payload)
+        };
+
+        let mut codec = UNCOMPRESSED;
+        if let Some((threshold, compression)) = &self.compression {
+            if payload.len() > *threshold {
+                let compressed = compression.compress(&payload);
+                if compressed.len() < payload.len() {
+                    payload = compressed;
+                    codec = compression.id();
+                }
+            }
+        }
+
+        let mut blob = Vec::with_capacity(HEADER_LEN + payload.len());
+        blob.extend_from_slice(&BLOB_MAGIC);
+        blob.push(version);
+        blob.push(codec);
+        blob.extend_from_slice(&payload);
+        blob
+    }
+}
+
+#[cfg_attr(feature = "serde", allow(dead_code))]
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&u32::try_from(s.len()).unwrap_or(u32::MAX).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+#[cfg_attr(feature = "serde", allow(dead_code))]
+fn push_value(buf: &mut Vec<u8>, value: &FieldValue) {
+    match value {
+        FieldValue::Str(v) => {
+            buf.push(0);
+            push_str(buf, v);
+        }
+        FieldValue::U64(v) => {
+            buf.push(1);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        FieldValue::I64(v) => {
+            buf.push(2);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        FieldValue::F64(v) => {
+            buf.push(3);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        FieldValue::Bool(v) => {
+            buf.push(4);
+            buf.push(u8::from(*v));
+        }
+    }
+}
+
+fn take<const N: usize>(buf: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = buf.get(..N)?.try_into().ok()?;
+    *buf = &buf[N..];
+    Some(bytes)
+}
+
+fn take_value(buf: &mut &[u8]) -> Option<FieldValue> {
+    let [tag] = take::<1>(buf)?;
+    Some(match tag {
+        0 => FieldValue::Str(take_str(buf)?.to_owned()),
+        1 => FieldValue::U64(u64::from_le_bytes(take(buf)?)),
+        2 => FieldValue::I64(i64::from_le_bytes(take(buf)?)),
+        3 => FieldValue::F64(f64::from_le_bytes(take(buf)?)),
+        4 => FieldValue::Bool(take::<1>(buf)?[0] != 0),
+        _ => return None,
+    })
+}
+
+fn take_str<'a>(buf: &mut &'a [u8]) -> Option<&'a str> {
+    let len = u32::from_le_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let s = std::str::from_utf8(buf.get(4..4 + len)?).ok()?;
+    *buf = &buf[4 + len..];
+    Some(s)
+}
+
+/// Returns the layout version and the decompressed payload of a blob.
+pub(crate) fn unpack(data: &[u8], codecs: &[&dyn Compression]) -> Option<(u8, Vec<u8>)> {
+    let header = data.get(..HEADER_LEN)?;
+    if header[..BLOB_MAGIC.len()] != BLOB_MAGIC {
+        return None;
+    }
+
+    let payload = &data[HEADER_LEN..];
+    let payload = match header[BLOB_MAGIC.len() + 1] {
+        UNCOMPRESSED => payload.to_vec(),
+        id => codecs
+            .iter()
+            .find(|codec| codec.id() == id)?
+            .decompress(payload)?,
+    };
+    Some((header[BLOB_MAGIC.len()], payload))
+}
+
+/// Decodes the fields of the binary layouts.
+pub(crate) fn decode_fields(version: u8, mut payload: &[u8]) -> Option<Vec<(String, FieldValue)>> {
+    let mut fields = Vec::new();
+    while !payload.is_empty() {
+        let name = take_str(&mut payload)?.to_owned();
+        let value = match version {
+            BLOB_VERSION => take_value(&mut payload)?,
+            BLOB_VERSION_STRINGS => FieldValue::Str(take_str(&mut payload)?.to_owned()),
+            _ => return None,
+        };
+        fields.push((name, value));
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stores the payload reversed, so a blob only decodes with this codec.
+    #[derive(Debug)]
+    struct Reversed;
+
+    impl Compression for Reversed {
+        fn id(&self) -> u8 {
+            9
+        }
+
+        fn compress(&self, data: &[u8]) -> Vec<u8> {
+            data.iter().rev().copied().collect()
+        }
+
+        fn decompress(&self, data: &[u8]) -> Option<Vec<u8>> {
+            Some(self.compress(data))
+        }
+    }
+
+    fn blob(version: u8, codec: u8, payload: &[u8]) -> Vec<u8> {
+        let mut blob = BLOB_MAGIC.to_vec();
+        blob.extend([version, codec]);
+        blob.extend_from_slice(payload);
+        blob
+    }
+
+    fn strings_payload() -> Vec<u8> {
+        let mut payload = Vec::new();
+        push_str(&mut payload, "volume");
+        push_str(&mut payload, "C:");
+        push_str(&mut payload, "free");
+        push_str(&mut payload, "1024");
+        payload
+    }
+
+    #[test]
+    fn decodes_the_string_layout() {
+        let blob = blob(BLOB_VERSION_STRINGS, UNCOMPRESSED, &strings_payload());
+        let (version, payload) = unpack(&blob, &[]).unwrap();
+        assert_eq!(
+            decode_fields(version, &payload).unwrap(),
+            [
+                ("volume".to_owned(), FieldValue::Str("C:".to_owned())),
+                ("free".to_owned(), FieldValue::Str("1024".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn unpacks_with_the_codec_of_matching_id() {
+        let payload = strings_payload();
+        let blob = blob(BLOB_VERSION_STRINGS, 9, &Reversed.compress(&payload));
+        assert_eq!(
+            unpack(&blob, &[&Reversed]),
+            Some((BLOB_VERSION_STRINGS, payload))
+        );
+        assert_eq!(unpack(&blob, &[]), None);
+    }
+
+    #[test]
+    fn rejects_foreign_and_truncated_data() {
+        assert_eq!(unpack(b"MZ\x90\x00\x03\x00", &[]), None);
+        assert_eq!(unpack(&BLOB_MAGIC, &[]), None);
+        let payload = strings_payload();
+        assert_eq!(
+            decode_fields(BLOB_VERSION_STRINGS, &payload[..payload.len() - 1]),
+            None
+        );
+        assert_eq!(decode_fields(99, &payload), None);
+    }
+
+    #[test]
+    fn typed_values_round_trip() {
+        let mut record = EventRecord::new("hello_world", 1, tracing::Level::INFO, "checked");
+        record.fields = [
+            ("free", FieldValue::U64(u64::MAX)),
+            ("delta", FieldValue::I64(-3)),
+            ("ratio", FieldValue::F64(0.25)),
+            ("critical", FieldValue::Bool(true)),
+            ("volume", FieldValue::Str("C:".to_owned())),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+        let blob = DataBlob::new().encode(&record);
+        assert_eq!(
+            crate::reader::decode_data(&blob, &[]),
+            Some(record.fields.into_iter().collect())
+        );
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn typed_layout_tags_each_value() {
+        let mut record = EventRecord::new("hello_world", 1, tracing::Level::INFO, "checked");
+        record
+            .fields
+            .insert("free".to_owned(), FieldValue::U64(1024));
+        let encoded = DataBlob::new().encode(&record);
+
+        let mut expected = blob(BLOB_VERSION, UNCOMPRESSED, &[]);
+        expected.extend(4u32.to_le_bytes());
+        expected.extend(b"free");
+        expected.push(1);
+        expected.extend(1024u64.to_le_bytes());
+        assert_eq!(encoded, expected);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_only_compresses_blobs_over_the_threshold() {
+        let mut record = EventRecord::new("hello_world", 1, tracing::Level::INFO, "dump");
+        record
+            .fields
+            .insert("config".to_owned(), FieldValue::Str("x".repeat(4096)));
+        let plain = DataBlob::new()
+            .with_compression(usize::MAX, Deflate)
+            .encode(&record);
+        let compressed = DataBlob::new()
+            .with_compression(64, Deflate)
+            .encode(&record);
+
+        assert_eq!(plain[HEADER_LEN - 1], UNCOMPRESSED);
+        assert_eq!(compressed[HEADER_LEN - 1], Deflate.id());
+        assert!(compressed.len() < plain.len());
+        assert_eq!(unpack(&compressed, &[&Deflate]), unpack(&plain, &[]));
+    }
+}
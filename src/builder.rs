@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+
+use crate::error::{Error, WriteError};
+use crate::event_type::{EventType, EventTypeMapping};
+use crate::eventlog::EventLogLayer;
+use crate::fallback::{Fallback, FallbackSink};
+#[cfg(not(feature = "lean"))]
+use crate::formatter::{EventFormatter, JsonFormatter, TemplateFormatter};
+use crate::guard::EventLogGuard;
+use crate::layout::{Layout, LineEnding};
+use crate::names::validate_source_name;
+use crate::output::{ErrorHandler, LazySource, Output};
+use crate::oversize::OversizedMessage;
+use crate::sink::EventSink;
+use crate::source::{EventSource, EventSourceRegistry};
+use crate::target_id::TargetIds;
+use crate::worker::NonBlocking;
+
+/// Installs a layer for `source` with a non-blocking writer as the global subscriber.
+///
+/// # Errors
+/// See [`EventLogLayerBuilder::install`].
+pub fn init(source: &str) -> Result<EventLogGuard, Error> {
+    EventLogLayer::builder()
+        .source(source)
+        .non_blocking(NonBlocking::default())
+        .install()
+}
+
+/// Builds an [`EventLogLayer`], registering its source up front.
+///
+/// Unlike [`EventLogLayer::new`], [`EventLogLayerBuilder::build`] reports a source that
+/// cannot be registered instead of retrying on every event. All other options are set on
+/// the built layer with its `with_*` methods.
+///
+/// ```no_run
+/// use tracing_layer_win_eventlog::EventLogLayer;
+///
+/// let layer = EventLogLayer::builder()
+///     .source("hello_world")
+///     .default_event_id(1000)
+///     .single_line(true)
+///     .build()?
+///     .with_rollups(false);
+/// # Ok::<(), tracing_layer_win_eventlog::Error>(())
+/// ```
+#[derive(Clone)]
+#[must_use]
+pub struct EventLogLayerBuilder {
+    source: Option<String>,
+    server: Option<String>,
+    default_event_id: Option<u32>,
+    default_category: u16,
+    target_ids: TargetIds,
+    max_level: LevelFilter,
+    oversized: OversizedMessage,
+    shared_source: bool,
+    lazy: bool,
+    layout: Layout,
+    event_types: EventTypeMapping,
+    floors: HashMap<u32, Level>,
+    #[cfg(not(feature = "lean"))]
+    formatter: Option<Arc<dyn EventFormatter>>,
+    non_blocking: Option<NonBlocking>,
+    fallback: Option<Fallback>,
+    on_error: Option<ErrorHandler>,
+    sink: Option<Arc<dyn EventSink>>,
+    panic_hook: bool,
+}
+
+impl fmt::Debug for EventLogLayerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("EventLogLayerBuilder");
+        debug
+            .field("source", &self.source)
+            .field("server", &self.server)
+            .field("default_event_id", &self.default_event_id)
+            .field("default_category", &self.default_category)
+            .field("target_ids", &self.target_ids)
+            .field("max_level", &self.max_level)
+            .field("oversized", &self.oversized)
+            .field("shared_source", &self.shared_source)
+            .field("lazy", &self.lazy)
+            .field("layout", &self.layout)
+            .field("event_types", &self.event_types)
+            .field("floors", &self.floors)
+            .field("non_blocking", &self.non_blocking)
+            .field("fallback", &self.fallback)
+            .field("on_error", &self.on_error.is_some())
+            .field("sink", &self.sink.is_some())
+            .field("panic_hook", &self.panic_hook);
+        #[cfg(not(feature = "lean"))]
+        debug.field("formatter", &self.formatter);
+        debug.finish()
+    }
+}
+
+impl Default for EventLogLayerBuilder {
+    fn default() -> Self {
+        Self {
+            source: None,
+            server: None,
+            default_event_id: None,
+            default_category: 0,
+            target_ids: TargetIds::default(),
+            max_level: LevelFilter::TRACE,
+            oversized: OversizedMessage::Truncate,
+            shared_source: true,
+            lazy: false,
+            layout: Layout::default(),
+            event_types: EventTypeMapping::default(),
+            floors: HashMap::new(),
+            #[cfg(not(feature = "lean"))]
+            formatter: None,
+            non_blocking: None,
+            fallback: None,
+            on_error: None,
+            sink: None,
+            panic_hook: false,
+        }
+    }
+}
+
+impl EventLogLayerBuilder {
+    /// The event source to write as, required.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Writes to the event log of `server` (a UNC name such as `\\logs01`) instead of
+    /// the local one. The handle is never shared.
+    pub fn server(mut self, server: impl Into<String>) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// The event ID of events without an `id` field, instead of one per level (0 for TRACE
+    /// up to 4 for ERROR).
+    pub const fn default_event_id(mut self, id: u32) -> Self {
+        self.default_event_id = Some(id);
+        self
+    }
+
+    /// The category of events without a `category` field, 0 (none) by default.
+    pub const fn default_category(mut self, category: u16) -> Self {
+        self.default_category = category;
+        self
+    }
+
+    /// See [`EventLogLayer::with_target_id`].
+    pub fn target_id(mut self, prefix: &str, id: u32) -> Self {
+        self.target_ids.insert_fixed(prefix, id);
+        self
+    }
+
+    /// See [`EventLogLayer::with_target_id_range`].
+    pub fn target_id_range(mut self, prefix: &str, base: u32) -> Self {
+        self.target_ids.insert_range(prefix, base);
+        self
+    }
+
+    /// See [`EventLogLayer::with_oversized_messages`].
+    pub const fn oversized_messages(mut self, policy: OversizedMessage) -> Self {
+        self.oversized = policy;
+        self
+    }
+
+    /// See [`EventLogLayer::with_max_level`].
+    pub const fn max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Whether to share the source handle with other layers of the process, see
+    /// [`EventLogLayer::with_shared_source`]. Enabled by default.
+    pub const fn shared_source(mut self, shared: bool) -> Self {
+        self.shared_source = shared;
+        self
+    }
+
+    /// See [`EventLogLayer::with_line_ending`].
+    pub const fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.layout.line_ending = line_ending;
+        self
+    }
+
+    /// See [`EventLogLayer::with_trailing_whitespace_trimmed`].
+    pub const fn trailing_whitespace_trimmed(mut self, trim: bool) -> Self {
+        self.layout.trim_trailing_whitespace = trim;
+        self
+    }
+
+    /// See [`EventLogLayer::with_ansi_stripped`].
+    pub const fn ansi_stripped(mut self, strip: bool) -> Self {
+        self.layout.strip_ansi = strip;
+        self
+    }
+
+    /// See [`EventLogLayer::with_single_line`].
+    pub const fn single_line(mut self, single_line: bool) -> Self {
+        self.layout.single_line = single_line;
+        self
+    }
+
+    /// See [`EventLogLayer::with_event_type`].
+    pub const fn event_type(mut self, level: Level, event_type: EventType) -> Self {
+        self.event_types = self.event_types.with(level, event_type);
+        self
+    }
+
+    /// See [`EventLogLayer::with_event_types`].
+    pub const fn event_types(mut self, mapping: EventTypeMapping) -> Self {
+        self.event_types = mapping;
+        self
+    }
+
+    /// See [`EventLogLayer::with_severity_floor`].
+    pub fn severity_floor(mut self, id: u32, floor: Level) -> Self {
+        self.floors.insert(id, floor);
+        self
+    }
+
+    /// See [`EventLogLayer::with_description_template`].
+    #[cfg(not(feature = "lean"))]
+    pub fn description_template(mut self, template: &str) -> Self {
+        self.formatter = Some(Arc::new(TemplateFormatter::new(template)));
+        self
+    }
+
+    /// See [`EventLogLayer::with_json_descriptions`].
+    #[cfg(not(feature = "lean"))]
+    pub fn json_descriptions(mut self) -> Self {
+        self.formatter = Some(Arc::new(JsonFormatter));
+        self
+    }
+
+    /// See [`EventLogLayer::with_non_blocking`].
+    pub const fn non_blocking(mut self, config: NonBlocking) -> Self {
+        self.non_blocking = Some(config);
+        self
+    }
+
+    /// See [`EventLogLayer::with_fallback`]. With a fallback, [`build`](Self::build) no
+    /// longer fails if `RegisterEventSourceW` does: events go to the fallback and the
+    /// source is registered again on every write until it succeeds.
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Called with every failed write instead of printing it to stderr, e.g. to count
+    /// failures or log them elsewhere in a service without a console. See
+    /// [`EventLogLayer::with_error_handler`].
+    pub fn on_error(mut self, on_error: impl Fn(&WriteError) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Registers the source on the first write instead of in [`build`](Self::build), and
+    /// again on later writes as long as registering fails, see
+    /// [`EventLogLayer::new_lazy`].
+    pub const fn lazy_registration(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// See [`EventLogLayer::with_sink`]. The source is not registered then.
+    pub fn sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Sets a panic hook writing panics as ERROR events through the layer's source once
+    /// [`EventLogLayerBuilder::install`] has set the subscriber, see
+    /// [`EventLogHandle::install_panic_hook`](crate::EventLogHandle::install_panic_hook).
+    /// [`EventLogLayerBuilder::build`] ignores it; call `install_panic_hook` on the handle
+    /// of the built layer instead.
+    pub const fn panic_hook(mut self, enabled: bool) -> Self {
+        self.panic_hook = enabled;
+        self
+    }
+
+    /// Registers the source and builds the layer.
+    ///
+    /// # Errors
+    /// Fails with [`Error::MissingSource`] if no source was set, with
+    /// [`Error::InvalidSourceName`] if its name cannot be registered and with
+    /// [`Error::Register`] if `RegisterEventSourceW` fails, unless a fallback is set,
+    /// registration is lazy or a sink is set.
+    pub fn build(self) -> Result<EventLogLayer, Error> {
+        let log_name = self.source.ok_or(Error::MissingSource)?;
+        validate_source_name(&log_name).map_err(Error::InvalidSourceName)?;
+        let mut output = if self.sink.is_some() {
+            Output::unregistered(log_name, self.server)
+        } else if self.lazy {
+            let mut output = Output::unregistered(log_name, self.server);
+            output.lazy = Some(Arc::new(LazySource::new(self.shared_source)));
+            output
+        } else {
+            let source = if self.shared_source && self.server.is_none() {
+                EventSourceRegistry::try_get_or_register(&log_name)
+            } else {
+                EventSource::try_register_on(self.server.as_deref(), &log_name).map(Arc::new)
+            };
+            match source {
+                Ok(source) => Output::with_source(source),
+                Err(Error::Register { .. }) if self.fallback.is_some() => {
+                    Output::unregistered(log_name, self.server)
+                }
+                Err(error) => return Err(error),
+            }
+        };
+        output.fallback = self.fallback.map(|f| Arc::new(FallbackSink::new(f)));
+        output.layout = self.layout;
+        output.event_types = self.event_types;
+        output.on_error = self.on_error;
+        output.oversized = self.oversized;
+        output.sink = self.sink;
+        let layer = self.floors.into_iter().fold(
+            EventLogLayer::from_output(output, self.default_event_id, self.default_category)
+                .with_max_level(self.max_level)
+                .with_target_ids(self.target_ids),
+            |layer, (id, floor)| layer.with_severity_floor(id, floor),
+        );
+        #[cfg(not(feature = "lean"))]
+        let layer = match self.formatter {
+            Some(formatter) => layer.with_formatter(formatter),
+            None => layer,
+        };
+        Ok(match self.non_blocking {
+            Some(config) => layer.with_non_blocking(config),
+            None => layer,
+        })
+    }
+
+    /// Builds the layer and sets a subscriber with just this layer as the global default.
+    ///
+    /// The returned guard writes pending events and deregisters the source when dropped.
+    /// To combine the layer with others, use [`EventLogLayerBuilder::build`] and
+    /// [`EventLogLayer::guard`].
+    ///
+    /// # Errors
+    /// Fails like [`EventLogLayerBuilder::build`] and with [`Error::AlreadyInstalled`] if
+    /// a global subscriber is already set.
+    pub fn install(self) -> Result<EventLogGuard, Error> {
+        let panic_hook = self.panic_hook;
+        let mut layer = self.build()?;
+        let guard = layer.guard();
+        let handle = layer.handle();
+        tracing_subscriber::registry()
+            .with(layer)
+            .try_init()
+            .map_err(|_| Error::AlreadyInstalled)?;
+        if panic_hook {
+            handle.install_panic_hook();
+        }
+        Ok(guard)
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::event_type::EventType;
+use crate::template::Template;
+
+/// Description templates of one language, keyed by event ID.
+///
+/// Catalogs can be loaded at runtime from text with one `<id> = <template>` line per
+/// event; empty lines and lines starting with `#` are skipped and `\n` in a template
+/// stands for a line break. An event type after the ID overrides the one derived from the
+/// level, so an `info!` can be logged as a warning without touching the call site:
+///
+/// ```text
+/// # German
+/// 1000 = Dienst {service} gestartet
+/// 1001 warning = Anmeldung von {user} fehlgeschlagen\nGrund: {reason}
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateCatalog {
+    templates: HashMap<u32, Template>,
+    severities: HashMap<u32, EventType>,
+}
+
+impl TemplateCatalog {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_template(mut self, id: u32, template: &str) -> Self {
+        self.templates.insert(id, Template::parse(template));
+        self
+    }
+
+    /// Logs event `id` as `event_type` regardless of its level. An explicit `eventlog.type`
+    /// field still takes precedence.
+    #[must_use]
+    pub fn with_severity(mut self, id: u32, event_type: EventType) -> Self {
+        self.severities.insert(id, event_type);
+        self
+    }
+
+    /// Parses a catalog in the text format above.
+    ///
+    /// # Errors
+    /// Fails on the first line that is neither empty, a comment nor an
+    /// `<id> [<event type>] = <template>` line.
+    pub fn parse(text: &str) -> Result<Self, ParseCatalogError> {
+        let mut catalog = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = ParseCatalogError { line: i + 1 };
+            let (key, template) = line.split_once('=').ok_or(error)?;
+            let mut key = key.split_whitespace();
+            let id = key.next().and_then(|id| id.parse().ok()).ok_or(error)?;
+            if let Some(event_type) = key.next() {
+                catalog = catalog.with_severity(id, event_type.parse().map_err(|_| error)?);
+            }
+            if key.next().is_some() {
+                return Err(error);
+            }
+            catalog = catalog.with_template(id, &template.trim().replace("\\n", "\n"));
+        }
+        Ok(catalog)
+    }
+
+    pub(crate) fn templates(&self) -> impl Iterator<Item = (u32, &Template)> {
+        self.templates.iter().map(|(id, template)| (*id, template))
+    }
+
+    pub(crate) fn severities(&self) -> impl Iterator<Item = (u32, EventType)> + '_ {
+        self.severities
+            .iter()
+            .map(|(id, event_type)| (*id, *event_type))
+    }
+}
+
+/// A line of a template catalog that could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseCatalogError {
+    /// The line number, starting at 1.
+    pub line: usize,
+}
+
+impl fmt::Display for ParseCatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid template catalog line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseCatalogError {}
+
+/// Template catalogs of several languages, keyed by LCID (e.g. `0x0407` for German).
+#[derive(Debug, Clone, Default)]
+pub struct TemplateCatalogs {
+    catalogs: HashMap<u32, TemplateCatalog>,
+    fallback: Option<u32>,
+}
+
+impl TemplateCatalogs {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_language(mut self, lcid: u32, catalog: TemplateCatalog) -> Self {
+        self.catalogs.insert(lcid, catalog);
+        self
+    }
+
+    /// The language used if no catalog matches the selected one.
+    #[must_use]
+    pub const fn with_fallback(mut self, lcid: u32) -> Self {
+        self.fallback = Some(lcid);
+        self
+    }
+
+    /// Returns the catalog of `lcid`, of a language with the same primary language
+    /// (e.g. `0x0807` for `0x0407`) or of the fallback language.
+    pub(crate) fn select(&self, lcid: u32) -> Option<&TemplateCatalog> {
+        let primary = |id: u32| id & 0x3ff;
+        self.catalogs
+            .get(&lcid)
+            .or_else(|| {
+                let mut candidates: Vec<_> = self
+                    .catalogs
+                    .iter()
+                    .filter(|(id, _)| primary(**id) == primary(lcid))
+                    .collect();
+                candidates.sort_by_key(|(id, _)| **id);
+                candidates.first().map(|(_, catalog)| *catalog)
+            })
+            .or_else(|| self.catalogs.get(&self.fallback?))
+    }
+}
@@ -0,0 +1,242 @@
+//! Chunking protocol for payloads that exceed the event size limits.
+//!
+//! A payload is split into `count` events that share a group GUID. Each description
+//! starts with the usual `ID:` header followed by a `chunk: <group> <index>/<count>`
+//! line; everything after that line is the verbatim part of the payload.
+
+use std::collections::HashMap;
+use tracing::Level;
+use winapi::shared::guiddef::GUID;
+use winapi::um::combaseapi::CoCreateGuid;
+
+use crate::error::{BatchError, Error};
+use crate::guid::format_guid;
+use crate::limits::MAX_STRING_LEN;
+use crate::source::EventSourceRegistry;
+
+/// Payload length per chunk in UTF-16 code units, leaving room for the headers.
+pub const DEFAULT_CHUNK_LEN: usize = MAX_STRING_LEN - 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub group: String,
+    pub index: usize,
+    pub count: usize,
+    pub body: String,
+}
+
+impl Chunk {
+    #[must_use]
+    pub fn description(&self, id: u32) -> String {
+        format!(
+            "ID: {id}\n\nchunk: {} {}/{}\n{}",
+            self.group, self.index, self.count, self.body
+        )
+    }
+
+    /// Parses a description written by [`Chunk::description`].
+    #[must_use]
+    pub fn parse(description: &str) -> Option<Self> {
+        let start = description.find("chunk: ")?;
+        let rest = &description[start + "chunk: ".len()..];
+        let (header, body) = rest.split_once('\n').unwrap_or((rest, ""));
+        let (group, position) = header.split_once(' ')?;
+        let (index, count) = position.split_once('/')?;
+
+        Some(Self {
+            group: group.to_owned(),
+            index: index.parse().ok()?,
+            count: count.parse().ok()?,
+            body: body.to_owned(),
+        })
+    }
+}
+
+fn new_group() -> String {
+    let mut guid: GUID = unsafe { std::mem::zeroed() };
+    unsafe {
+        CoCreateGuid(&mut guid);
+    }
+    format_guid(
+        u128::from(guid.Data1) << 96
+            | u128::from(guid.Data2) << 80
+            | u128::from(guid.Data3) << 64
+            | u128::from(u64::from_be_bytes(guid.Data4)),
+    )
+}
+
+/// Splits `payload` into chunks of at most `chunk_len` UTF-16 code units.
+#[must_use]
+pub fn split(payload: &str, chunk_len: usize) -> Vec<Chunk> {
+    let chunk_len = chunk_len.max(2);
+    let mut bodies = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for c in payload.chars() {
+        if current_len + c.len_utf16() > chunk_len {
+            bodies.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        current.push(c);
+        current_len += c.len_utf16();
+    }
+    if !current.is_empty() || bodies.is_empty() {
+        bodies.push(current);
+    }
+
+    let group = new_group();
+    let count = bodies.len();
+    bodies
+        .into_iter()
+        .enumerate()
+        .map(|(i, body)| Chunk {
+            group: group.clone(),
+            index: i + 1,
+            count,
+            body,
+        })
+        .collect()
+}
+
+/// Writes `payload` as correlated chunk events to `log_name` through the shared handle of
+/// the source and returns the group GUID.
+///
+/// # Errors
+/// Stops at the first chunk that cannot be written; [`BatchError::written`] tells how many
+/// were written before. Fails with `written` 0 if the source cannot be registered.
+pub fn write_chunked(
+    id: u32,
+    level: Level,
+    payload: &str,
+    log_name: &str,
+) -> Result<String, BatchError> {
+    let source = EventSourceRegistry::try_get_or_register(log_name)
+        .map_err(|error| BatchError { written: 0, error })?;
+    write_chunks(id, payload, |description| {
+        source.try_write(id, level, description)
+    })
+}
+
+/// Writes the chunks of `payload` with `write` and returns the group GUID.
+pub(crate) fn write_chunks(
+    id: u32,
+    payload: &str,
+    mut write: impl FnMut(&str) -> Result<(), Error>,
+) -> Result<String, BatchError> {
+    let chunks = split(payload, DEFAULT_CHUNK_LEN);
+    for (written, chunk) in chunks.iter().enumerate() {
+        write(&chunk.description(id)).map_err(|error| BatchError { written, error })?;
+    }
+    Ok(chunks
+        .into_iter()
+        .next()
+        .map(|c| c.group)
+        .unwrap_or_default())
+}
+
+/// Reassembles chunked payloads from event descriptions given in any order.
+///
+/// Descriptions without a chunk header are ignored; groups with missing chunks are not
+/// returned.
+pub fn reassemble<I, S>(descriptions: I) -> Vec<(String, String)>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut groups: HashMap<String, Vec<Chunk>> = HashMap::new();
+    for chunk in descriptions
+        .into_iter()
+        .filter_map(|d| Chunk::parse(d.as_ref()))
+    {
+        groups.entry(chunk.group.clone()).or_default().push(chunk);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(group, mut chunks)| {
+            chunks.sort_by_key(|c| c.index);
+            chunks.dedup_by_key(|c| c.index);
+            let count = chunks.first()?.count;
+            let complete =
+                chunks.len() == count && chunks.iter().enumerate().all(|(i, c)| c.index == i + 1);
+            complete.then(|| (group, chunks.into_iter().map(|c| c.body).collect()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ERROR_INVALID_HANDLE;
+
+    #[test]
+    fn splits_on_utf16_length_without_breaking_surrogate_pairs() {
+        let chunks = split("ab😀cd", 3);
+        let bodies: Vec<_> = chunks.iter().map(|c| c.body.as_str()).collect();
+        assert_eq!(bodies, ["ab", "😀c", "d"]);
+        assert!(chunks
+            .iter()
+            .all(|c| c.group == chunks[0].group && c.count == 3));
+        assert_eq!(
+            chunks.iter().map(|c| c.index).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn an_empty_payload_is_one_empty_chunk() {
+        let chunks = split("", DEFAULT_CHUNK_LEN);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].body, "");
+    }
+
+    #[test]
+    fn description_parses_back_with_multiline_body() {
+        let chunk = Chunk {
+            group: "{00000000-0000-0000-0000-000000000001}".to_owned(),
+            index: 2,
+            count: 3,
+            body: "line 1\nline 2".to_owned(),
+        };
+        assert_eq!(Chunk::parse(&chunk.description(42)), Some(chunk));
+        assert_eq!(Chunk::parse("ID: 42\n\nmessage: no chunk\n"), None);
+    }
+
+    #[test]
+    fn reassembles_complete_groups_in_any_order() {
+        let payload = "x".repeat(10);
+        let mut descriptions: Vec<_> = split(&payload, 4)
+            .iter()
+            .map(|c| c.description(1))
+            .collect();
+        descriptions.reverse();
+        descriptions.push("ID: 1\n\nmessage: unrelated\n".to_owned());
+        let incomplete = split("abcdef", 2);
+        descriptions.push(incomplete[0].description(1));
+
+        let groups = reassemble(&descriptions);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1, payload);
+    }
+
+    #[test]
+    fn write_errors_report_the_chunks_written() {
+        let payload = "y".repeat(DEFAULT_CHUNK_LEN * 2 + 1);
+        let mut calls = 0;
+        let error = write_chunks(1, &payload, |_| {
+            calls += 1;
+            if calls == 2 {
+                Err(Error::Write {
+                    code: ERROR_INVALID_HANDLE,
+                    attempt: 1,
+                })
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap_err();
+        assert_eq!(error.written, 1);
+        assert_eq!(error.error.code(), Some(ERROR_INVALID_HANDLE));
+    }
+}
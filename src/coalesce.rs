@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::callsite::Identifier;
+use tracing::{Level, Metadata};
+
+use crate::log_fields::LogMetadata;
+use crate::time::format_system_time;
+use crate::wide::to_wide;
+
+/// Coalescing of consecutive duplicate events.
+///
+/// An event from the same callsite with the same message as the event written just before
+/// it is not written, only counted, unless it arrives `timeout` or more after the first
+/// event of the burst. The next event that is not counted, a different one or such a late
+/// duplicate, has a single "last message repeated N times" event with the ID and level of
+/// the burst written before it, and is then written as usual. There is no timer: a burst
+/// is summarized only when the next event reaches the layer, or when the layer is dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateCoalescing {
+    timeout: Duration,
+}
+
+impl DuplicateCoalescing {
+    #[must_use]
+    pub const fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl Default for DuplicateCoalescing {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+#[derive(Debug)]
+struct Burst {
+    callsite: Identifier,
+    origin: Option<String>,
+    message: Option<String>,
+    name: String,
+    id: u32,
+    level: Level,
+    started: Instant,
+    repeats: u64,
+    first_repeat: Option<SystemTime>,
+    last_repeat: Option<SystemTime>,
+}
+
+impl Burst {
+    fn summary(&self) -> Option<(u32, Level, Vec<u16>)> {
+        let (Some(first), Some(last)) = (self.first_repeat, self.last_repeat) else {
+            return None;
+        };
+
+        Some((
+            self.id,
+            self.level,
+            to_wide(&format!(
+                "ID: {}\n\nmessage: last message repeated {} times\ncallsite: {}\nfirst_repeat: {}\nlast_repeat: {}\n",
+                self.id,
+                self.repeats,
+                self.name,
+                format_system_time(first),
+                format_system_time(last),
+            )),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct Coalescer {
+    config: DuplicateCoalescing,
+    last: Mutex<Option<Burst>>,
+}
+
+impl Coalescer {
+    pub fn new(config: DuplicateCoalescing) -> Self {
+        Self {
+            config,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub const fn config(&self) -> &DuplicateCoalescing {
+        &self.config
+    }
+
+    /// Returns whether the event is written, rather than counted as a repeat of the one
+    /// before it, and the summary of the burst it ends.
+    ///
+    /// Events from `tracing-log` share one callsite per level, so they are told apart by
+    /// their original target and location.
+    pub fn admit(
+        &self,
+        metadata: &'static Metadata<'static>,
+        log: &LogMetadata,
+        id: u32,
+        level: Level,
+        message: Option<&str>,
+    ) -> (bool, Vec<(u32, Level, Vec<u16>)>) {
+        let Ok(mut last) = self.last.lock() else {
+            return (true, Vec::new());
+        };
+
+        let now = Instant::now();
+        let origin = (!log.is_empty())
+            .then(|| format!("{} ({})", log.target(metadata), log.location(metadata)));
+        if let Some(burst) = last.as_mut().filter(|burst| {
+            burst.callsite == metadata.callsite()
+                && burst.origin == origin
+                && burst.message.as_deref() == message
+                && now.duration_since(burst.started) < self.config.timeout
+        }) {
+            let at = SystemTime::now();
+            burst.repeats += 1;
+            burst.first_repeat.get_or_insert(at);
+            burst.last_repeat = Some(at);
+            return (false, Vec::new());
+        }
+
+        let summaries = last
+            .take()
+            .and_then(|burst| burst.summary())
+            .into_iter()
+            .collect();
+        *last = Some(Burst {
+            callsite: metadata.callsite(),
+            name: origin.clone().unwrap_or_else(|| {
+                format!(
+                    "{}::{} ({})",
+                    metadata.target(),
+                    metadata.name(),
+                    log.location(metadata)
+                )
+            }),
+            origin,
+            message: message.map(ToOwned::to_owned),
+            id,
+            level,
+            started: now,
+            repeats: 0,
+            first_repeat: None,
+            last_repeat: None,
+        });
+        (true, summaries)
+    }
+
+    pub fn drain(&self) -> Vec<(u32, Level, Vec<u16>)> {
+        let Ok(mut last) = self.last.lock() else {
+            return Vec::new();
+        };
+        last.take()
+            .and_then(|burst| burst.summary())
+            .into_iter()
+            .collect()
+    }
+}
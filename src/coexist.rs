@@ -0,0 +1,167 @@
+use std::ptr;
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::memoryapi::{
+    CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, FILE_MAP_WRITE,
+};
+use winapi::um::synchapi::{CreateMutexW, ReleaseMutex, WaitForSingleObject};
+use winapi::um::winbase::{INFINITE, WAIT_ABANDONED, WAIT_OBJECT_0};
+use winapi::um::winnt::{HANDLE, PAGE_READWRITE};
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::wide::to_wide;
+
+/// Version of this crate, as recorded in the marker of every source it registers.
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Versions one marker can record.
+const SLOTS: usize = 8;
+/// Bytes per version, nul-padded.
+const SLOT_LEN: usize = 32;
+
+/// Process-wide record of the versions of this crate that registered a source, kept in a
+/// named file mapping that every copy of the crate in the process can open, unlike its
+/// statics.
+///
+/// The mapping exists as long as any copy holds a handle for the source.
+#[derive(Debug)]
+pub struct Marker(HANDLE);
+
+impl Drop for Marker {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Name of the kernel object `kind` for `source` in this process, in the session-local
+/// namespace. Source names are case-insensitive, so are the object names.
+fn object_name(source: &str, kind: &str) -> Vec<u16> {
+    let source = source.to_lowercase().replace('\\', "_");
+    to_wide(&format!(
+        "Local\\tracing-layer-win-eventlog.{}.{source}.{kind}",
+        std::process::id()
+    ))
+}
+
+/// Serializes access to the marker of `source` across the copies of the crate.
+struct Lock(HANDLE);
+
+impl Lock {
+    fn acquire(source: &str) -> Option<Self> {
+        let name = object_name(source, "lock");
+        let mutex = unsafe { CreateMutexW(ptr::null_mut(), 0, name.as_ptr()) };
+        if mutex.is_null() {
+            return None;
+        }
+        let wait = unsafe { WaitForSingleObject(mutex, INFINITE) };
+        if wait != WAIT_OBJECT_0 && wait != WAIT_ABANDONED {
+            unsafe {
+                CloseHandle(mutex);
+            }
+            return None;
+        }
+        Some(Self(mutex))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        unsafe {
+            ReleaseMutex(self.0);
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Calls `f` with the slots of the marker `mapping`.
+fn with_slots<T>(mapping: HANDLE, f: impl FnOnce(&mut [u8]) -> T) -> Option<T> {
+    let view = unsafe { MapViewOfFile(mapping, FILE_MAP_WRITE, 0, 0, SLOTS * SLOT_LEN) };
+    if view.is_null() {
+        return None;
+    }
+    let slots = unsafe { std::slice::from_raw_parts_mut(view.cast::<u8>(), SLOTS * SLOT_LEN) };
+    let result = f(slots);
+    unsafe {
+        UnmapViewOfFile(view);
+    }
+    Some(result)
+}
+
+fn versions(slots: &[u8]) -> Vec<String> {
+    slots
+        .chunks(SLOT_LEN)
+        .map(|slot| {
+            let len = slot.iter().position(|&b| b == 0).unwrap_or(SLOT_LEN);
+            String::from_utf8_lossy(&slot[..len]).into_owned()
+        })
+        .filter(|version| !version.is_empty())
+        .collect()
+}
+
+/// Records that this version of the crate holds `source` and reports other versions that
+/// do, see [`Diagnostic::VersionConflict`].
+pub(crate) fn claim(source: &str) -> Option<Marker> {
+    let _lock = Lock::acquire(source)?;
+    let name = object_name(source, "versions");
+    #[allow(clippy::cast_possible_truncation)]
+    let mapping = unsafe {
+        CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            ptr::null_mut(),
+            PAGE_READWRITE,
+            0,
+            (SLOTS * SLOT_LEN) as u32,
+            name.as_ptr(),
+        )
+    };
+    if mapping.is_null() {
+        return None;
+    }
+    let marker = Marker(mapping);
+
+    let others = with_slots(mapping, |slots| {
+        let versions = versions(slots);
+        if !versions.iter().any(|version| version == CRATE_VERSION) {
+            if let Some(slot) = slots
+                .chunks_mut(SLOT_LEN)
+                .find(|slot| slot.iter().all(|&b| b == 0))
+            {
+                let len = CRATE_VERSION.len().min(SLOT_LEN - 1);
+                slot[..len].copy_from_slice(&CRATE_VERSION.as_bytes()[..len]);
+            }
+        }
+        versions
+            .into_iter()
+            .filter(|version| version != CRATE_VERSION)
+            .collect::<Vec<_>>()
+    })?;
+    if !others.is_empty() {
+        diagnostics::emit(|| Diagnostic::VersionConflict {
+            source: source.to_owned(),
+            versions: others,
+        });
+    }
+    Some(marker)
+}
+
+/// The versions of this crate that registered `source` in this process while it has been
+/// held, including this one if it holds it, in the order they first did.
+///
+/// More than one version means separate copies of the crate write to the source with
+/// separate handles and configurations, e.g. because two dependencies embed different
+/// versions; a host can warn about the mismatch at startup. Versions that release the
+/// source stay listed until every copy has released it.
+#[must_use]
+pub fn crate_versions(source: &str) -> Vec<String> {
+    let Some(_lock) = Lock::acquire(source) else {
+        return Vec::new();
+    };
+    let name = object_name(source, "versions");
+    let mapping = unsafe { OpenFileMappingW(FILE_MAP_WRITE, 0, name.as_ptr()) };
+    if mapping.is_null() {
+        return Vec::new();
+    }
+    let marker = Marker(mapping);
+    with_slots(marker.0, |slots| versions(slots)).unwrap_or_default()
+}
@@ -0,0 +1,26 @@
+use std::future::Future;
+
+/// Field the task-local correlation ID is added as.
+pub const CORRELATION_ID_FIELD: &str = "correlation_id";
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/// Runs `future` with `id` as its correlation ID.
+///
+/// Unlike span context, the ID stays attached to the task across every `.await` in `future`.
+/// Events logged while it runs get a `correlation_id` field if the layer was built with
+/// [`EventLogLayer::with_task_correlation_id`](crate::EventLogLayer::with_task_correlation_id).
+pub async fn with_correlation_id<F>(id: impl Into<String>, future: F) -> F::Output
+where
+    F: Future,
+{
+    CORRELATION_ID.scope(id.into(), future).await
+}
+
+/// The correlation ID of the current task, if it runs inside [`with_correlation_id`].
+#[must_use]
+pub fn correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(Clone::clone).ok()
+}
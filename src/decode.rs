@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::winbase::{
+    FormatMessageW, FORMAT_MESSAGE_FROM_HMODULE, FORMAT_MESSAGE_FROM_SYSTEM,
+    FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+
+use crate::mapping::parse_code;
+use crate::wide::to_wide;
+
+/// Looks up the system text of the codes in the `hresult`, `win32_error` and `ntstatus`
+/// fields, returning `(field, text)` pairs.
+pub fn decode_error_codes(fields: &HashMap<String, String>) -> Vec<(String, String)> {
+    ["hresult", "win32_error", "ntstatus"]
+        .into_iter()
+        .filter_map(|name| {
+            let code = u32::try_from(parse_code(fields.get(name)?)?).ok()?;
+            let text = format_message(code, name == "ntstatus")?;
+            Some((name.to_owned(), text))
+        })
+        .collect()
+}
+
+fn format_message(code: u32, ntstatus: bool) -> Option<String> {
+    let mut flags = FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS;
+    let mut module = std::ptr::null_mut();
+    if ntstatus {
+        let ntdll = to_wide("ntdll.dll");
+        module = unsafe { GetModuleHandleW(ntdll.as_ptr()) };
+        if module.is_null() {
+            return None;
+        }
+        flags |= FORMAT_MESSAGE_FROM_HMODULE;
+    }
+
+    let mut buffer = vec![0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            flags,
+            module as *const _,
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            u32::try_from(buffer.len()).unwrap_or(u32::MAX),
+            std::ptr::null_mut(),
+        )
+    } as usize;
+    if len == 0 {
+        return None;
+    }
+
+    let text = String::from_utf16_lossy(&buffer[..len]);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
@@ -0,0 +1,76 @@
+#[cfg(feature = "internal-diagnostics")]
+use std::cell::Cell;
+#[cfg(feature = "internal-diagnostics")]
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Telemetry about the layer itself, see [`set_diagnostics_handler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// No handle was held for the source, or its handle went stale, so it was registered
+    /// again for a write.
+    RegistrationRetry { source: String, succeeded: bool },
+    /// Registering the source failed while configuring a layer; its writes register it
+    /// again. `code` is the Win32 error, if any.
+    RegistrationFailed { source: String, code: Option<u32> },
+    /// The account of the process could not be looked up, so events are written without a
+    /// user SID, see [`EventLogLayer::with_user_sid`](crate::EventLogLayer::with_user_sid).
+    UserSidUnavailable,
+    /// The thread of the non-blocking writer could not be spawned, so events are written
+    /// on the emitting thread.
+    WorkerUnavailable { source: String, error: String },
+    /// A failed write was appended to the spillover file.
+    Spilled { source: String },
+    /// Spilled events were written after the event log recovered.
+    Replayed { source: String, events: usize },
+    /// The queue of the non-blocking writer was full and an event was dropped.
+    QueueFull { source: String },
+    /// Other versions of this crate in the process hold handles for the source, see
+    /// [`crate_versions`](crate::crate_versions).
+    VersionConflict {
+        source: String,
+        versions: Vec<String>,
+    },
+}
+
+#[cfg(feature = "internal-diagnostics")]
+type Handler = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+#[cfg(feature = "internal-diagnostics")]
+fn handler() -> &'static RwLock<Option<Handler>> {
+    static HANDLER: OnceLock<RwLock<Option<Handler>>> = OnceLock::new();
+    HANDLER.get_or_init(|| RwLock::new(None))
+}
+
+#[cfg(feature = "internal-diagnostics")]
+thread_local! {
+    static IN_HANDLER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Hands the layer's own telemetry to `handler` instead of dropping it.
+///
+/// The handler is called directly, never through the event log. Diagnostics raised while
+/// it runs, e.g. because it logs through a layer of this crate, are dropped, so it cannot
+/// recurse into itself.
+#[cfg(feature = "internal-diagnostics")]
+pub fn set_diagnostics_handler(handler_fn: impl Fn(&Diagnostic) + Send + Sync + 'static) {
+    if let Ok(mut handler) = handler().write() {
+        *handler = Some(Arc::new(handler_fn));
+    }
+}
+
+#[cfg(feature = "internal-diagnostics")]
+pub(crate) fn emit(diagnostic: impl FnOnce() -> Diagnostic) {
+    if IN_HANDLER.get() {
+        return;
+    }
+    let Some(handler) = handler().read().ok().and_then(|handler| handler.clone()) else {
+        return;
+    };
+    IN_HANDLER.set(true);
+    handler(&diagnostic());
+    IN_HANDLER.set(false);
+}
+
+#[cfg(not(feature = "internal-diagnostics"))]
+#[inline]
+pub(crate) fn emit(_diagnostic: impl FnOnce() -> Diagnostic) {}
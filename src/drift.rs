@@ -0,0 +1,140 @@
+use std::fmt::Write as _;
+
+use crate::registry::{Hive, Key};
+
+const DIGEST_VALUE: &str = "LoggingConfigDigest";
+const CONFIG_VALUE: &str = "LoggingConfig";
+
+/// Detects changes of the logging configuration between runs.
+///
+/// At startup, a digest of the effective layer configuration and of the settings added
+/// with [`ConfigDrift::setting`] (e.g. the subscriber's filter) is compared to the one
+/// stored under the registry key by the previous run. If it differs, a WARN event lists
+/// the changed settings, e.g. `logging configuration changed: filter WARN→DEBUG`. The
+/// new configuration is then stored.
+#[derive(Debug, Clone)]
+pub struct ConfigDrift {
+    id: u32,
+    hive: Hive,
+    key: String,
+    settings: Vec<(String, String)>,
+}
+
+impl ConfigDrift {
+    pub const DEFAULT_ID: u32 = 1001;
+
+    /// Stores the configuration under `HKEY_LOCAL_MACHINE\<key>`, e.g. `SOFTWARE\MyApp`.
+    #[must_use]
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            id: Self::DEFAULT_ID,
+            hive: Hive::LocalMachine,
+            key: key.into(),
+            settings: Vec::new(),
+        }
+    }
+
+    /// Stores the configuration below `HKEY_CURRENT_USER` instead.
+    #[must_use]
+    pub const fn current_user(mut self) -> Self {
+        self.hive = Hive::CurrentUser;
+        self
+    }
+
+    #[must_use]
+    pub const fn id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Adds a setting the layer does not know about, e.g. `("filter", "WARN")`.
+    #[must_use]
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+
+    pub(crate) const fn event_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Combines the layer settings with the application settings, sorted by name.
+    pub(crate) fn effective(&self, layer: Vec<(&'static str, String)>) -> Vec<(String, String)> {
+        let mut settings: Vec<_> = layer
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .chain(self.settings.iter().cloned())
+            .collect();
+        settings.sort();
+        settings
+    }
+
+    /// Stores `settings` and returns the description of the changes since the previous
+    /// run, if there are any.
+    pub(crate) fn check(&self, settings: &[(String, String)]) -> Option<String> {
+        let config = serialize(settings);
+        let digest = digest(&config);
+
+        let key = Key::create(self.hive, &self.key)?;
+        let previous_digest = key.read_string(DIGEST_VALUE);
+        if previous_digest.as_deref() == Some(digest.as_str()) {
+            return None;
+        }
+        let previous = key.read_string(CONFIG_VALUE);
+        key.write_string(DIGEST_VALUE, &digest);
+        key.write_string(CONFIG_VALUE, &config);
+
+        // The first run has nothing to compare with.
+        previous_digest?;
+        let previous = previous.unwrap_or_default();
+        let before = key_values(&previous);
+        let after = key_values(&config);
+
+        let mut changes = Vec::new();
+        for (name, value) in &after {
+            match before.iter().find(|(n, _)| n == name) {
+                Some((_, old)) if old == value => {}
+                Some((_, old)) => changes.push(format!("{name} {old}\u{2192}{value}")),
+                None => changes.push(format!("{name} added ({value})")),
+            }
+        }
+        for (name, value) in &before {
+            if !after.iter().any(|(n, _)| n == name) {
+                changes.push(format!("{name} removed ({value})"));
+            }
+        }
+
+        Some(format!(
+            "logging configuration changed: {}",
+            if changes.is_empty() {
+                "unknown settings".to_owned()
+            } else {
+                changes.join(", ")
+            }
+        ))
+    }
+}
+
+/// FNV-1a, stable across runs and compiler versions.
+pub fn digest(config: &str) -> String {
+    let hash = config.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Renders settings as `name=value` lines.
+pub fn serialize(settings: &[(String, String)]) -> String {
+    let mut config = String::new();
+    for (name, value) in settings {
+        let _ = writeln!(config, "{name}={value}");
+    }
+    config
+}
+
+fn key_values(config: &str) -> Vec<(&str, &str)> {
+    config
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
@@ -0,0 +1,16 @@
+/// Writes the default description of an event alongside the configured one, so monitoring
+/// rules can be moved to a new format (e.g. templates or single-line descriptions) while
+/// the old one is still available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DualFormat {
+    /// Writes a second event with the same ID, level and type right after each event,
+    /// carrying the default description.
+    Consecutive,
+    /// Keeps a single event and puts the default description into its raw data: as the
+    /// `legacy_message` field of the data blob if one is configured, otherwise as UTF-8
+    /// text.
+    DataBlob,
+}
+
+/// Field of the data blob holding the default description in [`DualFormat::DataBlob`] mode.
+pub const LEGACY_MESSAGE_FIELD: &str = "legacy_message";
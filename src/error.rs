@@ -0,0 +1,315 @@
+use std::fmt;
+
+use crate::limits::MAX_STRING_LEN;
+use crate::names::SourceNameError;
+use crate::record::EventRecord;
+
+/// Win32 error code of a full event log (`ERROR_LOG_FILE_FULL`).
+pub const ERROR_LOG_FILE_FULL: u32 = 1502;
+/// Win32 error code of an invalid handle (`ERROR_INVALID_HANDLE`).
+pub const ERROR_INVALID_HANDLE: u32 = 6;
+
+/// How a failed Win32 call is expected to behave when retried, for deciding between
+/// retrying, falling back to another writer and giving up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Likely to succeed later, e.g. the event log service or a remote server is
+    /// unavailable, or the handle was closed.
+    Transient,
+    /// Fails the same way until the input or configuration changes.
+    Permanent,
+    /// The account lacks the rights for the call.
+    Permission,
+    /// The log, disk or memory is full; succeeds once space is freed.
+    ResourceFull,
+}
+
+impl ErrorClass {
+    /// Classifies a Win32 error code; codes not known to be anything else are permanent.
+    #[must_use]
+    pub const fn of_code(code: u32) -> Self {
+        match code {
+            // ERROR_ACCESS_DENIED, ERROR_PRIVILEGE_NOT_HELD, ERROR_LOGON_FAILURE
+            5 | 1314 | 1326 => Self::Permission,
+            // ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY, ERROR_DISK_FULL,
+            // ERROR_NO_SYSTEM_RESOURCES, ERROR_LOG_FILE_FULL, ERROR_NOT_ENOUGH_QUOTA
+            8 | 14 | 112 | 1450 | ERROR_LOG_FILE_FULL | 1816 => Self::ResourceFull,
+            // ERROR_INVALID_HANDLE, ERROR_SEM_TIMEOUT, ERROR_BUSY, WAIT_TIMEOUT,
+            // ERROR_SERVICE_NOT_ACTIVE, ERROR_EVENTLOG_CANT_START, RPC_S_UNKNOWN_IF,
+            // RPC_S_SERVER_UNAVAILABLE, RPC_S_SERVER_TOO_BUSY, RPC_S_CALL_FAILED,
+            // EPT_S_NOT_REGISTERED
+            ERROR_INVALID_HANDLE | 121 | 170 | 258 | 1062 | 1501 | 1717 | 1722 | 1723 | 1726
+            | 1753 => Self::Transient,
+            _ => Self::Permanent,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Transient => "transient",
+            Self::Permanent => "permanent",
+            Self::Permission => "permission",
+            Self::ResourceFull => "resource-full",
+        }
+    }
+
+    /// Whether retrying the same call later can succeed without changing the input.
+    #[must_use]
+    pub const fn is_retryable(self) -> bool {
+        matches!(self, Self::Transient | Self::ResourceFull)
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Errors of the fallible write APIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// `RegisterEventSourceW` failed for the source.
+    Register { source: String, code: u32 },
+    /// `ReportEventW` failed; `attempt` is 2 if it failed again after the source was
+    /// registered anew, see [`WriteError::attempt`].
+    Write { code: u32, attempt: u32 },
+    /// The message contains a nul character.
+    InvalidMessage,
+    /// A description or insertion string is longer than
+    /// [`MAX_STRING_LEN`](crate::limits::MAX_STRING_LEN) UTF-16 code units.
+    TooLong { len: usize },
+    /// `EvtQuery`, `EvtNext` or `EvtRender` failed while reading events.
+    Query { code: u32 },
+    /// The layer builder was not given a source name.
+    MissingSource,
+    /// A global subscriber was already set when installing the layer.
+    AlreadyInstalled,
+    /// Writing the registration of an event source to the registry failed.
+    Registry { code: u32 },
+    /// The source name cannot be registered.
+    InvalidSourceName(SourceNameError),
+    /// The source is already registered with a different log or message file.
+    SourceExists(SourceExistsError),
+}
+
+/// A source that is already registered differently than requested. Its events would be
+/// rendered with the wrong templates or land in the wrong log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceExistsError {
+    pub source: String,
+    /// The log the source is registered with.
+    pub log: String,
+    /// The `EventMessageFile` it is registered with, if any.
+    pub message_file: Option<String>,
+}
+
+impl fmt::Display for SourceExistsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event source {} is already registered in {}",
+            self.source, self.log
+        )?;
+        if let Some(file) = &self.message_file {
+            write!(f, " with message file {file}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SourceExistsError {}
+
+impl Error {
+    pub(crate) fn last_os_code() -> u32 {
+        std::io::Error::last_os_error()
+            .raw_os_error()
+            .and_then(|c| u32::try_from(c).ok())
+            .unwrap_or_default()
+    }
+
+    /// The Win32 error code, if the error originates from a Win32 call.
+    #[must_use]
+    pub const fn code(&self) -> Option<u32> {
+        match self {
+            Self::Register { code, .. }
+            | Self::Write { code, .. }
+            | Self::Query { code }
+            | Self::Registry { code } => Some(*code),
+            Self::InvalidMessage
+            | Self::TooLong { .. }
+            | Self::MissingSource
+            | Self::AlreadyInstalled
+            | Self::InvalidSourceName(_)
+            | Self::SourceExists(_) => None,
+        }
+    }
+
+    /// How the error is expected to behave when retried. Errors that do not originate from
+    /// a Win32 call are permanent.
+    #[must_use]
+    pub const fn class(&self) -> ErrorClass {
+        match self.code() {
+            Some(code) => ErrorClass::of_code(code),
+            None => ErrorClass::Permanent,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_log_full(&self) -> bool {
+        matches!(self.code(), Some(ERROR_LOG_FILE_FULL))
+    }
+
+    #[must_use]
+    pub const fn is_invalid_handle(&self) -> bool {
+        matches!(self.code(), Some(ERROR_INVALID_HANDLE))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register { source, code } => {
+                write!(f, "failed to register event source {source} (error {code})")
+            }
+            Self::Write { code, .. } => write!(f, "failed to write to event log (error {code})"),
+            Self::InvalidMessage => write!(f, "message contains a nul character"),
+            Self::TooLong { len } => write!(
+                f,
+                "string of {len} UTF-16 code units exceeds the limit of {MAX_STRING_LEN}"
+            ),
+            Self::Query { code } => write!(f, "failed to query the event log (error {code})"),
+            Self::MissingSource => write!(f, "no event source name given"),
+            Self::AlreadyInstalled => write!(f, "a global subscriber is already set"),
+            Self::InvalidSourceName(error) => error.fmt(f),
+            Self::SourceExists(error) => error.fmt(f),
+            Self::Registry { code } => {
+                write!(
+                    f,
+                    "failed to register the event source in the registry (error {code})"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidSourceName(error) => Some(error),
+            Self::SourceExists(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// What failed when writing an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteErrorKind {
+    Register,
+    Write,
+    InvalidMessage,
+    /// A string of the event exceeds the length limit and the layer is configured to
+    /// reject such events, see [`OversizedMessage::Reject`](crate::OversizedMessage::Reject).
+    TooLong,
+    /// The `eventlog.type` field holds no valid event type. The event was still written,
+    /// with the type following its level.
+    InvalidEventType,
+    /// The event lacks fields its template expects, listed in the `missing_fields` field of
+    /// the record. The event was still written, with the slots left as `{name}`. Only
+    /// checked in debug builds or with the `template-validation` feature.
+    MissingTemplateFields,
+}
+
+/// Field of [`WriteError::record`] listing the template slots an event did not fill.
+pub const MISSING_FIELDS_FIELD: &str = "missing_fields";
+
+/// A failed write as handed to the error handler of the layer.
+#[derive(Debug, Clone)]
+pub struct WriteError {
+    /// The event that was not written.
+    pub record: EventRecord,
+    pub kind: WriteErrorKind,
+    pub win32_code: Option<u32>,
+    /// Number of the attempt that failed, starting at 1; 2 if the write failed again after
+    /// the source was registered anew because its handle went stale.
+    pub attempt: u32,
+}
+
+impl WriteError {
+    /// See [`Error::class`].
+    #[must_use]
+    pub const fn class(&self) -> ErrorClass {
+        match self.win32_code {
+            Some(code) => ErrorClass::of_code(code),
+            None => ErrorClass::Permanent,
+        }
+    }
+
+    pub(crate) fn new(record: EventRecord, error: &Error) -> Self {
+        Self {
+            record,
+            kind: match error {
+                Error::Register { .. }
+                | Error::MissingSource
+                | Error::AlreadyInstalled
+                | Error::Registry { .. }
+                | Error::InvalidSourceName(_)
+                | Error::SourceExists(_) => WriteErrorKind::Register,
+                Error::Write { .. } | Error::Query { .. } => WriteErrorKind::Write,
+                Error::InvalidMessage => WriteErrorKind::InvalidMessage,
+                Error::TooLong { .. } => WriteErrorKind::TooLong,
+            },
+            win32_code: error.code(),
+            attempt: match error {
+                Error::Write { attempt, .. } => *attempt,
+                _ => 1,
+            },
+        }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.kind {
+            WriteErrorKind::Register => "register event source",
+            WriteErrorKind::Write => "write to event log",
+            WriteErrorKind::InvalidMessage => "write event with a nul character",
+            WriteErrorKind::TooLong => "write event exceeding the length limit",
+            WriteErrorKind::InvalidEventType => "apply the eventlog.type field",
+            WriteErrorKind::MissingTemplateFields => "fill all slots of the template",
+        };
+        write!(
+            f,
+            "Failed to {action} (source {}, event {}, attempt {}",
+            self.record.source, self.record.id, self.attempt
+        )?;
+        if let Some(code) = self.win32_code {
+            write!(f, ", error {code}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// A failed batch write, see [`write_events`](crate::write_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    /// Number of records written before the failure; the record at this index failed.
+    pub written: usize,
+    pub error: Error,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} records", self.error, self.written)
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
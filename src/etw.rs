@@ -0,0 +1,151 @@
+use std::fmt::{self, Write as _};
+use tracing::field::Visit;
+use tracing::Level;
+use winapi::shared::evntprov::{
+    EventProviderEnabled, EventRegister, EventUnregister, EVENT_DATA_DESCRIPTOR, REGHANDLE,
+};
+use winapi::shared::guiddef::GUID;
+
+use crate::error::Error;
+use crate::log_fields::LogMetadata;
+use crate::normalize::FieldNameNormalization;
+use crate::record::FieldValue;
+
+/// An ETW provider registered with `EventRegister`, unregistered when dropped.
+#[derive(Debug)]
+pub struct Provider {
+    pub name: String,
+    pub id: u128,
+    pub handle: REGHANDLE,
+}
+
+impl Provider {
+    /// Fails with [`Error::Register`] if `EventRegister` fails.
+    pub fn register(name: &str, id: u128) -> Result<Self, Error> {
+        let guid = to_guid(id);
+        let mut handle: REGHANDLE = 0;
+        let code = unsafe { EventRegister(&guid, None, std::ptr::null_mut(), &mut handle) };
+        if code != 0 {
+            return Err(Error::Register {
+                source: name.to_owned(),
+                code,
+            });
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            id,
+            handle,
+        })
+    }
+
+    /// Whether a trace session listens for events of `level` and `keyword`.
+    pub fn enabled(&self, level: u8, keyword: u64) -> bool {
+        unsafe { EventProviderEnabled(self.handle, level, keyword) != 0 }
+    }
+}
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        unsafe {
+            EventUnregister(self.handle);
+        }
+    }
+}
+
+/// The ETW level of `level`: ERROR, WARN and INFO map to the levels of the same name,
+/// DEBUG and TRACE to verbose.
+pub const fn etw_level(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 2,
+        Level::WARN => 3,
+        Level::INFO => 4,
+        _ => 5,
+    }
+}
+
+pub fn data_descriptor(bytes: &[u8], kind: u8) -> EVENT_DATA_DESCRIPTOR {
+    let mut descriptor = EVENT_DATA_DESCRIPTOR {
+        Ptr: bytes.as_ptr() as u64,
+        Size: u32::try_from(bytes.len()).unwrap_or_default(),
+        u: unsafe { std::mem::zeroed() },
+    };
+    unsafe {
+        descriptor.u.s_mut().Type = kind;
+    }
+    descriptor
+}
+
+fn to_guid(id: u128) -> GUID {
+    let bytes = id.to_be_bytes();
+    let mut data4 = [0; 8];
+    data4.copy_from_slice(&bytes[8..]);
+    GUID {
+        Data1: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        Data2: u16::from_be_bytes([bytes[4], bytes[5]]),
+        Data3: u16::from_be_bytes([bytes[6], bytes[7]]),
+        Data4: data4,
+    }
+}
+
+/// Collects the fields of an event with their types, in the order they were recorded.
+///
+/// The visitor of `EventLogLayer` formats values for the description and handles its
+/// reserved fields; ETW payloads need the types and the order instead, so the ETW layers
+/// share only the field name normalization with it.
+pub struct FieldVisitor<'a> {
+    pub fields: Vec<(String, FieldValue)>,
+    pub normalization: Option<&'a FieldNameNormalization>,
+    pub log: LogMetadata,
+}
+
+impl<'a> FieldVisitor<'a> {
+    pub fn new(normalization: Option<&'a FieldNameNormalization>) -> Self {
+        Self {
+            fields: Vec::new(),
+            normalization,
+            log: LogMetadata::default(),
+        }
+    }
+
+    fn push(&mut self, field: &tracing::field::Field, value: FieldValue) {
+        let name = match self.normalization {
+            Some(normalization) if field.name() != "message" => {
+                normalization.apply(field.name()).into_owned()
+            }
+            _ => field.name().to_owned(),
+        };
+        self.fields.push((name, value));
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        let mut text = String::new();
+        let _ = write!(text, "{value:?}");
+        self.push(field, FieldValue::Str(text));
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if !self.log.record_str(field.name(), value) {
+            self.push(field, FieldValue::Str(value.to_owned()));
+        }
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        if !self.log.record_u64(field.name(), value) {
+            self.push(field, FieldValue::U64(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.push(field, FieldValue::I64(value));
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.push(field, FieldValue::F64(value));
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.push(field, FieldValue::Bool(value));
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::str::FromStr;
+use tracing::Level;
+
+/// The type of an event log entry.
+///
+/// By default the type follows the level: ERROR events are errors, WARN events warnings
+/// and all others information, see [`EventTypeMapping`] to change this. The reserved field `eventlog.type` selects it explicitly,
+/// e.g. `eventlog.type = "audit_failure"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventType {
+    Error,
+    Warning,
+    Information,
+    Success,
+    AuditSuccess,
+    AuditFailure,
+}
+
+/// Name of the field overriding the event type.
+pub const EVENT_TYPE_FIELD: &str = "eventlog.type";
+
+impl EventType {
+    #[must_use]
+    pub const fn from_level(level: Level) -> Self {
+        match level {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warning,
+            Level::INFO | Level::DEBUG | Level::TRACE => Self::Information,
+        }
+    }
+
+    /// The name accepted by the `eventlog.type` field.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Information => "information",
+            Self::Success => "success",
+            Self::AuditSuccess => "audit_success",
+            Self::AuditFailure => "audit_failure",
+        }
+    }
+
+    /// The `EVENTLOG_*` constant passed to `ReportEventW`.
+    #[cfg(windows)]
+    pub(crate) const fn raw(self) -> u16 {
+        use winapi::um::winnt::{
+            EVENTLOG_AUDIT_FAILURE, EVENTLOG_AUDIT_SUCCESS, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_SUCCESS, EVENTLOG_WARNING_TYPE,
+        };
+
+        match self {
+            Self::Error => EVENTLOG_ERROR_TYPE,
+            Self::Warning => EVENTLOG_WARNING_TYPE,
+            Self::Information => EVENTLOG_INFORMATION_TYPE,
+            Self::Success => EVENTLOG_SUCCESS,
+            Self::AuditSuccess => EVENTLOG_AUDIT_SUCCESS,
+            Self::AuditFailure => EVENTLOG_AUDIT_FAILURE,
+        }
+    }
+}
+
+impl From<Level> for EventType {
+    fn from(level: Level) -> Self {
+        Self::from_level(level)
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The event type of each level for events without an `eventlog.type` field.
+///
+/// The default is [`EventType::from_level`]. Any level can be mapped to any type, e.g.
+/// INFO to [`EventType::Success`]:
+///
+/// ```
+/// use tracing::Level;
+/// use tracing_layer_win_eventlog::{EventType, EventTypeMapping};
+///
+/// let mapping = EventTypeMapping::default().with(Level::INFO, EventType::Success);
+/// assert_eq!(mapping.get(Level::INFO), EventType::Success);
+/// assert_eq!(mapping.get(Level::DEBUG), EventType::Information);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventTypeMapping {
+    /// Indexed from ERROR to TRACE.
+    types: [EventType; 5],
+}
+
+impl Default for EventTypeMapping {
+    fn default() -> Self {
+        Self {
+            types: [
+                EventType::Error,
+                EventType::Warning,
+                EventType::Information,
+                EventType::Information,
+                EventType::Information,
+            ],
+        }
+    }
+}
+
+impl EventTypeMapping {
+    const fn index(level: Level) -> usize {
+        match level {
+            Level::ERROR => 0,
+            Level::WARN => 1,
+            Level::INFO => 2,
+            Level::DEBUG => 3,
+            Level::TRACE => 4,
+        }
+    }
+
+    /// Maps `level` to `event_type`.
+    #[must_use]
+    pub const fn with(mut self, level: Level, event_type: EventType) -> Self {
+        self.types[Self::index(level)] = event_type;
+        self
+    }
+
+    /// The event type of `level`.
+    #[must_use]
+    pub const fn get(&self, level: Level) -> EventType {
+        self.types[Self::index(level)]
+    }
+}
+
+/// A value of the `eventlog.type` field that names no event type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEventTypeError(pub String);
+
+impl fmt::Display for ParseEventTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid event type {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseEventTypeError {}
+
+impl FromStr for EventType {
+    type Err = ParseEventTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Error,
+            Self::Warning,
+            Self::Information,
+            Self::Success,
+            Self::AuditSuccess,
+            Self::AuditFailure,
+        ]
+        .into_iter()
+        .find(|t| t.as_str().eq_ignore_ascii_case(s))
+        .ok_or_else(|| ParseEventTypeError(s.to_owned()))
+    }
+}
@@ -1,186 +1,1844 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::span;
+use winapi::um::winnls::GetSystemDefaultLCID;
+use winapi::um::winnt::HANDLE;
+
+use crate::adaptive::{AdaptiveFilter, AdaptiveVerbosity};
+use crate::affinity::thread_source;
+use crate::banner::StartupBanner;
+use crate::binary::{decode_binary, DATA_FIELD};
+use crate::blob::DataBlob;
+use crate::builder::EventLogLayerBuilder;
+use crate::catalog::TemplateCatalogs;
+use crate::coalesce::{Coalescer, DuplicateCoalescing};
+use crate::decode::decode_error_codes;
+use crate::diagnostics::{self, Diagnostic};
+use crate::drift::{digest, serialize, ConfigDrift};
+#[cfg(not(feature = "lean"))]
+use crate::dual::{DualFormat, LEGACY_MESSAGE_FIELD};
+use crate::error::{BatchError, Error, WriteError, WriteErrorKind};
+use crate::event_type::{EventType, EventTypeMapping, EVENT_TYPE_FIELD};
+use crate::fallback::{Fallback, FallbackSink};
+use crate::flood::{FloodGuard, FloodProtection, Occurrence};
+use crate::follows::{link, FollowsFrom, FOLLOWS_FROM_FIELD, SPAN_ID_FIELD};
+#[cfg(not(feature = "lean"))]
+use crate::formatter::{
+    DefaultFormatter, EventContext, EventFormatter, JsonFormatter, TemplateFormatter,
+};
+use crate::guard::{self, EventLogGuard};
+use crate::handle::EventLogHandle;
+use crate::layout::LineEnding;
+use crate::log_fields::LogMetadata;
+use crate::mapping::ValueMapping;
+use crate::missing::MissingMessage;
+use crate::normalize::FieldNameNormalization;
+use crate::output::{LazySource, Output};
+use crate::oversize::OversizedMessage;
+use crate::process::{self, ContextScope, StaticContext};
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::record::{EventRecord, FieldValue};
+use crate::rollup::Rollup;
+use crate::sampling::{ExponentialSampling, Sampler};
+use crate::sid::{is_sid_field, SidResolver, UserSid};
+use crate::sink::EventSink;
+use crate::source::{EventSource, EventSourceRegistry, HandleOwnership};
+use crate::span_fields::SpanFields;
+use crate::span_name::SpanLabel;
+use crate::spill::{SpillFile, Spillover};
+use crate::stack::{StackCompression, StackDeduper};
+use crate::stats::{EventLogStats, StatsSummary};
+use crate::target_id::TargetIds;
+use crate::template::Template;
+use crate::time::{format_duration, format_system_time};
+use crate::wide::to_wide;
+use crate::worker::{NonBlocking, Worker};
 use tracing::field::Visit;
-use tracing::{Level, Subscriber};
-use tracing_subscriber::registry::LookupSpan;
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
 use tracing_subscriber::Layer;
-use winapi::shared::minwindef::DWORD;
-use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceA, ReportEventA};
-use winapi::um::winnt::{EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE};
-
-#[allow(clippy::manual_c_str_literals)]
-pub fn write_to_event_log(event_id: u32, level: Level, message: &str, log_name: &str) {
-    let event_source = unsafe {
-        RegisterEventSourceA(
-            std::ptr::null(),
-            format!("{log_name}\0").as_ptr().cast::<i8>(),
-        )
-    };
 
-    if event_source.is_null() {
-        eprintln!("Failed to register event source");
-        return;
+/// Writes `message` to `log_name` through the shared handle of the source.
+///
+/// # Errors
+/// Fails if the source cannot be registered, the message contains a nul character or
+/// `ReportEventW` fails.
+pub fn write_event(
+    log_name: &str,
+    event_id: u32,
+    level: Level,
+    message: &str,
+) -> Result<(), Error> {
+    EventSourceRegistry::try_get_or_register(log_name)?.try_write(event_id, level, message)
+}
+
+/// Writes buffered `records` to `log_name` in order, e.g. to replay events persisted
+/// during an event log outage.
+///
+/// The source handle is looked up once and the buffers are reused across records. The
+/// message of a record becomes the description, followed by its fields and its original
+/// timestamp as `original_timestamp`; messages that already are a description of this
+/// crate (starting with `ID: `), like those of [`WriteError`] records, are kept as they
+/// are.
+///
+/// # Errors
+/// Stops at the first record that cannot be written; [`BatchError::written`] tells how
+/// many were written before.
+pub fn write_events(log_name: &str, records: &[EventRecord]) -> Result<(), BatchError> {
+    let source = EventSourceRegistry::try_get_or_register(log_name)
+        .map_err(|error| BatchError { written: 0, error })?;
+    let mut output = Output::new(log_name.to_owned());
+    output.source = Some(source);
+    output.try_write_records(records)
+}
+
+/// Name of the field holding the event category, a number up to 65535 that a category
+/// message file maps to a task category name.
+pub const CATEGORY_FIELD: &str = "category";
+
+pub struct EventLogLayer {
+    output: Output,
+    default_event_id: Option<u32>,
+    default_category: u16,
+    target_ids: TargetIds,
+    max_level: LevelFilter,
+    flood: Option<Arc<FloodGuard>>,
+    sid_resolver: Option<SidResolver>,
+    context: Vec<StaticContext>,
+    span_close_events: bool,
+    omit_fieldless_spans: bool,
+    span_names: HashMap<&'static str, Vec<&'static str>>,
+    span_fields: bool,
+    follows_from: bool,
+    #[cfg(feature = "tokio")]
+    task_correlation_id: bool,
+    rollups: bool,
+    templates: HashMap<u32, Template>,
+    severities: HashMap<u32, EventType>,
+    adaptive: Option<AdaptiveFilter>,
+    sampler: Option<Arc<Sampler>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    coalescer: Option<Arc<Coalescer>>,
+    stats_summary: Option<StatsSummary>,
+    stacks: Option<StackDeduper>,
+    value_mapping: Option<ValueMapping>,
+    decode_error_codes: bool,
+    data_blob: Option<DataBlob>,
+    banner: Option<StartupBanner>,
+    config_drift: Option<ConfigDrift>,
+    missing_message: MissingMessage,
+    normalization: Option<FieldNameNormalization>,
+    non_blocking: Option<NonBlocking>,
+    worker: Option<Worker>,
+    #[cfg(not(feature = "lean"))]
+    dual_format: Option<DualFormat>,
+    alerts: HashMap<u32, u32>,
+    floors: HashMap<u32, Level>,
+    #[cfg(not(feature = "lean"))]
+    formatter: Arc<dyn EventFormatter>,
+    insertion_strings: HashMap<u32, Vec<String>>,
+}
+
+impl EventLogLayer {
+    #[must_use]
+    pub fn new(log_name: String) -> Self {
+        Self::from_output(Output::new(log_name), None, 0)
     }
 
-    let event_type = match level {
-        Level::ERROR => EVENTLOG_ERROR_TYPE,
-        Level::WARN => EVENTLOG_WARNING_TYPE,
-        Level::INFO | Level::DEBUG | Level::TRACE => EVENTLOG_INFORMATION_TYPE,
-    };
+    /// Like [`EventLogLayer::new`], but registers the source on the first write instead of
+    /// now, and again on later writes as long as registering fails, so the layer can be
+    /// installed before the event log service is reachable, e.g. early at boot or in
+    /// tests.
+    #[must_use]
+    pub fn new_lazy(log_name: String) -> Self {
+        let mut output = Output::unregistered(log_name, None);
+        output.lazy = Some(Arc::new(LazySource::new(true)));
+        Self::from_output(output, None, 0)
+    }
 
-    let Ok(message_cstr) = CString::new(message) else {
-        eprintln!("failed to create CString from message: {message}");
-        return;
-    };
+    /// Writes through `source`, which is kept alive as long as the application holds it,
+    /// e.g. across subscriber rebuilds, so the source is registered exactly once.
+    #[must_use]
+    pub fn from_source(source: Arc<EventSource>) -> Self {
+        Self::from_output(Output::with_source(source), None, 0)
+    }
 
-    let result = unsafe {
-        ReportEventA(
-            event_source,
-            event_type,
-            0,
-            event_id as DWORD,
-            std::ptr::null_mut(),
-            1,
-            0,
-            &mut message_cstr.as_ptr(),
-            std::ptr::null_mut(),
+    /// Writes through a handle registered by other code, see
+    /// [`EventSource::from_raw_handle`].
+    ///
+    /// # Errors
+    /// Fails if `handle` is null.
+    ///
+    /// # Safety
+    /// See [`EventSource::from_raw_handle`].
+    pub unsafe fn from_raw_handle(
+        name: &str,
+        handle: HANDLE,
+        ownership: HandleOwnership,
+    ) -> Result<Self, Error> {
+        let source = unsafe { EventSource::from_raw_handle(name, handle, ownership)? };
+        Ok(Self::from_source(Arc::new(source)))
+    }
+
+    /// Writes to the event log of `server` (a UNC name such as `\\logs01`) over RPC.
+    ///
+    /// # Errors
+    /// Fails if the source cannot be registered on the server, e.g. because it cannot be
+    /// reached.
+    pub fn new_remote(server: &str, source: &str) -> Result<Self, Error> {
+        Self::builder().server(server).source(source).build()
+    }
+
+    /// Configures a layer step by step, failing early if the source cannot be registered.
+    pub fn builder() -> EventLogLayerBuilder {
+        EventLogLayerBuilder::default()
+    }
+
+    pub(crate) fn from_output(
+        output: Output,
+        default_event_id: Option<u32>,
+        default_category: u16,
+    ) -> Self {
+        Self {
+            output,
+            default_event_id,
+            default_category,
+            target_ids: TargetIds::default(),
+            max_level: LevelFilter::TRACE,
+            flood: None,
+            sid_resolver: None,
+            context: Vec::new(),
+            span_close_events: false,
+            omit_fieldless_spans: false,
+            span_names: HashMap::new(),
+            span_fields: false,
+            follows_from: false,
+            #[cfg(feature = "tokio")]
+            task_correlation_id: false,
+            rollups: true,
+            templates: HashMap::new(),
+            severities: HashMap::new(),
+            adaptive: None,
+            sampler: None,
+            rate_limiter: None,
+            coalescer: None,
+            stats_summary: None,
+            stacks: None,
+            value_mapping: None,
+            decode_error_codes: true,
+            data_blob: None,
+            banner: None,
+            config_drift: None,
+            missing_message: MissingMessage::Omit,
+            normalization: None,
+            non_blocking: None,
+            worker: None,
+            #[cfg(not(feature = "lean"))]
+            dual_format: None,
+            alerts: HashMap::new(),
+            floors: HashMap::new(),
+            #[cfg(not(feature = "lean"))]
+            formatter: Arc::new(DefaultFormatter),
+            insertion_strings: HashMap::new(),
+        }
+    }
+
+    /// The event ID of events without an `id` field whose target is `prefix` or a module
+    /// below it, e.g. `"db"` for `db` and `db::pool` but not `dbus`. The longest matching
+    /// prefix wins; events of other targets keep the default ID. A trailing `::*` is
+    /// ignored.
+    #[must_use]
+    pub fn with_target_id(mut self, prefix: &str, id: u32) -> Self {
+        self.target_ids.insert_fixed(prefix, id);
+        self
+    }
+
+    /// Like [`with_target_id`](EventLogLayer::with_target_id), with one ID per level
+    /// counted from `base`: `base` for TRACE up to `base + 4` for ERROR.
+    #[must_use]
+    pub fn with_target_id_range(mut self, prefix: &str, base: u32) -> Self {
+        self.target_ids.insert_range(prefix, base);
+        self
+    }
+
+    pub(crate) fn with_target_ids(mut self, target_ids: TargetIds) -> Self {
+        self.target_ids = target_ids;
+        self
+    }
+
+    /// What to do with descriptions and insertion strings above the length limit of
+    /// `ReportEventW`, see [`OversizedMessage`]. Truncated by default.
+    #[must_use]
+    pub const fn with_oversized_messages(mut self, policy: OversizedMessage) -> Self {
+        self.output.oversized = policy;
+        self
+    }
+
+    /// Ignores spans and events more verbose than `max_level`, e.g. TRACE and DEBUG with
+    /// [`LevelFilter::INFO`], before any field is recorded. TRACE by default.
+    ///
+    /// Like the `enabled` method of any layer, this disables them for the whole subscriber,
+    /// so other layers do not see them either. To limit only this layer, add it with
+    /// [`Layer::with_filter`] instead.
+    #[must_use]
+    pub const fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Counts of the events written, failed and dropped so far by the layer and its
+    /// handles.
+    #[must_use]
+    pub fn stats(&self) -> EventLogStats {
+        self.output.stats.snapshot()
+    }
+
+    /// Writes the [`stats`](Self::stats) as an INFO event with ID `id` every `interval`,
+    /// with the first event after it elapsed, so a layer losing events shows in the event
+    /// log itself.
+    #[must_use]
+    pub fn with_stats_summary(mut self, id: u32, interval: Duration) -> Self {
+        self.stats_summary = Some(StatsSummary::new(id, interval));
+        self
+    }
+
+    #[must_use]
+    pub fn with_flood_protection(mut self, config: FloodProtection) -> Self {
+        self.flood = Some(Arc::new(FloodGuard::new(config)));
+        self
+    }
+
+    /// Caps the number of events written per level and time window, see [`RateLimit`].
+    ///
+    /// The summary of the events suppressed in a window is not written when the window
+    /// closes but with the next event the layer sees after that, or when the layer or its
+    /// [`EventLogGuard`] is dropped. A burst followed by silence shows up in the log only
+    /// then.
+    #[must_use]
+    pub fn with_rate_limit(mut self, config: RateLimit) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(config)));
+        self
+    }
+
+    /// Writes a burst of identical consecutive events once, followed by a "last message
+    /// repeated N times" event, see [`DuplicateCoalescing`].
+    #[must_use]
+    pub fn with_duplicate_coalescing(mut self, config: DuplicateCoalescing) -> Self {
+        self.coalescer = Some(Arc::new(Coalescer::new(config)));
+        self
+    }
+
+    /// Fields named `sid` or ending in `_sid` that hold a string SID get an additional
+    /// `<field>_account` field with the resolved `DOMAIN\user` name.
+    #[must_use]
+    pub fn with_sid_resolution(mut self, enabled: bool) -> Self {
+        self.sid_resolver = enabled.then(SidResolver::default);
+        self
+    }
+
+    /// Attributes every event to the account the process runs as, looked up once from the
+    /// process token, so events can be filtered by user in Event Viewer.
+    #[must_use]
+    pub fn with_user_sid(mut self, enabled: bool) -> Self {
+        self.output.user_sid = if enabled {
+            UserSid::current().map(Arc::new).or_else(|| {
+                diagnostics::emit(|| Diagnostic::UserSidUnavailable);
+                None
+            })
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Records the parent process ID and image name once and writes them on the startup
+    /// event (and, depending on `scope`, on every event).
+    #[must_use]
+    pub fn with_process_ancestry(mut self, scope: ContextScope) -> Self {
+        self.context.push(process::ancestry(scope));
+        self
+    }
+
+    /// Records the session ID and whether the process runs in an interactive session
+    /// (as opposed to session 0) and writes them on the startup event (and, depending on
+    /// `scope`, on every event).
+    #[must_use]
+    pub fn with_session_context(mut self, scope: ContextScope) -> Self {
+        self.context.push(process::session(scope));
+        self
+    }
+
+    /// Writes an event when a span closes. Its duration is measured with the monotonic
+    /// clock and rendered separately from the wall-clock open and close timestamps.
+    #[must_use]
+    pub const fn with_span_close_events(mut self, enabled: bool) -> Self {
+        self.span_close_events = enabled;
+        self
+    }
+
+    /// Preregisters the message shape of event `id`, see [`Template`]. Events with this ID
+    /// are rendered from the template instead of the default layout.
+    #[must_use]
+    pub fn with_template(mut self, id: u32, template: &str) -> Self {
+        self.templates.insert(id, Template::parse(template));
+        self
+    }
+
+    /// Adds the templates of the catalog for `lcid`, or for the system locale if `None`.
+    ///
+    /// Templates registered with [`EventLogLayer::with_template`] take precedence. Event types
+    /// set in the catalog override the ones derived from the level.
+    #[must_use]
+    pub fn with_template_catalogs(
+        mut self,
+        catalogs: &TemplateCatalogs,
+        lcid: Option<u32>,
+    ) -> Self {
+        let lcid = lcid.unwrap_or_else(|| unsafe { GetSystemDefaultLCID() });
+        if let Some(catalog) = catalogs.select(lcid) {
+            for (id, template) in catalog.templates() {
+                self.templates.entry(id).or_insert_with(|| template.clone());
+            }
+            self.severities.extend(catalog.severities());
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn with_adaptive_verbosity(mut self, config: AdaptiveVerbosity) -> Self {
+        self.adaptive = Some(AdaptiveFilter::new(config));
+        self
+    }
+
+    #[must_use]
+    pub fn with_exponential_sampling(mut self, config: ExponentialSampling) -> Self {
+        self.sampler = Some(Arc::new(Sampler::new(config)));
+        self
+    }
+
+    /// Leaves the `source` line out if no span in scope has fields (or a name), e.g. for
+    /// spans created with `#[instrument(skip_all)]`. Off by default.
+    #[must_use]
+    pub const fn with_fieldless_spans_omitted(mut self, omit: bool) -> Self {
+        self.omit_fieldless_spans = omit;
+        self
+    }
+
+    /// Shows the values of `fields` next to the span's name in the `source` line, e.g.
+    /// `request[42]` for `span!(Level::INFO, "request", id = 42)` with `&["id"]`.
+    #[must_use]
+    pub fn with_span_name_fields(mut self, span: &'static str, fields: &[&'static str]) -> Self {
+        self.span_names.insert(span, fields.to_vec());
+        self
+    }
+
+    /// Adds the fields of the spans in scope to every event, e.g. `user_id` for events
+    /// inside `info_span!("request", user_id = 42)`. Fields of the event take precedence
+    /// over those of its spans, inner spans over outer ones. Off by default.
+    #[must_use]
+    pub const fn with_span_fields(mut self, span_fields: bool) -> Self {
+        self.span_fields = span_fields;
+        self
+    }
+
+    /// Records `follows_from` links and adds them to events as `follows_from` (`name#id`, ...),
+    /// along with the ID of the current span as `span_id`, so work spawned from a request can
+    /// be traced back to it. Off by default.
+    #[must_use]
+    pub const fn with_follows_from(mut self, follows_from: bool) -> Self {
+        self.follows_from = follows_from;
+        self
+    }
+
+    /// Adds the task-local ID set by [`with_correlation_id`](crate::with_correlation_id) to
+    /// every event as `correlation_id`, unless the event sets that field itself.
+    #[cfg(feature = "tokio")]
+    #[must_use]
+    pub const fn with_task_correlation_id(mut self, enabled: bool) -> Self {
+        self.task_correlation_id = enabled;
+        self
+    }
+
+    /// Operation rollups of spans carrying the `eventlog.rollup` field, enabled by default.
+    #[must_use]
+    pub const fn with_rollups(mut self, rollups: bool) -> Self {
+        self.rollups = rollups;
+        self
+    }
+
+    /// Turns off every feature that reads the clock or keeps per-event state: flood
+    /// protection, rate limits, duplicate coalescing, adaptive verbosity, sampling, stack
+    /// compression, span close events, rollups, statistics summaries, error code decoding,
+    /// the data blob and drift detection.
+    ///
+    /// What remains per event is recording the fields, formatting and writing. Settings
+    /// applied after this call take effect again.
+    #[must_use]
+    pub fn lightweight(mut self) -> Self {
+        self.flood = None;
+        self.rate_limiter = None;
+        self.coalescer = None;
+        self.adaptive = None;
+        self.sampler = None;
+        self.stacks = None;
+        self.span_close_events = false;
+        self.rollups = false;
+        self.stats_summary = None;
+        self.decode_error_codes = false;
+        self.data_blob = None;
+        self.config_drift = None;
+        self
+    }
+
+    /// Writes well-known values of enum-like fields by name, see [`ValueMapping`].
+    #[must_use]
+    pub fn with_value_mapping(mut self, mapping: ValueMapping) -> Self {
+        self.value_mapping = Some(mapping);
+        self
+    }
+
+    /// Appends the system text to codes in `hresult`, `win32_error` and `ntstatus` fields,
+    /// enabled by default.
+    #[must_use]
+    pub const fn with_error_code_decoding(mut self, decode: bool) -> Self {
+        self.decode_error_codes = decode;
+        self
+    }
+
+    /// Writes the fields of every event as raw data as well, see [`DataBlob`].
+    #[must_use]
+    pub fn with_data_blob(mut self, blob: DataBlob) -> Self {
+        self.data_blob = Some(blob);
+        self
+    }
+
+    #[must_use]
+    pub fn with_stack_compression(mut self, config: StackCompression) -> Self {
+        self.stacks = Some(StackDeduper::new(config));
+        self
+    }
+
+    /// Writes events of `level` as `event_type` unless they have an `eventlog.type` field,
+    /// e.g. INFO as [`EventType::Success`], see [`EventTypeMapping`].
+    #[must_use]
+    pub const fn with_event_type(mut self, level: Level, event_type: EventType) -> Self {
+        self.output.event_types = self.output.event_types.with(level, event_type);
+        self
+    }
+
+    /// Replaces the event type of every level, see [`EventTypeMapping`].
+    #[must_use]
+    pub const fn with_event_types(mut self, mapping: EventTypeMapping) -> Self {
+        self.output.event_types = mapping;
+        self
+    }
+
+    /// Line separator of the written descriptions, `LineEnding::Lf` by default.
+    #[must_use]
+    pub const fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.output.layout.line_ending = line_ending;
+        self
+    }
+
+    /// Trailing whitespace of every line and trailing newlines of the description are
+    /// trimmed by default.
+    #[must_use]
+    pub const fn with_trailing_whitespace_trimmed(mut self, trim: bool) -> Self {
+        self.output.layout.trim_trailing_whitespace = trim;
+        self
+    }
+
+    /// ANSI escape sequences (e.g. colored field values) are stripped from the
+    /// descriptions by default, the Event Viewer cannot render them.
+    #[must_use]
+    pub const fn with_ansi_stripped(mut self, strip: bool) -> Self {
+        self.output.layout.strip_ansi = strip;
+        self
+    }
+
+    /// Collapses all whitespace and line breaks of the descriptions into single spaces, for
+    /// collectors that cannot handle multi-line descriptions. Off by default.
+    #[must_use]
+    pub const fn with_single_line(mut self, single_line: bool) -> Self {
+        self.output.layout.single_line = single_line;
+        self
+    }
+
+    /// How to synthesize a message for pure key-value events, see [`MissingMessage`].
+    #[must_use]
+    pub fn with_missing_message(mut self, policy: MissingMessage) -> Self {
+        self.missing_message = policy;
+        self
+    }
+
+    #[must_use]
+    pub fn with_field_name_normalization(mut self, normalization: FieldNameNormalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Spills events that cannot be written to a file and replays them on recovery, see
+    /// [`Spillover`].
+    #[must_use]
+    pub fn with_spillover(mut self, spillover: Spillover) -> Self {
+        self.output.spill = Some(Arc::new(SpillFile::new(spillover)));
+        self
+    }
+
+    /// Writes events that cannot be written to the event log to `fallback` instead, see
+    /// [`Fallback`].
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.output.fallback = Some(Arc::new(FallbackSink::new(fallback)));
+        self
+    }
+
+    /// Writes every event to `sink` instead of the event log, including those of the
+    /// handles, the guard and threads bound to another source, e.g. a [`MockSink`](crate::MockSink) in unit
+    /// tests. Combine it with [`EventLogLayer::new_lazy`] or
+    /// [`EventLogLayerBuilder::sink`] so the source is never registered.
+    #[must_use]
+    pub fn with_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.output.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Handles failed writes instead of printing them to stderr.
+    #[must_use]
+    pub fn with_error_handler(
+        mut self,
+        on_error: impl Fn(&WriteError) + Send + Sync + 'static,
+    ) -> Self {
+        self.output.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Writes events with ID `id` at `floor` or above, whatever level they were emitted at,
+    /// so that operator-critical events are not demoted by accident. The event still has
+    /// to pass the filters of the subscriber at its original level.
+    #[must_use]
+    pub fn with_severity_floor(mut self, id: u32, floor: Level) -> Self {
+        self.floors.insert(id, floor);
+        self
+    }
+
+    /// Produces the descriptions of events without a template, see [`EventFormatter`].
+    #[cfg(not(feature = "lean"))]
+    #[must_use]
+    pub fn with_formatter(mut self, formatter: impl EventFormatter + 'static) -> Self {
+        self.formatter = Arc::new(formatter);
+        self
+    }
+
+    /// Writes the values of `fields` as the insertion strings `%1` to `%n` of event `id`,
+    /// in that order, instead of a description, for sources whose message file defines the
+    /// text of the event. `message` names the message; missing fields are empty strings.
+    #[must_use]
+    pub fn with_insertion_strings(mut self, id: u32, fields: &[&str]) -> Self {
+        self.insertion_strings
+            .insert(id, fields.iter().map(|&field| field.to_owned()).collect());
+        self
+    }
+
+    /// Describes events without a template by filling in `template`, e.g.
+    /// `"{message}\n\n{span_path}\n{fields}"`, see [`TemplateFormatter`].
+    #[cfg(not(feature = "lean"))]
+    #[must_use]
+    pub fn with_description_template(self, template: &str) -> Self {
+        self.with_formatter(TemplateFormatter::new(template))
+    }
+
+    /// Describes events without a template as a single JSON object, see
+    /// [`JsonFormatter`].
+    #[cfg(not(feature = "lean"))]
+    #[must_use]
+    pub fn with_json_descriptions(self) -> Self {
+        self.with_formatter(JsonFormatter)
+    }
+
+    /// Writes a duplicate of every event `id` with the fixed ID `alert_id` and a minimal
+    /// description (the message and the original ID), for Event Viewer attached tasks and
+    /// monitoring agents that only trigger on exact IDs.
+    #[must_use]
+    pub fn with_alert(mut self, id: u32, alert_id: u32) -> Self {
+        self.alerts.insert(id, alert_id);
+        self
+    }
+
+    /// Also writes the default description of every event, see [`DualFormat`].
+    #[cfg(not(feature = "lean"))]
+    #[must_use]
+    pub const fn with_dual_format(mut self, mode: DualFormat) -> Self {
+        self.dual_format = Some(mode);
+        self
+    }
+
+    /// Writes events on a background thread, see [`NonBlocking`]. The thread is started
+    /// when the layer is added to a subscriber; queued events are written when the layer or
+    /// its [`guard`](EventLogLayer::guard) is dropped.
+    #[must_use]
+    pub const fn with_non_blocking(mut self, config: NonBlocking) -> Self {
+        self.non_blocking = Some(config);
+        self
+    }
+
+    /// Returns a guard that writes pending events and deregisters the source when dropped,
+    /// see [`EventLogGuard`]. Starts the non-blocking writer now if one is configured.
+    pub fn guard(&mut self) -> EventLogGuard {
+        self.start_worker();
+        EventLogGuard::new(
+            self.worker.as_ref().map(Worker::stopper),
+            self.output.clone(),
+            self.flood.clone(),
+            self.sampler.clone(),
+            self.rate_limiter.clone(),
+            self.coalescer.clone(),
         )
-    };
+    }
 
-    if result == 0 {
-        eprintln!("Failed to write to event log");
+    fn start_worker(&mut self) {
+        let Some(config) = self.non_blocking.filter(|_| self.worker.is_none()) else {
+            return;
+        };
+        self.worker = Worker::spawn(config, self.output.clone())
+            .map_err(|e| {
+                diagnostics::emit(|| Diagnostic::WorkerUnavailable {
+                    source: self.output.log_name.clone(),
+                    error: e.to_string(),
+                });
+            })
+            .ok();
     }
 
-    unsafe {
-        DeregisterEventSource(event_source);
+    /// Configures the banner written by [`EventLogHandle::emit_startup_banner`].
+    #[must_use]
+    pub fn with_startup_banner(mut self, banner: StartupBanner) -> Self {
+        self.banner = Some(banner);
+        self
     }
-}
 
-pub struct EventLogLayer {
-    log_name: String,
-}
+    /// Writes a WARN event at startup if the configuration differs from the previous run,
+    /// see [`ConfigDrift`].
+    #[must_use]
+    pub fn with_config_drift_detection(mut self, drift: ConfigDrift) -> Self {
+        self.config_drift = Some(drift);
+        self
+    }
 
-impl EventLogLayer {
+    /// Returns a handle for writing through this layer's event source once the layer has
+    /// been moved into the subscriber. Configure the layer before taking the handle.
+    #[must_use]
+    pub fn handle(&self) -> EventLogHandle {
+        EventLogHandle {
+            output: self.output.clone(),
+            banner: self.banner.clone(),
+            settings: self.effective_settings(),
+            error_id: self.default_event_id.unwrap_or(4),
+        }
+    }
+
+    /// By default all layers for the same source share one process-wide handle.
+    /// Disable this to register a dedicated handle for this layer. Handles on a remote
+    /// machine are never shared.
     #[must_use]
-    pub const fn new(log_name: String) -> Self {
-        Self { log_name }
+    pub fn with_shared_source(mut self, shared: bool) -> Self {
+        if self.output.lazy.is_some() {
+            self.output.lazy = Some(Arc::new(LazySource::new(shared)));
+            return self;
+        }
+        let output = &self.output;
+        let source = if shared && output.server.is_none() {
+            EventSourceRegistry::get_or_register(&output.log_name)
+        } else {
+            EventSource::try_register_on(output.server.as_deref(), &output.log_name)
+                .map_err(|e| {
+                    diagnostics::emit(|| Diagnostic::RegistrationFailed {
+                        source: output.log_name.clone(),
+                        code: e.code(),
+                    });
+                })
+                .ok()
+                .map(Arc::new)
+        };
+        self.output.claim = source
+            .as_ref()
+            .filter(|s| s.is_shared())
+            .map(EventSource::claim);
+        self.output.source = source;
+        self
+    }
+
+    /// The effective configuration, one `(name, value)` pair per setting.
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        fn or_off<T: std::fmt::Debug>(config: Option<&T>) -> String {
+            config.map_or_else(|| "off".to_owned(), |c| format!("{c:?}"))
+        }
+
+        let mut templates: Vec<_> = self.templates.keys().collect();
+        templates.sort();
+        let mut alerts: Vec<_> = self.alerts.iter().collect();
+        alerts.sort();
+        let mut insertion_strings: Vec<_> = self.insertion_strings.iter().collect();
+        insertion_strings.sort();
+        let mut floors: Vec<_> = self.floors.iter().collect();
+        floors.sort();
+        let mut severities: Vec<_> = self.severities.iter().collect();
+        severities.sort_by_key(|(id, _)| **id);
+        let layout = &self.output.layout;
+
+        #[allow(unused_mut)]
+        let mut settings = vec![
+            ("log_name", self.output.log_name.clone()),
+            ("server", or_off(self.output.server.as_ref())),
+            ("default_category", self.default_category.to_string()),
+            ("default_event_id", or_off(self.default_event_id.as_ref())),
+            ("max_level", self.max_level.to_string()),
+            ("oversized_messages", format!("{:?}", self.output.oversized)),
+            ("target_ids", self.target_ids.describe()),
+            (
+                "flood_protection",
+                or_off(self.flood.as_deref().map(FloodGuard::config)),
+            ),
+            (
+                "rate_limit",
+                or_off(self.rate_limiter.as_deref().map(RateLimiter::config)),
+            ),
+            (
+                "duplicate_coalescing",
+                or_off(self.coalescer.as_deref().map(Coalescer::config)),
+            ),
+            ("sid_resolution", self.sid_resolver.is_some().to_string()),
+            ("user_sid", self.output.user_sid.is_some().to_string()),
+            (
+                "context_fields",
+                self.context
+                    .iter()
+                    .map(|c| c.fields.len())
+                    .sum::<usize>()
+                    .to_string(),
+            ),
+            ("span_close_events", self.span_close_events.to_string()),
+            (
+                "omit_fieldless_spans",
+                self.omit_fieldless_spans.to_string(),
+            ),
+            ("span_names", format!("{:?}", self.span_names)),
+            ("span_fields", self.span_fields.to_string()),
+            ("follows_from", self.follows_from.to_string()),
+            ("rollups", self.rollups.to_string()),
+            (
+                "stats_summary",
+                self.stats_summary.as_ref().map_or_else(
+                    || "off".to_owned(),
+                    |summary| format!("{} every {:?}", summary.id, summary.interval),
+                ),
+            ),
+            ("templates", format!("{templates:?}")),
+            ("severities", format!("{severities:?}")),
+            ("alerts", format!("{alerts:?}")),
+            ("severity_floors", format!("{floors:?}")),
+            ("insertion_strings", format!("{insertion_strings:?}")),
+            (
+                "adaptive_verbosity",
+                or_off(self.adaptive.as_ref().map(AdaptiveFilter::config)),
+            ),
+            (
+                "exponential_sampling",
+                or_off(self.sampler.as_deref().map(Sampler::config)),
+            ),
+            (
+                "stack_compression",
+                or_off(self.stacks.as_ref().map(StackDeduper::config)),
+            ),
+            ("value_mapping", self.value_mapping.is_some().to_string()),
+            ("error_code_decoding", self.decode_error_codes.to_string()),
+            ("data_blob", self.data_blob.is_some().to_string()),
+            ("spillover", self.output.spill.is_some().to_string()),
+            ("fallback", self.output.fallback.is_some().to_string()),
+            ("lazy_registration", self.output.lazy.is_some().to_string()),
+            ("sink", self.output.sink.is_some().to_string()),
+            ("non_blocking", or_off(self.non_blocking.as_ref())),
+            (
+                "event_types",
+                [
+                    Level::ERROR,
+                    Level::WARN,
+                    Level::INFO,
+                    Level::DEBUG,
+                    Level::TRACE,
+                ]
+                .map(|level| format!("{level}={}", self.output.event_types.get(level)))
+                .join(","),
+            ),
+            ("line_ending", format!("{:?}", layout.line_ending)),
+            (
+                "trim_trailing_whitespace",
+                layout.trim_trailing_whitespace.to_string(),
+            ),
+            ("strip_ansi", layout.strip_ansi.to_string()),
+            ("single_line", layout.single_line.to_string()),
+            (
+                "missing_message",
+                match &self.missing_message {
+                    MissingMessage::Omit => "omit".to_owned(),
+                    MissingMessage::Callsite => "callsite".to_owned(),
+                    MissingMessage::Field(name) => format!("field {name}"),
+                    MissingMessage::Template(_) => "template".to_owned(),
+                },
+            ),
+            (
+                "field_name_normalization",
+                or_off(self.normalization.as_ref()),
+            ),
+        ];
+        #[cfg(feature = "tokio")]
+        settings.push(("task_correlation_id", self.task_correlation_id.to_string()));
+        #[cfg(not(feature = "lean"))]
+        settings.extend([
+            ("dual_format", or_off(self.dual_format.as_ref())),
+            ("formatter", format!("{:?}", self.formatter)),
+        ]);
+        #[cfg(feature = "lean")]
+        settings.push(("formatter", "lean".to_owned()));
+        settings
+    }
+
+    /// The layer settings with those added to the [`ConfigDrift`], sorted by name.
+    fn effective_settings(&self) -> Vec<(String, String)> {
+        match &self.config_drift {
+            Some(drift) => drift.effective(self.settings()),
+            None => {
+                let mut settings: Vec<_> = self
+                    .settings()
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), value))
+                    .collect();
+                settings.sort();
+                settings
+            }
+        }
+    }
+
+    fn write_config_drift(&self) {
+        let Some(drift) = &self.config_drift else {
+            return;
+        };
+        let settings = self.effective_settings();
+        let Some(changes) = drift.check(&settings) else {
+            return;
+        };
+
+        let mut visitor = EventVisitor::new(Level::WARN);
+        visitor.id = Some(drift.event_id());
+        visitor.message = Some(changes);
+        visitor
+            .fields
+            .insert("config_digest".to_owned(), digest(&serialize(&settings)));
+
+        self.write_described(&visitor, None);
+    }
+
+    fn write_startup_event(&self) {
+        if self.context.is_empty() {
+            return;
+        }
+
+        let mut visitor = EventVisitor::new(Level::INFO);
+        visitor.message = Some("process started".to_owned());
+        for (key, value) in self.context.iter().flat_map(|c| &c.fields) {
+            visitor.fields.insert((*key).to_owned(), value.clone());
+        }
+
+        self.write_described(&visitor, None);
+    }
+
+    /// Reports the slots of `template` that `visitor` has no value for.
+    #[cfg(any(debug_assertions, feature = "template-validation"))]
+    fn validate_template(&self, id: u32, template: &Template, visitor: &EventVisitor<'_>) {
+        let missing: Vec<&str> = template
+            .slots()
+            .filter(|slot| visitor.value(slot).is_none())
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let mut record = EventRecord::new(
+            self.output.log_name.clone(),
+            id,
+            visitor.log_level,
+            visitor.message.clone().unwrap_or_default(),
+        );
+        record.fields.insert(
+            crate::error::MISSING_FIELDS_FIELD.to_owned(),
+            FieldValue::Str(missing.join(", ")),
+        );
+        self.output.handle_error(&WriteError {
+            record,
+            kind: WriteErrorKind::MissingTemplateFields,
+            win32_code: None,
+            attempt: 1,
+        });
+    }
+
+    fn write_span_closed<S>(&self, span: &SpanRef<'_, S>, timing: &SpanTiming)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let mut visitor = EventVisitor::new(Level::INFO);
+        visitor.message = Some("span closed".to_owned());
+        visitor.parents = span_path(span.parent());
+        visitor
+            .fields
+            .insert("span".to_owned(), span.name().to_owned());
+        visitor.fields.insert(
+            "duration".to_owned(),
+            format_duration(timing.opened.elapsed()),
+        );
+        visitor
+            .fields
+            .insert("opened_at".to_owned(), format_system_time(timing.opened_at));
+        visitor.fields.insert(
+            "closed_at".to_owned(),
+            format_system_time(SystemTime::now()),
+        );
+
+        self.write_described(&visitor, Some(span.metadata()));
+    }
+
+    fn write_rollup<S>(&self, span: &SpanRef<'_, S>, rollup: Rollup)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if rollup.errors == 0 {
+            return;
+        }
+
+        let mut visitor = EventVisitor::new(Level::ERROR);
+        visitor.message = Some("operation failed".to_owned());
+        visitor.parents = span_path(span.parent());
+        visitor
+            .fields
+            .insert("operation".to_owned(), span.name().to_owned());
+        visitor.fields.insert(
+            "duration".to_owned(),
+            format_duration(rollup.opened.elapsed()),
+        );
+        visitor
+            .fields
+            .insert("child_errors".to_owned(), rollup.errors.to_string());
+        if let Some(first) = rollup.first_error {
+            visitor.fields.insert("first_error".to_owned(), first);
+        }
+        if let Some(last) = rollup.last_error {
+            visitor.fields.insert("last_error".to_owned(), last);
+        }
+
+        self.write_described(&visitor, Some(span.metadata()));
+    }
+
+    /// Writes an event the layer produces itself, described like events without a
+    /// template.
+    fn write_described(&self, visitor: &EventVisitor<'_>, metadata: Option<&Metadata<'_>>) {
+        #[cfg(not(feature = "lean"))]
+        {
+            let (id, message) = self.describe(visitor, metadata, 0);
+            self.write(id, visitor.log_level, to_wide(&message));
+        }
+        #[cfg(feature = "lean")]
+        {
+            let (message, inserts) = visitor.strings(metadata);
+            self.write_occurrence(Occurrence {
+                id: visitor.event_id(),
+                level: visitor.log_level,
+                event_type: None,
+                category: 0,
+                message,
+                inserts,
+                data: Vec::new(),
+                source: None,
+            });
+        }
+    }
+
+    /// Formats the description of an event without a template.
+    #[cfg(not(feature = "lean"))]
+    fn describe(
+        &self,
+        visitor: &EventVisitor<'_>,
+        metadata: Option<&Metadata<'_>>,
+        category: u16,
+    ) -> (u32, String) {
+        let context = visitor.context(metadata, category);
+        (context.id, self.formatter.format(&context))
+    }
+
+    fn report(&self, occurrence: Occurrence) {
+        // Written directly if there is no worker or it has stopped.
+        let occurrence = match &self.worker {
+            Some(worker) => match worker.send(occurrence) {
+                Some(occurrence) => occurrence,
+                None => return,
+            },
+            None => occurrence,
+        };
+        self.output.report_occurrence(&occurrence);
+    }
+
+    fn write(&self, id: u32, level: Level, message: Vec<u16>) {
+        self.write_occurrence(Occurrence {
+            id,
+            level,
+            event_type: None,
+            category: 0,
+            message,
+            inserts: Vec::new(),
+            data: Vec::new(),
+            source: None,
+        });
+    }
+
+    fn write_occurrence(&self, mut occurrence: Occurrence) {
+        if occurrence.source.is_none() {
+            occurrence.source = thread_source();
+        }
+        let Some(flood) = &self.flood else {
+            self.report(occurrence);
+            return;
+        };
+
+        let (occurrences, admitted) = flood.admit(occurrence);
+        if !admitted {
+            self.output.stats.filtered();
+        }
+        for occurrence in occurrences {
+            self.report(occurrence);
+        }
+    }
+}
+
+impl Drop for EventLogLayer {
+    fn drop(&mut self) {
+        for occurrence in guard::held(
+            self.flood.as_deref(),
+            self.sampler.as_deref(),
+            self.rate_limiter.as_deref(),
+            self.coalescer.as_deref(),
+        ) {
+            self.report(occurrence);
+        }
     }
 }
+
 impl<S> Layer<S> for EventLogLayer
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.level() <= &self.max_level {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(
+        &self,
+        metadata: &Metadata<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) -> bool {
+        metadata.level() <= &self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn on_layer(&mut self, _subscriber: &mut S) {
+        self.start_worker();
+        self.write_config_drift();
+        self.write_startup_event();
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+
+        if self.span_close_events {
+            extensions.insert(SpanTiming {
+                opened: Instant::now(),
+                opened_at: SystemTime::now(),
+            });
+        }
+        if let Some(fields) = self.span_names.get(span.name()) {
+            let mut label = SpanLabel::new(span.name(), fields);
+            attrs.record(&mut label);
+            extensions.insert(label);
+        }
+        if self.span_fields {
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            extensions.insert(fields);
+        }
+        if self.rollups {
+            if let Some(rollup) = Rollup::from_attributes(attrs) {
+                extensions.insert(rollup);
+            }
+        }
+    }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(label) = extensions.get_mut::<SpanLabel>() {
+            values.record(label);
+        }
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_follows_from(
+        &self,
+        id: &span::Id,
+        follows: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if !self.follows_from {
+            return;
+        }
+        let (Some(span), Some(cause)) = (ctx.span(id), ctx.span(follows)) else {
+            return;
+        };
+        let cause = link(&display_name(&cause), follows);
+
+        let mut extensions = span.extensions_mut();
+        if let Some(links) = extensions.get_mut::<FollowsFrom>() {
+            links.0.push(cause);
+        } else {
+            extensions.insert(FollowsFrom(vec![cause]));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let (timing, rollup) = {
+            let mut extensions = span.extensions_mut();
+            (
+                extensions.remove::<SpanTiming>(),
+                extensions.remove::<Rollup>(),
+            )
+        };
+
+        if let Some(timing) = timing {
+            self.write_span_closed(&span, &timing);
+        }
+        if let Some(rollup) = rollup {
+            self.write_rollup(&span, rollup);
+        }
+    }
+
     fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
         let metadata = event.metadata();
 
-        let mut visitor = EventVisitor {
-            id: None,
-            message: None,
-            parents: None,
-            log_level: *metadata.level(),
-            log_name: &self.log_name,
-            fields: HashMap::new(),
-        };
+        if let Some(summary) = self.stats_summary.as_ref().filter(|summary| summary.due()) {
+            let stats = self.output.stats.snapshot();
+            self.write(
+                summary.id,
+                Level::INFO,
+                to_wide(&stats.describe(summary.id)),
+            );
+        }
+
+        if let Some(adaptive) = &self.adaptive {
+            if !adaptive.admit(*metadata.level()) {
+                self.output.stats.filtered();
+                return;
+            }
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let (admitted, summaries) = rate_limiter.admit(*metadata.level());
+            for (id, level, message) in summaries {
+                self.write(id, level, message);
+            }
+            if !admitted {
+                self.output.stats.filtered();
+                return;
+            }
+        }
+
+        let mut visitor = EventVisitor::new(*metadata.level());
+        visitor.sid_resolver = self.sid_resolver.as_ref();
+        visitor.normalization = self.normalization.as_ref();
+        let needs_raw_strs = cfg!(feature = "lean")
+            || !self.templates.is_empty()
+            || !self.insertion_strings.is_empty()
+            || matches!(
+                self.missing_message,
+                MissingMessage::Field(_) | MissingMessage::Template(_)
+            );
+        visitor.raw_strs = needs_raw_strs.then(HashMap::new);
+        visitor.typed = self.data_blob.is_some().then(HashMap::new);
 
         event.record(&mut visitor);
 
-        let mut parents = Vec::new();
+        if visitor.message.is_none() {
+            visitor.message = match &self.missing_message {
+                MissingMessage::Omit => None,
+                MissingMessage::Callsite if visitor.log.is_empty() => {
+                    Some(format!("{}: {}", metadata.target(), metadata.name()))
+                }
+                MissingMessage::Callsite => Some(format!(
+                    "{}: event {}",
+                    visitor.log.target(metadata),
+                    visitor.log.location(metadata)
+                )),
+                MissingMessage::Field(name) => {
+                    let raw = visitor.raw_strs.as_mut().and_then(|strs| strs.remove(name));
+                    visitor
+                        .fields
+                        .remove(name)
+                        .map(|value| raw.unwrap_or(value))
+                }
+                MissingMessage::Template(template) => {
+                    Some(template.render_string(|name| visitor.value(name)))
+                }
+            };
+        }
+
+        if self.span_fields {
+            for span in ctx.event_scope(event).into_iter().flatten() {
+                let extensions = span.extensions();
+                let Some(fields) = extensions.get::<SpanFields>() else {
+                    continue;
+                };
+                for (name, value, raw) in fields.iter() {
+                    let name = match visitor.normalization {
+                        Some(normalization) => normalization.apply(name).into_owned(),
+                        None => name.to_owned(),
+                    };
+                    if visitor.fields.contains_key(&name) {
+                        continue;
+                    }
+                    if let (Some(strs), Some(raw)) = (&mut visitor.raw_strs, raw) {
+                        strs.insert(name.clone(), raw.to_owned());
+                    }
+                    visitor.fields.insert(name, value.to_owned());
+                }
+            }
+        }
+
+        for (key, value) in self
+            .context
+            .iter()
+            .filter(|c| c.scope == ContextScope::AllEvents)
+            .flat_map(|c| &c.fields)
+        {
+            visitor
+                .fields
+                .entry((*key).to_owned())
+                .or_insert_with(|| value.clone());
+        }
 
-        let span = ctx.lookup_current().map(|s| {
-            let mut current_span = s;
-            while let Some(span) = current_span.parent() {
-                parents.push(span.name().to_owned());
+        #[cfg(feature = "tokio")]
+        if self.task_correlation_id {
+            if let Some(id) = crate::correlation::correlation_id() {
+                visitor
+                    .fields
+                    .entry(crate::correlation::CORRELATION_ID_FIELD.to_owned())
+                    .or_insert(id);
+            }
+        }
 
-                current_span = span;
+        let current = ctx.lookup_current();
+        if self.follows_from {
+            if let Some(span) = &current {
+                visitor
+                    .fields
+                    .insert(SPAN_ID_FIELD.to_owned(), span.id().into_u64().to_string());
+                let links: Vec<String> = span
+                    .scope()
+                    .filter_map(|s| s.extensions().get::<FollowsFrom>().map(|l| l.0.join(", ")))
+                    .collect();
+                if !links.is_empty() {
+                    visitor
+                        .fields
+                        .insert(FOLLOWS_FROM_FIELD.to_owned(), links.join(", "));
+                }
             }
-            current_span.name().to_owned()
+        }
+        let fieldless = self.omit_fieldless_spans
+            && current.as_ref().is_some_and(|span| {
+                span.scope()
+                    .all(|s| s.name().is_empty() || s.fields().is_empty())
+            });
+        if !fieldless {
+            visitor.parents = span_path(current);
+        }
+
+        if visitor.log_level == Level::ERROR {
+            for span in ctx.event_scope(event).into_iter().flatten() {
+                if let Some(rollup) = span.extensions_mut().get_mut::<Rollup>() {
+                    rollup.record_error(visitor.message.as_deref());
+                }
+            }
+        }
+
+        if visitor.id.is_none() && !self.target_ids.is_empty() {
+            visitor.id = self
+                .target_ids
+                .get(visitor.log.target(metadata), visitor.log_level);
+        }
+        visitor.id = visitor.id.or(self.default_event_id);
+        let id = visitor.event_id();
+        if let Some(&floor) = self.floors.get(&id) {
+            visitor.log_level = visitor.log_level.min(floor);
+        }
+
+        if let Some(sampler) = &self.sampler {
+            let (admitted, summaries) = sampler.admit(metadata, &visitor.log, id);
+            for (id, level, message) in summaries {
+                self.write(id, level, message);
+            }
+            if !admitted {
+                self.output.stats.filtered();
+                return;
+            }
+        }
+
+        if let Some(coalescer) = &self.coalescer {
+            let (admitted, summaries) = coalescer.admit(
+                metadata,
+                &visitor.log,
+                id,
+                visitor.log_level,
+                visitor.message.as_deref(),
+            );
+            for (id, level, message) in summaries {
+                self.write(id, level, message);
+            }
+            if !admitted {
+                self.output.stats.filtered();
+                return;
+            }
+        }
+
+        let event_type = visitor.event_type.take().and_then(|raw| {
+            raw.parse::<EventType>()
+                .map_err(|_| {
+                    let mut record = EventRecord::new(
+                        self.output.log_name.clone(),
+                        id,
+                        visitor.log_level,
+                        visitor.message.clone().unwrap_or_default(),
+                    );
+                    record
+                        .fields
+                        .insert(EVENT_TYPE_FIELD.to_owned(), FieldValue::Str(raw));
+                    self.output.handle_error(&WriteError {
+                        record,
+                        kind: WriteErrorKind::InvalidEventType,
+                        win32_code: None,
+                        attempt: 1,
+                    });
+                })
+                .ok()
         });
+        let event_type = event_type.or_else(|| self.severities.get(&id).copied());
+        let category = visitor.category.unwrap_or(self.default_category);
 
-        if parents.is_empty() {
-            visitor.parents = span;
+        let decoded = if self.decode_error_codes {
+            decode_error_codes(&visitor.fields)
         } else {
-            visitor.parents = Some(
-                parents
-                    .into_iter()
-                    .rev()
-                    .collect::<Vec<String>>()
-                    .join(" / "),
-            );
+            Vec::new()
+        };
+        let mut changed = Vec::new();
+        if let Some(mapping) = &self.value_mapping {
+            changed.extend(mapping.apply(&mut visitor.fields));
+        }
+        for (name, text) in decoded {
+            if let Some(value) = visitor.fields.get_mut(&name) {
+                *value = format!("{}: {text}", value.trim_matches('"'));
+                changed.push(name);
+            }
+        }
+        if let Some(stacks) = &self.stacks {
+            changed.extend(stacks.compress(&mut visitor.fields));
+        }
+        for name in changed {
+            let Some(value) = visitor.fields.get(&name) else {
+                continue;
+            };
+            // Mapped numbers keep their type, rewritten strings their new form.
+            if let Some(typed @ FieldValue::Str(_)) = visitor
+                .typed
+                .as_mut()
+                .and_then(|typed| typed.get_mut(&name))
+            {
+                *typed = FieldValue::Str(value.clone());
+            }
+            if let Some(strs) = &mut visitor.raw_strs {
+                strs.insert(name, value.clone());
+            }
+        }
+
+        let mut inserts = Vec::new();
+        let message = match (self.insertion_strings.get(&id), self.templates.get(&id)) {
+            (Some(fields), _) => {
+                let mut strings = fields
+                    .iter()
+                    .map(|field| to_wide(visitor.value(field).unwrap_or_default()));
+                let first = strings.next().unwrap_or_else(|| to_wide(""));
+                inserts = strings.collect();
+                first
+            }
+            (None, Some(template)) => {
+                #[cfg(any(debug_assertions, feature = "template-validation"))]
+                self.validate_template(id, template, &visitor);
+                visitor.render(template)
+            }
+            #[cfg(not(feature = "lean"))]
+            (None, None) => to_wide(&self.describe(&visitor, Some(metadata), category).1),
+            #[cfg(feature = "lean")]
+            (None, None) => {
+                let (message, strings) = visitor.strings(Some(metadata));
+                inserts = strings;
+                message
+            }
+        };
+        #[cfg(not(feature = "lean"))]
+        let legacy = self.dual_format.map(|mode| {
+            let context = visitor.context(Some(metadata), category);
+            (mode, DefaultFormatter.format(&context))
+        });
+        let attached = visitor.raw_data.take();
+        let data = self
+            .data_blob
+            .as_ref()
+            .filter(|_| attached.is_none())
+            .map(|blob| {
+                let mut record = EventRecord::new(
+                    self.output.log_name.clone(),
+                    id,
+                    visitor.log_level,
+                    visitor.message.clone().unwrap_or_default(),
+                );
+                let typed = visitor.typed.take().unwrap_or_default();
+                record.fields = visitor
+                    .fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = typed
+                            .get(name)
+                            .cloned()
+                            .unwrap_or_else(|| FieldValue::Str(value.clone()));
+                        (name.clone(), value)
+                    })
+                    .collect();
+                #[cfg(not(feature = "lean"))]
+                if let Some((DualFormat::DataBlob, legacy)) = &legacy {
+                    record.fields.insert(
+                        LEGACY_MESSAGE_FIELD.to_owned(),
+                        FieldValue::Str(legacy.clone()),
+                    );
+                }
+                blob.encode(&record)
+            })
+            .or(attached);
+        #[cfg(not(feature = "lean"))]
+        let data = data.or_else(|| match &legacy {
+            Some((DualFormat::DataBlob, legacy)) => Some(legacy.as_bytes().to_vec()),
+            _ => None,
+        });
+        let data = data.unwrap_or_default();
+        self.write_occurrence(Occurrence {
+            id,
+            level: visitor.log_level,
+            event_type,
+            category,
+            message,
+            inserts,
+            data,
+            source: None,
+        });
+        if let Some(&alert_id) = self.alerts.get(&id) {
+            let mut alert = format!("ID: {alert_id}\n\n");
+            if let Some(message) = &visitor.message {
+                let _ = writeln!(alert, "message: {message}");
+            }
+            let _ = writeln!(alert, "original_id: {id}");
+            self.write_occurrence(Occurrence {
+                id: alert_id,
+                level: visitor.log_level,
+                event_type,
+                category,
+                message: to_wide(&alert),
+                inserts: Vec::new(),
+                data: Vec::new(),
+                source: None,
+            });
+        }
+        #[cfg(not(feature = "lean"))]
+        if let Some((DualFormat::Consecutive, legacy)) = legacy {
+            self.write_occurrence(Occurrence {
+                id,
+                level: visitor.log_level,
+                event_type,
+                category,
+                message: to_wide(&legacy),
+                inserts: Vec::new(),
+                data: Vec::new(),
+                source: None,
+            });
+        }
+    }
+}
+
+struct SpanTiming {
+    opened: Instant,
+    opened_at: SystemTime,
+}
+
+fn span_path<S>(span: Option<SpanRef<'_, S>>) -> Option<String>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let mut parents = Vec::new();
+
+    let span = span.map(|s| {
+        let mut current_span = s;
+        while let Some(span) = current_span.parent() {
+            parents.push(display_name(&span));
+
+            current_span = span;
         }
+        display_name(&current_span)
+    });
 
-        visitor.log();
+    if parents.is_empty() {
+        span
+    } else {
+        Some(
+            parents
+                .into_iter()
+                .rev()
+                .collect::<Vec<String>>()
+                .join(" / "),
+        )
     }
 }
 
+fn display_name<S>(span: &SpanRef<'_, S>) -> String
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    span.extensions()
+        .get::<SpanLabel>()
+        .map_or_else(|| span.name().to_owned(), SpanLabel::render)
+}
+
 #[derive(Debug)]
 struct EventVisitor<'a> {
     id: Option<u32>,
+    category: Option<u16>,
+    /// Raw data attached through the `eventlog.data` field.
+    raw_data: Option<Vec<u8>>,
     log_level: Level,
     message: Option<String>,
     parents: Option<String>,
     fields: HashMap<String, String>,
-    log_name: &'a str,
+    raw_strs: Option<HashMap<String, String>>,
+    /// Values with their recorded type, collected for the data blob.
+    typed: Option<HashMap<String, FieldValue>>,
+    /// Raw value of the `eventlog.type` field.
+    event_type: Option<String>,
+    sid_resolver: Option<&'a SidResolver>,
+    normalization: Option<&'a FieldNameNormalization>,
+    log: LogMetadata,
 }
 
 impl<'a> EventVisitor<'a> {
-    fn log(&self) {
-        let id: u32 = self.id.unwrap_or(match self.log_level {
+    fn new(log_level: Level) -> Self {
+        Self {
+            id: None,
+            category: None,
+            raw_data: None,
+            log_level,
+            message: None,
+            parents: None,
+            fields: HashMap::new(),
+            raw_strs: None,
+            typed: None,
+            event_type: None,
+            sid_resolver: None,
+            normalization: None,
+            log: LogMetadata::default(),
+        }
+    }
+
+    fn field_name<'f>(&self, field: &'f tracing::field::Field) -> Cow<'f, str> {
+        match self.normalization {
+            Some(normalization) => normalization.apply(field.name()),
+            None => Cow::Borrowed(field.name()),
+        }
+    }
+
+    fn event_id(&self) -> u32 {
+        self.id.unwrap_or(match self.log_level {
             Level::TRACE => 0,
             Level::DEBUG => 1,
             Level::INFO => 2,
             Level::WARN => 3,
             Level::ERROR => 4,
-        });
-
-        let mut msg = format!("ID: {id}\n\n");
+        })
+    }
 
-        if let Some(m) = &self.parents {
-            msg.push_str(&format!("source: {m}\n"));
+    /// Keeps the typed value of a field for the data blob, if one is written.
+    fn record_typed(&mut self, field: &tracing::field::Field, value: FieldValue) {
+        if self.typed.is_none() || field.name() == "message" {
+            return;
         }
-        if let Some(m) = &self.message {
-            msg.push_str(&format!("message: {m}\n"));
+        let name = self.field_name(field).into_owned();
+        if let Some(typed) = &mut self.typed {
+            typed.insert(name, value);
         }
+    }
 
-        self.fields.iter().for_each(|i| {
-            msg.push_str(&format!("{}: {:?}\n", i.0, i.1.replace(r"\\", r"\")));
-        });
+    /// Value of a field for template slots, preferring the raw string over its `Debug` form.
+    fn value(&self, name: &str) -> Option<&str> {
+        if name == "message" {
+            return self.message.as_deref();
+        }
+        self.raw_strs
+            .as_ref()
+            .and_then(|strs| strs.get(name))
+            .or_else(|| self.fields.get(name))
+            .map(String::as_str)
+    }
 
-        write_to_event_log(id, self.log_level, &msg, self.log_name);
+    fn render(&self, template: &Template) -> Vec<u16> {
+        template.render(|name| self.value(name))
+    }
+
+    /// The message and the fields as insertion strings: the fields of the callsite in the
+    /// order they are declared, then the others by name.
+    #[cfg(feature = "lean")]
+    fn strings(&self, metadata: Option<&Metadata<'_>>) -> (Vec<u16>, Vec<Vec<u16>>) {
+        let declared: Vec<&str> = metadata
+            .map(|metadata| {
+                metadata
+                    .fields()
+                    .iter()
+                    .map(|field| field.name())
+                    .filter(|name| self.fields.contains_key(*name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut others: Vec<&str> = self
+            .fields
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !declared.contains(name))
+            .collect();
+        others.sort_unstable();
+
+        let inserts = declared
+            .into_iter()
+            .chain(others)
+            .map(|name| to_wide(self.value(name).unwrap_or_default()))
+            .collect();
+        (
+            to_wide(self.message.as_deref().unwrap_or_default()),
+            inserts,
+        )
+    }
+
+    #[cfg(not(feature = "lean"))]
+    fn context<'b>(
+        &'b self,
+        metadata: Option<&'b Metadata<'b>>,
+        category: u16,
+    ) -> EventContext<'b> {
+        EventContext {
+            metadata,
+            id: self.event_id(),
+            category,
+            level: self.log_level,
+            message: self.message.as_deref(),
+            span_path: self.parents.as_deref(),
+            fields: &self.fields,
+        }
     }
 }
 
 impl<'a> Visit for EventVisitor<'a> {
     #[allow(clippy::cast_possible_truncation)]
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-        if field.name().to_lowercase() == "id" && value <= u32::MAX.into() {
+        if self.log.record_u64(field.name(), value) {
+            return;
+        }
+        let name = self.field_name(field);
+        if name.to_lowercase() == "id" && value <= u32::MAX.into() {
             self.id = Some(value as u32);
+        } else if name.to_lowercase() == CATEGORY_FIELD && value <= u16::MAX.into() {
+            self.category = Some(value as u16);
         } else {
-            self.fields
-                .insert(field.name().to_string(), format!("{value}"));
+            let name = name.into_owned();
+            self.fields.insert(name, format!("{value}"));
+            self.record_typed(field, FieldValue::U64(value));
         }
     }
 
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
-        if field.name().to_lowercase() == "id" && value >= 0 && value <= u32::MAX.into() {
+        let name = self.field_name(field);
+        if name.to_lowercase() == "id" && value >= 0 && value <= u32::MAX.into() {
             self.id = Some(value as u32);
+        } else if name.to_lowercase() == CATEGORY_FIELD && value >= 0 && value <= u16::MAX.into() {
+            self.category = Some(value as u16);
         } else {
-            self.fields
-                .insert(field.name().to_string(), format!("{value:?}"));
+            let name = name.into_owned();
+            self.fields.insert(name, format!("{value:?}"));
+            self.record_typed(field, FieldValue::I64(value));
         }
     }
 
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        if field.name() == "message" {
+        if field.name() == EVENT_TYPE_FIELD {
+            self.event_type = Some(format!("{value:?}"));
+        } else if field.name() == "message" {
             self.message = Some(format!("{value:?}"));
         } else {
-            self.fields
-                .insert(field.name().to_string(), format!("{value:?}"));
+            let name = self.field_name(field).into_owned();
+            self.fields.insert(name, format!("{value:?}"));
         }
     }
 
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
         self.record_debug(field, &value);
+        self.record_typed(field, FieldValue::F64(value));
     }
 
     fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
@@ -193,9 +1851,406 @@ impl<'a> Visit for EventVisitor<'a> {
 
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
         self.record_debug(field, &value);
+        self.record_typed(field, FieldValue::Bool(value));
+    }
+
+    fn record_bytes(&mut self, field: &tracing::field::Field, value: &[u8]) {
+        if field.name() == DATA_FIELD {
+            self.raw_data = Some(value.to_vec());
+        } else {
+            self.record_debug(field, &value);
+        }
     }
 
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if self.log.record_str(field.name(), value) {
+            return;
+        }
+        if field.name() == EVENT_TYPE_FIELD {
+            self.event_type = Some(value.to_owned());
+            return;
+        }
+        if field.name() == DATA_FIELD {
+            if let Some(data) = decode_binary(value) {
+                self.raw_data = Some(data);
+                return;
+            }
+        }
+        let name = self.field_name(field).into_owned();
+        if let Some(resolver) = self.sid_resolver {
+            if is_sid_field(&name) {
+                if let Some(account) = resolver.resolve(value) {
+                    self.fields.insert(format!("{name}_account"), account);
+                }
+            }
+        }
+        if let Some(strs) = &mut self.raw_strs {
+            strs.insert(name, value.to_owned());
+        }
         self.record_debug(field, &value);
+        self.record_typed(field, FieldValue::Str(value.to_owned()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::limits::MAX_STRING_LEN;
+    use crate::oversize::TRUNCATION_MARKER;
+    use crate::sink::{CapturedEvent, MockSink};
+
+    const SOURCE: &str = "tracing-layer-win-eventlog-test";
+
+    fn layer() -> EventLogLayer {
+        EventLogLayer::new_lazy(SOURCE.to_owned())
+    }
+
+    /// Runs `f` with `layer` writing to `sink`; the layer is dropped afterwards.
+    fn run(layer: EventLogLayer, sink: &MockSink, f: impl FnOnce()) {
+        let subscriber = tracing_subscriber::registry().with(layer.with_sink(sink.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    /// The events written while running `f`, and when the layer is dropped.
+    fn capture(layer: EventLogLayer, f: impl FnOnce()) -> Vec<CapturedEvent> {
+        let sink = MockSink::new();
+        run(layer, &sink, f);
+        sink.take()
+    }
+
+    fn ids(events: &[CapturedEvent]) -> Vec<u32> {
+        events.iter().map(|event| event.id).collect()
+    }
+
+    /// A layer collecting the kinds of its failed writes.
+    fn error_recording_layer() -> (EventLogLayer, Arc<Mutex<Vec<WriteErrorKind>>>) {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&errors);
+        let layer = layer().with_error_handler(move |error| {
+            seen.lock().unwrap().push(error.kind);
+        });
+        (layer, errors)
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-layer-win-eventlog-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn id_comes_from_field_target_or_level() {
+        let layer = layer()
+            .with_target_id("my_app::db", 500)
+            .with_target_id_range("my_app::net", 600);
+        let events = capture(layer, || {
+            tracing::info!(id = 42, "explicit");
+            tracing::info!(target: "my_app::db::pool", "fixed target");
+            tracing::info!(target: "my_app::db::pool", id = 43, "explicit wins");
+            tracing::warn!(target: "my_app::net", "range");
+            tracing::trace!("trace");
+            tracing::debug!("debug");
+            tracing::info!("info");
+            tracing::warn!("warn");
+            tracing::error!("error");
+        });
+        assert_eq!(ids(&events), [42, 500, 43, 603, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn default_event_id_and_category_apply_without_fields() {
+        let sink = MockSink::new();
+        let layer = EventLogLayer::builder()
+            .source(SOURCE)
+            .default_event_id(9)
+            .default_category(4)
+            .sink(sink.clone())
+            .build()
+            .unwrap();
+        run(layer, &sink, || {
+            tracing::info!("defaults");
+            tracing::info!(id = 10, category = 5, "explicit");
+        });
+        let events: Vec<_> = sink
+            .take()
+            .iter()
+            .map(|event| (event.id, event.category))
+            .collect();
+        assert_eq!(events, [(9, 4), (10, 5)]);
+    }
+
+    #[test]
+    fn event_type_follows_level_unless_selected() {
+        let events = capture(layer(), || {
+            tracing::error!("error");
+            tracing::warn!("warn");
+            tracing::info!("info");
+            tracing::debug!("debug");
+            tracing::info!(eventlog.type = "audit_failure", "denied");
+        });
+        let types: Vec<_> = events.iter().map(|event| event.event_type).collect();
+        assert_eq!(
+            types,
+            [
+                EventType::Error,
+                EventType::Warning,
+                EventType::Information,
+                EventType::Information,
+                EventType::AuditFailure,
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_event_type_is_reported_and_written_by_level() {
+        let (layer, errors) = error_recording_layer();
+        let events = capture(layer, || {
+            tracing::warn!(eventlog.type = "loud", "disk almost full");
+        });
+        assert_eq!(events[0].event_type, EventType::Warning);
+        assert_eq!(*errors.lock().unwrap(), [WriteErrorKind::InvalidEventType]);
+    }
+
+    #[test]
+    fn raw_data_comes_from_the_data_field() {
+        let events = capture(layer(), || {
+            tracing::info!(eventlog.data = "deadbeef", "dump");
+        });
+        assert_eq!(events[0].data, [0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[cfg(not(feature = "lean"))]
+    #[test]
+    fn description_lists_span_message_and_fields() {
+        let events = capture(layer(), || {
+            let _span = tracing::info_span!("request").entered();
+            tracing::warn!(id = 42, free = 1024u64, "disk almost full");
+        });
+        assert_eq!(
+            events[0].body,
+            "ID: 42\n\nsource: request\nmessage: disk almost full\nfree: \"1024\""
+        );
+        assert!(events[0].inserts.is_empty());
+    }
+
+    #[cfg(not(feature = "lean"))]
+    #[test]
+    fn layout_sets_line_endings_and_single_line() {
+        let crlf = capture(layer().with_line_ending(LineEnding::CrLf), || {
+            tracing::warn!(id = 42, "disk almost full");
+        });
+        assert_eq!(crlf[0].body, "ID: 42\r\n\r\nmessage: disk almost full");
+
+        let single = capture(layer().with_single_line(true), || {
+            tracing::warn!(id = 42, "disk  almost\nfull");
+        });
+        assert_eq!(single[0].body, "ID: 42 message: disk almost full");
+    }
+
+    #[test]
+    fn insertion_strings_follow_the_configured_fields() {
+        let layer = layer().with_insertion_strings(7, &["message", "user", "missing"]);
+        let events = capture(layer, || {
+            tracing::info!(id = 7, user = "alice", "signed in");
+        });
+        assert_eq!(events[0].body, "signed in");
+        assert_eq!(events[0].inserts, ["alice", ""]);
+    }
+
+    #[test]
+    fn oversized_descriptions_are_truncated_by_default() {
+        let long = "x".repeat(MAX_STRING_LEN + 100);
+        let events = capture(layer(), || tracing::info!("{long}"));
+        assert_eq!(events[0].body.encode_utf16().count(), MAX_STRING_LEN);
+        assert!(events[0].body.ends_with(TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn oversized_descriptions_can_be_rejected() {
+        let (layer, errors) = error_recording_layer();
+        let layer = layer.with_oversized_messages(OversizedMessage::Reject);
+        let long = "x".repeat(MAX_STRING_LEN + 100);
+        let events = capture(layer, || tracing::info!("{long}"));
+        assert!(events.is_empty());
+        assert_eq!(*errors.lock().unwrap(), [WriteErrorKind::TooLong]);
+    }
+
+    #[test]
+    fn flood_protection_keeps_first_and_last_with_a_summary() {
+        let layer = layer().with_flood_protection(FloodProtection::new(2, Duration::from_secs(60)));
+        let events = capture(layer, || {
+            for i in 0..5 {
+                tracing::warn!(id = 7, attempt = i, "retrying");
+            }
+        });
+        assert_eq!(ids(&events), [7, 7, 7, 7]);
+        assert!(events[2]
+            .body
+            .contains("message: flood protection suppressed 2 occurrences"));
+        #[cfg(not(feature = "lean"))]
+        assert!(events[3].body.contains("attempt: \"4\""));
+    }
+
+    #[test]
+    fn sampling_writes_powers_of_two_and_summarizes_the_rest() {
+        let layer = layer().with_exponential_sampling(ExponentialSampling::new(
+            Level::WARN,
+            Duration::from_secs(60),
+        ));
+        let events = capture(layer, || {
+            for _ in 0..5 {
+                tracing::warn!(id = 9, "retrying");
+            }
+        });
+        assert_eq!(ids(&events), [9, 9, 9, 9]);
+        assert!(events[3].body.contains("occurrences: 5\nnot_written: 2"));
+    }
+
+    #[test]
+    fn sampling_writes_no_summary_when_nothing_was_left_out() {
+        let layer = layer().with_exponential_sampling(ExponentialSampling::new(
+            Level::WARN,
+            Duration::from_secs(60),
+        ));
+        let events = capture(layer, || {
+            for _ in 0..2 {
+                tracing::warn!(id = 9, "retrying");
+            }
+        });
+        assert_eq!(ids(&events), [9, 9]);
+    }
+
+    #[test]
+    fn rate_limit_summarizes_suppressed_events() {
+        let limit = RateLimit::new().with_limit(Level::INFO, 2, Duration::from_secs(60));
+        let events = capture(layer().with_rate_limit(limit), || {
+            for i in 0..5 {
+                tracing::info!(id = i, "polled");
+            }
+            tracing::warn!(id = 20, "not limited");
+        });
+        assert_eq!(ids(&events), [0, 1, 20, RateLimit::DEFAULT_ID]);
+        assert_eq!(events[3].level, Level::INFO);
+        assert!(events[3]
+            .body
+            .contains("message: rate limit suppressed 3 INFO events"));
+    }
+
+    #[test]
+    fn duplicates_are_coalesced_until_a_different_event() {
+        let layer = layer().with_duplicate_coalescing(DuplicateCoalescing::default());
+        let events = capture(layer, || {
+            for _ in 0..4 {
+                tracing::warn!(id = 5, "disk almost full");
+            }
+            tracing::warn!(id = 6, "disk full");
+        });
+        assert_eq!(ids(&events), [5, 5, 6]);
+        assert_eq!(events[1].level, Level::WARN);
+        assert!(events[1]
+            .body
+            .contains("message: last message repeated 3 times"));
+    }
+
+    #[test]
+    fn spilled_events_are_replayed_as_written() {
+        let path = temp_file("replay.spill");
+        let (layer, errors) = error_recording_layer();
+        let layer = layer
+            .with_insertion_strings(7, &["message", "user"])
+            .with_spillover(Spillover::new(&path, 1024 * 1024));
+        let sink = MockSink::new();
+        run(layer, &sink, || {
+            sink.fail_with(Some(Error::Write {
+                code: 1717,
+                attempt: 1,
+            }));
+            tracing::error!(
+                id = 7,
+                category = 3,
+                eventlog.type = "audit_failure",
+                eventlog.data = "deadbeef",
+                user = "alice",
+                "denied"
+            );
+            sink.fail_with(None);
+            tracing::info!(id = 8, "back");
+        });
+        let _ = std::fs::remove_file(&path);
+
+        let events = sink.take();
+        assert_eq!(ids(&events), [8, 7, Spillover::DEFAULT_ID]);
+        assert_eq!(
+            events[1],
+            CapturedEvent {
+                id: 7,
+                level: Level::ERROR,
+                event_type: EventType::AuditFailure,
+                category: 3,
+                body: "denied".to_owned(),
+                inserts: vec!["alice".to_owned()],
+                data: vec![0xde, 0xad, 0xbe, 0xef],
+            }
+        );
+        assert!(events[2]
+            .body
+            .contains("message: event log outage, 1 events replayed, 0 dropped"));
+        assert_eq!(*errors.lock().unwrap(), [WriteErrorKind::Write]);
+    }
+
+    #[test]
+    fn permanent_failures_are_not_spilled() {
+        let path = temp_file("permanent.spill");
+        let (layer, _errors) = error_recording_layer();
+        let layer = layer.with_spillover(Spillover::new(&path, 1024 * 1024));
+        let sink = MockSink::new();
+        run(layer, &sink, || {
+            sink.fail_with(Some(Error::Write {
+                code: 87,
+                attempt: 1,
+            }));
+            tracing::error!(id = 7, "denied");
+            sink.fail_with(None);
+            tracing::info!(id = 8, "back");
+        });
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(ids(&sink.take()), [8]);
+    }
+
+    #[test]
+    fn failed_events_go_to_the_fallback() {
+        let path = temp_file("fallback.log");
+        let (layer, _errors) = error_recording_layer();
+        let layer = layer
+            .with_insertion_strings(7, &["message", "user"])
+            .with_fallback(Fallback::file(&path, 1024 * 1024));
+        let sink = MockSink::new();
+        run(layer, &sink, || {
+            sink.fail_with(Some(Error::Write {
+                code: 5,
+                attempt: 1,
+            }));
+            tracing::error!(id = 7, category = 3, user = "alice", "denied");
+            sink.fail_with(None);
+            tracing::info!(id = 8, "back");
+        });
+        let written = std::fs::read_to_string(&path).unwrap_or_default();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(written.contains(&format!(
+            " ERROR {SOURCE} 7: denied | category: 3 | %2: alice\n"
+        )));
+        let events = sink.take();
+        assert_eq!(ids(&events), [8, Fallback::DEFAULT_ID]);
+        assert!(events[1]
+            .body
+            .contains("1 events were written to the fallback"));
     }
 }
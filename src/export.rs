@@ -0,0 +1,121 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::record::EventRecord;
+use crate::time::format_system_time;
+
+/// Writes `records` as JSON Lines, one [`EventRecord`] per line in its serde schema.
+///
+/// # Errors
+/// Fails if writing to `writer` fails.
+#[cfg(feature = "serde")]
+pub fn write_jsonl<'a>(
+    records: impl IntoIterator<Item = &'a EventRecord>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// Writes `records` as CSV.
+///
+/// The columns are `timestamp` (RFC 3339), `source`, `id`, `level` and `message`,
+/// followed by one column per field name found in any record, sorted by name.
+///
+/// # Errors
+/// Fails if writing to `writer` fails.
+pub fn write_csv(records: &[EventRecord], mut writer: impl Write) -> io::Result<()> {
+    let fields: BTreeSet<&str> = records
+        .iter()
+        .flat_map(|r| r.fields.keys().map(String::as_str))
+        .collect();
+
+    let mut header = vec!["timestamp", "source", "id", "level", "message"];
+    header.extend(&fields);
+    write_row(&mut writer, header)?;
+
+    for record in records {
+        let mut row = vec![
+            format_system_time(record.timestamp),
+            record.source.clone(),
+            record.id.to_string(),
+            record.level.to_string(),
+            record.message.clone(),
+        ];
+        row.extend(fields.iter().map(|name| {
+            record
+                .fields
+                .get(*name)
+                .map(ToString::to_string)
+                .unwrap_or_default()
+        }));
+        write_row(&mut writer, row)?;
+    }
+    writer.flush()
+}
+
+fn write_row<S: AsRef<str>>(writer: &mut impl Write, row: Vec<S>) -> io::Result<()> {
+    let row: Vec<_> = row.iter().map(|cell| escape(cell.as_ref())).collect();
+    writer.write_all(row.join(",").as_bytes())?;
+    writer.write_all(b"\r\n")
+}
+
+fn escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use tracing::Level;
+
+    use super::*;
+    use crate::record::FieldValue;
+
+    fn record(id: u32, message: &str, fields: &[(&str, FieldValue)]) -> EventRecord {
+        let mut record = EventRecord::new("hello_world", id, Level::WARN, message);
+        record.timestamp = UNIX_EPOCH + Duration::from_millis(1_714_564_801_482);
+        for (name, value) in fields {
+            record.fields.insert((*name).to_owned(), value.clone());
+        }
+        record
+    }
+
+    #[test]
+    fn escapes_separators_quotes_and_line_breaks() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(
+            escape("ID: 1\r\n\r\nmessage: x"),
+            "\"ID: 1\r\n\r\nmessage: x\""
+        );
+    }
+
+    #[test]
+    fn writes_one_column_per_field_of_any_record() {
+        let records = [
+            record(
+                1,
+                "disk almost full",
+                &[("volume", FieldValue::Str("C:".to_owned()))],
+            ),
+            record(2, "free, 10%", &[("free", FieldValue::U64(1024))]),
+        ];
+        let mut csv = Vec::new();
+        write_csv(&records, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "timestamp,source,id,level,message,free,volume\r\n\
+             2024-05-01T12:00:01.482Z,hello_world,1,WARN,disk almost full,,C:\r\n\
+             2024-05-01T12:00:01.482Z,hello_world,2,WARN,\"free, 10%\",1024,\r\n"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::binary::encode_hex;
+use crate::record::EventRecord;
+use crate::time::format_system_time;
+
+/// Where events go while the event log cannot be written, e.g. without administrative
+/// rights to register the source or while the event log service is stopped.
+///
+/// Every event that fails to be written is appended to the fallback as one line. The
+/// first successful write afterwards is followed by a WARN notice (ID 1005 by default)
+/// telling how many events went to the fallback.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    target: FallbackTarget,
+    id: u32,
+}
+
+#[derive(Debug, Clone)]
+enum FallbackTarget {
+    Stderr,
+    /// Rolled over to `<path>.1` once it would exceed `max_bytes`.
+    File {
+        path: PathBuf,
+        max_bytes: u64,
+    },
+}
+
+impl Fallback {
+    pub const DEFAULT_ID: u32 = 1005;
+
+    #[must_use]
+    pub const fn stderr() -> Self {
+        Self {
+            target: FallbackTarget::Stderr,
+            id: Self::DEFAULT_ID,
+        }
+    }
+
+    /// Appends to the file at `path`; when it would grow beyond `max_bytes` it is renamed
+    /// to `<path>.1`, replacing the previous one, and a new file is started.
+    #[must_use]
+    pub fn file(path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            target: FallbackTarget::File {
+                path: path.into(),
+                max_bytes,
+            },
+            id: Self::DEFAULT_ID,
+        }
+    }
+
+    /// Event ID of the notice written once the event log is writable again.
+    #[must_use]
+    pub const fn id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct FallbackSink {
+    config: Fallback,
+    /// Serializes appends and rollovers of the file.
+    file: Mutex<()>,
+    active: AtomicBool,
+    written: AtomicU64,
+}
+
+impl FallbackSink {
+    pub const fn new(config: Fallback) -> Self {
+        Self {
+            config,
+            file: Mutex::new(()),
+            active: AtomicBool::new(false),
+            written: AtomicU64::new(0),
+        }
+    }
+
+    /// Writes `record` as one line: timestamp, level, source, ID and the description with
+    /// its line breaks replaced by ` | `, followed by the event type, category, further
+    /// insertion strings (`%2` onwards) and raw data in hex, where the event has them.
+    pub fn write(&self, record: &EventRecord) {
+        let one_line = |text: &str| text.trim_end().replace("\r\n", " | ").replace('\n', " | ");
+        let mut line = format!(
+            "{} {} {} {}: {}",
+            format_system_time(record.timestamp),
+            record.level,
+            record.source,
+            record.id,
+            one_line(&record.message)
+        );
+        if let Some(event_type) = record.event_type {
+            let _ = write!(line, " | event_type: {}", event_type.as_str());
+        }
+        if record.category != 0 {
+            let _ = write!(line, " | category: {}", record.category);
+        }
+        for (i, insert) in record.inserts.iter().enumerate() {
+            let _ = write!(line, " | %{}: {}", i + 2, one_line(insert));
+        }
+        if !record.data.is_empty() {
+            let _ = write!(line, " | data: {}", encode_hex(&record.data));
+        }
+        line.push('\n');
+        let written = match &self.config.target {
+            FallbackTarget::Stderr => std::io::stderr().write_all(line.as_bytes()).is_ok(),
+            FallbackTarget::File { path, max_bytes } => {
+                let Ok(_file) = self.file.lock() else {
+                    return;
+                };
+                let full =
+                    std::fs::metadata(path).is_ok_and(|m| m.len() + line.len() as u64 > *max_bytes);
+                if full {
+                    let mut rolled = path.clone().into_os_string();
+                    rolled.push(".1");
+                    let _ = std::fs::rename(path, rolled);
+                }
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(line.as_bytes()))
+                    .is_ok()
+            }
+        };
+        if written {
+            self.written.fetch_add(1, Ordering::Relaxed);
+            self.active.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The ID and description of the notice if events went to the fallback since the last
+    /// call.
+    pub fn recovered(&self) -> Option<(u32, String)> {
+        if !self.active.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        let written = self.written.swap(0, Ordering::Relaxed);
+        let target = match &self.config.target {
+            FallbackTarget::Stderr => "stderr".to_owned(),
+            FallbackTarget::File { path, .. } => path.display().to_string(),
+        };
+        let id = self.config.id;
+        Some((
+            id,
+            format!(
+                "ID: {id}\n\nmessage: event log writable again, {written} events were written to the fallback\nfallback: {target}\n"
+            ),
+        ))
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+#[cfg(windows)]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::Level;
+
+use crate::event_type::EventType;
+#[cfg(windows)]
+use crate::source::EventSource;
+use crate::time::{format_duration, format_system_time};
+use crate::wide::to_wide;
+
+/// Per event ID flood protection.
+///
+/// Within `window` (measured from the first occurrence) the first `threshold`
+/// occurrences of an event ID are written as usual. Further occurrences are held
+/// back: when the window closes a summary of the suppressed middle is written,
+/// followed by the last occurrence, so the first and the last are always kept.
+///
+/// The last occurrence is written late, with an `original_timestamp` line giving the time
+/// it was logged. It is held in memory until then and lost if the process crashes.
+#[derive(Debug, Clone, Copy)]
+pub struct FloodProtection {
+    threshold: u32,
+    window: Duration,
+}
+
+impl FloodProtection {
+    #[must_use]
+    pub const fn new(threshold: u32, window: Duration) -> Self {
+        Self {
+            threshold: if threshold == 0 { 1 } else { threshold },
+            window,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Occurrence {
+    pub id: u32,
+    pub level: Level,
+    /// Explicit event type, otherwise the type follows the level.
+    pub event_type: Option<EventType>,
+    /// Event category, 0 for none.
+    pub category: u16,
+    pub message: Vec<u16>,
+    /// Insertion strings after the message (`%2` onwards), see
+    /// [`EventLogLayer::with_insertion_strings`](crate::EventLogLayer::with_insertion_strings).
+    pub inserts: Vec<Vec<u16>>,
+    /// Raw data of the event, empty for summaries.
+    pub data: Vec<u8>,
+    /// Source bound to the emitting thread, see
+    /// [`bind_thread_source`](crate::bind_thread_source).
+    #[cfg(windows)]
+    pub source: Option<Arc<EventSource>>,
+}
+
+#[derive(Debug)]
+struct Burst {
+    started: Instant,
+    last_seen: Instant,
+    first_at: SystemTime,
+    last_at: SystemTime,
+    seen: u32,
+    suppressed: u32,
+    last: Option<Occurrence>,
+}
+
+impl Burst {
+    fn new(now: Instant) -> Self {
+        let now_at = SystemTime::now();
+        Self {
+            started: now,
+            last_seen: now,
+            first_at: now_at,
+            last_at: now_at,
+            seen: 0,
+            suppressed: 0,
+            last: None,
+        }
+    }
+
+    fn flush(&mut self, id: u32, out: &mut Vec<Occurrence>) {
+        let Some(mut last) = self.last.take() else {
+            return;
+        };
+
+        if self.suppressed > 1 {
+            out.push(Occurrence {
+                id,
+                level: last.level,
+                event_type: last.event_type,
+                category: last.category,
+                message: to_wide(&format!(
+                    "ID: {id}\n\nmessage: flood protection suppressed {} occurrences\nwindow: {}\nfirst_seen: {}\nlast_seen: {}\n",
+                    self.suppressed - 1,
+                    format_duration(self.last_seen.duration_since(self.started)),
+                    format_system_time(self.first_at),
+                    format_system_time(self.last_at),
+                )),
+                inserts: Vec::new(),
+                data: Vec::new(),
+                #[cfg(windows)]
+                source: last.source.clone(),
+            });
+        }
+
+        stamp(&mut last.message, self.last_at);
+        out.push(last);
+    }
+}
+
+/// Appends the time an occurrence was logged to its description, like a replayed
+/// spillover record. The `%1` of a message file event or a template is left as it is.
+fn stamp(message: &mut Vec<u16>, logged_at: SystemTime) {
+    if !message.starts_with(&to_wide("ID: ")[..4]) {
+        return;
+    }
+    if message.last() == Some(&0) {
+        message.pop();
+    }
+    if message.last() != Some(&u16::from(b'\n')) {
+        message.push(u16::from(b'\n'));
+    }
+    message.extend(to_wide(&format!(
+        "original_timestamp: {}\n",
+        format_system_time(logged_at)
+    )));
+}
+
+#[derive(Debug)]
+pub struct FloodGuard {
+    config: FloodProtection,
+    bursts: Mutex<HashMap<u32, Burst>>,
+}
+
+impl FloodGuard {
+    pub fn new(config: FloodProtection) -> Self {
+        Self {
+            config,
+            bursts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub const fn config(&self) -> &FloodProtection {
+        &self.config
+    }
+
+    /// Returns the occurrences that have to be written now, in order, and whether
+    /// `occurrence` is one of them rather than suppressed.
+    pub fn admit(&self, occurrence: Occurrence) -> (Vec<Occurrence>, bool) {
+        let now = Instant::now();
+        let mut out = Vec::new();
+        let Ok(mut bursts) = self.bursts.lock() else {
+            out.push(occurrence);
+            return (out, true);
+        };
+
+        bursts.retain(|&key, burst| {
+            if now.duration_since(burst.started) < self.config.window {
+                return true;
+            }
+            burst.flush(key, &mut out);
+            false
+        });
+
+        let burst = bursts
+            .entry(occurrence.id)
+            .or_insert_with(|| Burst::new(now));
+        burst.seen += 1;
+        burst.last_seen = now;
+        burst.last_at = SystemTime::now();
+
+        let admitted = burst.seen <= self.config.threshold;
+        if admitted {
+            out.push(occurrence);
+        } else {
+            burst.suppressed += 1;
+            burst.last = Some(occurrence);
+        }
+
+        (out, admitted)
+    }
+
+    pub fn drain(&self) -> Vec<Occurrence> {
+        let mut out = Vec::new();
+        if let Ok(mut bursts) = self.bursts.lock() {
+            for (id, mut burst) in bursts.drain() {
+                burst.flush(id, &mut out);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occurrence(message: &str) -> Occurrence {
+        Occurrence {
+            id: 7,
+            level: Level::WARN,
+            event_type: None,
+            category: 0,
+            message: to_wide(message),
+            inserts: Vec::new(),
+            data: Vec::new(),
+            #[cfg(windows)]
+            source: None,
+        }
+    }
+
+    fn text(occurrence: &Occurrence) -> String {
+        let message = &occurrence.message;
+        String::from_utf16_lossy(message.strip_suffix(&[0]).unwrap_or(message))
+    }
+
+    #[test]
+    fn last_occurrence_keeps_the_time_it_was_logged() {
+        let guard = FloodGuard::new(FloodProtection::new(1, Duration::from_secs(60)));
+        for n in 0..4 {
+            guard.admit(occurrence(&format!("ID: 7\n\nmessage: login failed {n}\n")));
+        }
+        let logged_at = guard.bursts.lock().unwrap()[&7].last_at;
+
+        let held = guard.drain();
+        assert_eq!(held.len(), 2);
+        assert!(text(&held[0]).contains("suppressed 2 occurrences"));
+        assert_eq!(
+            text(&held[1]),
+            format!(
+                "ID: 7\n\nmessage: login failed 3\noriginal_timestamp: {}\n",
+                format_system_time(logged_at)
+            )
+        );
+        assert_eq!(held[1].message.last(), Some(&0));
+    }
+
+    #[test]
+    fn message_file_inserts_are_held_unchanged() {
+        let guard = FloodGuard::new(FloodProtection::new(1, Duration::from_secs(60)));
+        guard.admit(occurrence("alice"));
+        guard.admit(occurrence("bob"));
+        let held = guard.drain();
+        assert_eq!(held.len(), 1);
+        assert_eq!(text(&held[0]), "bob");
+    }
+}
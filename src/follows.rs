@@ -0,0 +1,13 @@
+/// Spans a span follows from, rendered as `name#id`.
+#[derive(Debug, Default)]
+pub struct FollowsFrom(pub Vec<String>);
+
+/// Field listing the `follows_from` links of the spans in scope.
+pub const FOLLOWS_FROM_FIELD: &str = "follows_from";
+
+/// Field carrying the ID of the current span, the other end of a link.
+pub const SPAN_ID_FIELD: &str = "span_id";
+
+pub fn link(name: &str, id: &tracing::span::Id) -> String {
+    format!("{name}#{}", id.into_u64())
+}
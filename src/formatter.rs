@@ -0,0 +1,227 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+use tracing::{Level, Metadata};
+
+use crate::template::Template;
+
+/// What an [`EventFormatter`] describes: an event after its ID, category and fields have
+/// been resolved.
+#[derive(Debug)]
+pub struct EventContext<'a> {
+    /// The callsite of the event or span, `None` for events the layer writes itself, e.g.
+    /// the startup event.
+    pub metadata: Option<&'a Metadata<'a>>,
+    pub id: u32,
+    pub category: u16,
+    /// The level after any [severity floor](crate::EventLogLayer::with_severity_floor).
+    pub level: Level,
+    pub message: Option<&'a str>,
+    /// The enclosing spans, outermost first, separated by ` / `.
+    pub span_path: Option<&'a str>,
+    pub(crate) fields: &'a HashMap<String, String>,
+}
+
+impl<'a> EventContext<'a> {
+    /// The fields of the event other than the reserved ones, in no particular order.
+    pub fn fields(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Produces the description of events that have no template.
+///
+/// The description is written as is, after the layout options such as
+/// [`with_single_line`](crate::EventLogLayer::with_single_line) are applied.
+pub trait EventFormatter: fmt::Debug + Send + Sync {
+    fn format(&self, event: &EventContext<'_>) -> String;
+}
+
+impl<F: EventFormatter + ?Sized> EventFormatter for Arc<F> {
+    fn format(&self, event: &EventContext<'_>) -> String {
+        (**self).format(event)
+    }
+}
+
+/// The description used by default:
+///
+/// ```text
+/// ID: 42
+///
+/// source: server / request
+/// message: disk almost full
+/// volume: "C:"
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl EventFormatter for DefaultFormatter {
+    fn format(&self, event: &EventContext<'_>) -> String {
+        let mut msg = format!("ID: {}\n\n", event.id);
+
+        if let Some(m) = event.span_path {
+            let _ = writeln!(msg, "source: {m}");
+        }
+        if let Some(m) = event.message {
+            let _ = writeln!(msg, "message: {m}");
+        }
+
+        for (name, value) in event.fields() {
+            let _ = writeln!(msg, "{name}: {:?}", value.replace(r"\\", r"\"));
+        }
+
+        msg
+    }
+}
+
+/// Describes events by filling in a template string, to rearrange the description without
+/// implementing [`EventFormatter`].
+///
+/// The placeholders `{id}`, `{category}`, `{level}`, `{target}`, `{message}` and
+/// `{span_path}` are replaced by the respective part of the event, or nothing if the event
+/// has none; `{fields}` by one `name: value` line per field. Any other `{name}` is the
+/// value of the field `name`, kept as is if the event has no such field. `{{` and `}}` are
+/// literal braces.
+///
+/// ```
+/// use tracing_layer_win_eventlog::TemplateFormatter;
+///
+/// let formatter = TemplateFormatter::new("{message}\n\n{span_path}\n{fields}");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TemplateFormatter {
+    template: Template,
+}
+
+impl TemplateFormatter {
+    #[must_use]
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: Template::parse(template),
+        }
+    }
+}
+
+impl EventFormatter for TemplateFormatter {
+    fn format(&self, event: &EventContext<'_>) -> String {
+        let id = event.id.to_string();
+        let category = event.category.to_string();
+        let fields = event
+            .fields()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.template.render_string(|name| match name {
+            "id" => Some(&id),
+            "category" => Some(&category),
+            "level" => Some(event.level.as_str()),
+            "target" => Some(event.metadata.map_or("", Metadata::target)),
+            "message" => Some(event.message.unwrap_or_default()),
+            "span_path" => Some(event.span_path.unwrap_or_default()),
+            "fields" => Some(&fields),
+            name => event.fields.get(name).map(String::as_str),
+        })
+    }
+}
+
+/// Describes events as a single JSON object, for log shippers that scrape the event log:
+///
+/// ```text
+/// {"id":42,"category":0,"level":"WARN","target":"server::disk","span_path":"server / request","message":"disk almost full","fields":{"volume":"C:"}}
+/// ```
+///
+/// `span_path` and `message` are `null` if the event has none, `target` if the layer
+/// wrote the event itself. Field values are strings, with the fields sorted by name;
+/// string fields hold the string itself, other fields their `Debug` form.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl EventFormatter for JsonFormatter {
+    fn format(&self, event: &EventContext<'_>) -> String {
+        let mut json = format!(
+            "{{\"id\":{},\"category\":{},\"level\":\"{}\",\"target\":",
+            event.id, event.category, event.level
+        );
+        push_json(&mut json, event.metadata.map(Metadata::target));
+        json.push_str(",\"span_path\":");
+        push_json(&mut json, event.span_path);
+        json.push_str(",\"message\":");
+        push_json(&mut json, event.message);
+        json.push_str(",\"fields\":{");
+        let fields: BTreeMap<_, _> = event.fields().collect();
+        for (i, (name, value)) in fields.into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            push_json(&mut json, Some(name));
+            json.push(':');
+            push_json(&mut json, Some(&unquote(value)));
+        }
+        json.push_str("}}");
+        json
+    }
+}
+
+/// Reverses the `Debug` form of a string field, `"C:\\temp"` becomes `C:\temp`. Other
+/// values are returned as is.
+fn unquote(value: &str) -> Cow<'_, str> {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return Cow::Borrowed(value);
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let unescaped = match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return Cow::Borrowed(value),
+                }
+            }
+            _ => return Cow::Borrowed(value),
+        };
+        out.push(unescaped);
+    }
+    Cow::Owned(out)
+}
+
+/// Appends `value` as a JSON string, or `null`.
+fn push_json(json: &mut String, value: Option<&str>) {
+    let Some(value) = value else {
+        json.push_str("null");
+        return;
+    };
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
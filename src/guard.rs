@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use tracing::Level;
+
+use crate::coalesce::Coalescer;
+use crate::flood::{FloodGuard, Occurrence};
+use crate::output::Output;
+use crate::rate_limit::RateLimiter;
+use crate::sampling::Sampler;
+use crate::stats::EventLogStats;
+use crate::wide::to_wide;
+use crate::worker::Stopper;
+
+/// Flushes the layer and deregisters the event source when dropped, returned by
+/// [`init`](crate::init), [`EventLogLayerBuilder::install`](crate::EventLogLayerBuilder::install)
+/// and [`EventLogLayer::guard`](crate::EventLogLayer::guard).
+///
+/// The global subscriber is never dropped, so without the guard events still queued for a
+/// non-blocking writer or held back by flood protection, sampling, rate limits and
+/// duplicate coalescing are lost when the process exits. Hold it in `main`:
+///
+/// ```no_run
+/// let _guard = tracing_layer_win_eventlog::init("hello_world")?
+///     .with_shutdown_event(1006, "service stopped");
+/// tracing::info!("written before main returns");
+/// # Ok::<(), tracing_layer_win_eventlog::Error>(())
+/// ```
+///
+/// On drop the guard stops the non-blocking writer after it has written its queue, writes
+/// the events held back, then the shutdown event if one is set, and deregisters the
+/// source if the layer registered it for itself, e.g. with
+/// [`EventLogLayer::with_shared_source(false)`](crate::EventLogLayer::with_shared_source)
+/// or on a remote machine. Events logged after that are not written. A handle shared
+/// through [`EventSourceRegistry`](crate::EventSourceRegistry) stays registered while
+/// other layers of the source still use it, and is deregistered by the guard of the last
+/// of them, even if direct writers still hold it.
+#[derive(Debug)]
+#[must_use = "dropping the guard flushes the layer and may deregister the event source"]
+pub struct EventLogGuard {
+    worker: Option<Stopper>,
+    output: Output,
+    flood: Option<Arc<FloodGuard>>,
+    sampler: Option<Arc<Sampler>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    coalescer: Option<Arc<Coalescer>>,
+    shutdown: Option<(u32, String)>,
+}
+
+impl EventLogGuard {
+    pub(crate) const fn new(
+        worker: Option<Stopper>,
+        output: Output,
+        flood: Option<Arc<FloodGuard>>,
+        sampler: Option<Arc<Sampler>>,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        coalescer: Option<Arc<Coalescer>>,
+    ) -> Self {
+        Self {
+            worker,
+            output,
+            flood,
+            sampler,
+            rate_limiter,
+            coalescer,
+            shutdown: None,
+        }
+    }
+
+    /// Writes an INFO event with ID `id` and `message` as the last event before the source
+    /// is deregistered, so a clean shutdown can be told apart from a crash.
+    pub fn with_shutdown_event(mut self, id: u32, message: impl Into<String>) -> Self {
+        self.shutdown = Some((id, message.into()));
+        self
+    }
+
+    /// See [`EventLogLayer::stats`](crate::EventLogLayer::stats).
+    #[must_use]
+    pub fn stats(&self) -> EventLogStats {
+        self.output.stats.snapshot()
+    }
+}
+
+/// The events flood protection, sampling, rate limits and duplicate coalescing still hold
+/// back, as summaries.
+pub(crate) fn held(
+    flood: Option<&FloodGuard>,
+    sampler: Option<&Sampler>,
+    rate_limiter: Option<&RateLimiter>,
+    coalescer: Option<&Coalescer>,
+) -> Vec<Occurrence> {
+    let mut held = flood.map(FloodGuard::drain).unwrap_or_default();
+    let summaries = sampler
+        .map(Sampler::drain)
+        .into_iter()
+        .chain(rate_limiter.map(RateLimiter::drain))
+        .chain(coalescer.map(Coalescer::drain))
+        .flatten();
+    held.extend(summaries.map(|(id, level, message)| Occurrence {
+        id,
+        level,
+        event_type: None,
+        category: 0,
+        message,
+        inserts: Vec::new(),
+        data: Vec::new(),
+        source: None,
+    }));
+    held
+}
+
+impl Drop for EventLogGuard {
+    fn drop(&mut self) {
+        if let Some(worker) = &self.worker {
+            worker.stop();
+        }
+        for occurrence in held(
+            self.flood.as_deref(),
+            self.sampler.as_deref(),
+            self.rate_limiter.as_deref(),
+            self.coalescer.as_deref(),
+        ) {
+            self.output.report_occurrence(&occurrence);
+        }
+        if let Some((id, message)) = &self.shutdown {
+            let description = format!("ID: {id}\n\nmessage: {message}\n");
+            self.output
+                .report(*id, Level::INFO, None, 0, &to_wide(&description), &[], &[]);
+        }
+        // A shared handle may be in use by other layers of the source, e.g. one built after
+        // a configuration reload; it is deregistered once the last of them lets go of it.
+        if let Some(claim) = self.output.claim() {
+            claim.release();
+        } else if let Some(source) = self.output.held_source().filter(|s| !s.is_shared()) {
+            source.deregister();
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::time::Duration;
+use tracing::Level;
+
+use crate::banner::StartupBanner;
+use crate::chunk;
+use crate::drift::{digest, serialize};
+use crate::error::{BatchError, Error};
+use crate::event_type::EventType;
+use crate::heartbeat::Heartbeat;
+use crate::output::Output;
+use crate::panic::PanicReport;
+use crate::record::EventRecord;
+use crate::scoped::ScopedEvent;
+use crate::stats::EventLogStats;
+use crate::wide::to_wide;
+
+/// A handle to write directly through the event source of an [`EventLogLayer`], usable
+/// after the layer has been moved into the subscriber.
+///
+/// The handle reflects the layer's configuration at the time [`EventLogLayer::handle`]
+/// is called.
+///
+/// [`EventLogLayer`]: crate::EventLogLayer
+/// [`EventLogLayer::handle`]: crate::EventLogLayer::handle
+#[derive(Debug, Clone)]
+pub struct EventLogHandle {
+    pub(crate) output: Output,
+    pub(crate) banner: Option<StartupBanner>,
+    /// The effective configuration of the layer, sorted by name.
+    pub(crate) settings: Vec<(String, String)>,
+    /// ID of the events written by [`EventLogHandle::write_panic`].
+    pub(crate) error_id: u32,
+}
+
+impl EventLogHandle {
+    /// ID of the event written by [`EventLogHandle::dump_config`].
+    pub const CONFIG_DUMP_ID: u32 = 1004;
+
+    /// See [`EventLogLayer::stats`](crate::EventLogLayer::stats).
+    #[must_use]
+    pub fn stats(&self) -> EventLogStats {
+        self.output.stats.snapshot()
+    }
+
+    pub(crate) fn report(&self, id: u32, level: Level, message: &[u16]) -> bool {
+        self.output.report(id, level, None, 0, message, &[], &[])
+    }
+
+    /// Writes `message` as the description of an event through the layer's source.
+    ///
+    /// # Errors
+    /// Fails if the source cannot be registered, the message contains a nul character or
+    /// `ReportEventW` fails.
+    pub fn try_write(&self, event_id: u32, level: Level, message: &str) -> Result<(), Error> {
+        if message.contains('\0') {
+            return Err(Error::InvalidMessage);
+        }
+        self.output
+            .try_report(event_id, level, None, 0, &to_wide(message), &[], &[])
+    }
+
+    /// Writes buffered `records` through the layer's source, see
+    /// [`write_events`](crate::write_events).
+    ///
+    /// # Errors
+    /// Stops at the first record that cannot be written.
+    pub fn write_events(&self, records: &[EventRecord]) -> Result<(), BatchError> {
+        self.output.try_write_records(records)
+    }
+
+    /// Writes `payload` as correlated chunk events through the layer's source and returns
+    /// the group GUID, see [`chunk::write_chunked`](crate::chunk::write_chunked).
+    ///
+    /// # Errors
+    /// Stops at the first chunk that cannot be written.
+    pub fn write_chunked(
+        &self,
+        event_id: u32,
+        level: Level,
+        payload: &str,
+    ) -> Result<String, BatchError> {
+        chunk::write_chunks(event_id, payload, |description| {
+            self.try_write(event_id, level, description)
+        })
+    }
+
+    /// Returns a guard that writes a completion event with ID `event_id` when dropped, see
+    /// [`ScopedEvent`].
+    pub fn scoped(&self, event_id: u32, message: impl Into<String>) -> ScopedEvent {
+        ScopedEvent::new(self, event_id, message)
+    }
+
+    /// Returns a guard that writes a heartbeat event with ID `event_id` every `interval`
+    /// and a completion event when dropped, see [`Heartbeat`].
+    ///
+    /// # Errors
+    /// Fails if the heartbeat thread cannot be spawned.
+    pub fn heartbeat(
+        &self,
+        event_id: u32,
+        message: impl Into<String>,
+        interval: Duration,
+    ) -> std::io::Result<Heartbeat> {
+        Heartbeat::start(self, event_id, message, interval)
+    }
+
+    /// Writes the startup banner configured with
+    /// [`EventLogLayer::with_startup_banner`](crate::EventLogLayer::with_startup_banner),
+    /// does nothing if none is.
+    pub fn emit_startup_banner(&self) {
+        let Some(banner) = &self.banner else {
+            return;
+        };
+
+        self.report(banner.event_id(), Level::INFO, &to_wide(&banner.describe()));
+    }
+
+    /// Writes the effective configuration of the layer, including the settings added to its
+    /// [`ConfigDrift`](crate::ConfigDrift), as an INFO event with ID
+    /// [`CONFIG_DUMP_ID`](EventLogHandle::CONFIG_DUMP_ID), one `name: value` line per
+    /// setting.
+    pub fn dump_config(&self) {
+        let id = Self::CONFIG_DUMP_ID;
+        let mut msg = format!("ID: {id}\n\nmessage: effective logging configuration\n");
+        for (name, value) in &self.settings {
+            let _ = writeln!(msg, "{name}: {value}");
+        }
+        let _ = writeln!(msg, "config_digest: {}", digest(&serialize(&self.settings)));
+        self.report(id, Level::INFO, &to_wide(&msg));
+    }
+
+    /// Writes a panic as an ERROR event of type [`EventType::Error`] with the layer's
+    /// default event ID, or the ID of ERROR events if none is set. Payload, thread,
+    /// location and backtrace are the insertion strings `%2` to `%5`. Call it from a panic
+    /// hook, or set one with [`install_panic_hook`](Self::install_panic_hook):
+    ///
+    /// ```ignore
+    /// std::panic::set_hook(Box::new(move |info| handle.write_panic(info)));
+    /// ```
+    pub fn write_panic(&self, info: &PanicHookInfo<'_>) {
+        let report = PanicReport::capture(info);
+        let message = to_wide(&report.describe(self.error_id));
+        self.output.report(
+            self.error_id,
+            Level::ERROR,
+            Some(EventType::Error),
+            0,
+            &message,
+            &report.inserts(),
+            &[],
+        );
+    }
+
+    /// Sets a panic hook writing every panic with [`write_panic`](Self::write_panic),
+    /// synchronously and bypassing the non-blocking writer, then calling the hook set
+    /// before, which prints the panic to stderr by default.
+    pub fn install_panic_hook(&self) {
+        let handle = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            handle.write_panic(info);
+            previous(info);
+        }));
+    }
+}
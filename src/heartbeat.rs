@@ -0,0 +1,151 @@
+use std::fmt::{Display, Write as _};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+use crate::handle::EventLogHandle;
+use crate::time::format_duration;
+use crate::wide::to_wide;
+
+/// Writes a heartbeat event every `interval` while a long-running operation is in
+/// progress and a completion event when dropped, so a hung job can be told apart from a
+/// slow one.
+///
+/// Both have the ID and message given at construction, the fields set with
+/// [`Heartbeat::set`], `elapsed` and `status`: `running` for heartbeats, `completed` or
+/// `panicked` for the completion event. Heartbeats and the completion event are INFO
+/// events, the completion event an ERROR event if the guard is dropped while the thread is
+/// unwinding from a panic.
+///
+/// ```ignore
+/// let heartbeat = handle.heartbeat(7002, "nightly import", Duration::from_secs(300))?;
+/// for (i, row) in rows.enumerate() {
+///     import(row)?;
+///     heartbeat.set("processed", i + 1);
+/// }
+/// ```
+#[derive(Debug)]
+#[must_use = "the heartbeats stop when the guard is dropped"]
+pub struct Heartbeat {
+    operation: Arc<Operation>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Operation {
+    handle: EventLogHandle,
+    id: u32,
+    message: String,
+    started: Instant,
+    fields: Mutex<Vec<(String, String)>>,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl Heartbeat {
+    /// Starts timing the operation now; the first heartbeat is written after `interval`.
+    ///
+    /// # Errors
+    /// Fails if the heartbeat thread cannot be spawned.
+    pub fn start(
+        handle: &EventLogHandle,
+        id: u32,
+        message: impl Into<String>,
+        interval: Duration,
+    ) -> std::io::Result<Self> {
+        let operation = Arc::new(Operation {
+            handle: handle.clone(),
+            id,
+            message: message.into(),
+            started: Instant::now(),
+            fields: Mutex::new(Vec::new()),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+        });
+
+        let beating = Arc::clone(&operation);
+        let thread = thread::Builder::new()
+            .name("eventlog-heartbeat".to_owned())
+            .spawn(move || beating.beat(interval))?;
+
+        Ok(Self {
+            operation,
+            thread: Some(thread),
+        })
+    }
+
+    /// Sets a field of the following heartbeats and the completion event, replacing its
+    /// previous value, e.g. the number of items processed so far.
+    pub fn set(&self, name: impl Into<String>, value: impl Display) {
+        let name = name.into();
+        let value = value.to_string();
+        let mut fields = self
+            .operation
+            .fields
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match fields.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, current)) => *current = value,
+            None => fields.push((name, value)),
+        }
+    }
+}
+
+impl Operation {
+    fn beat(&self, interval: Duration) {
+        let mut stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            let (guard, timeout) = self
+                .wake
+                .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                .unwrap_or_else(PoisonError::into_inner);
+            stopped = guard;
+            if !timeout.timed_out() {
+                return;
+            }
+            self.handle
+                .report(self.id, Level::INFO, &to_wide(&self.describe("running")));
+        }
+    }
+
+    fn describe(&self, status: &str) -> String {
+        let mut msg = format!("ID: {}\n\nmessage: {}\n", self.id, self.message);
+        let fields = self.fields.lock().unwrap_or_else(PoisonError::into_inner);
+        for (name, value) in fields.iter() {
+            let _ = writeln!(msg, "{name}: {value}");
+        }
+        let _ = write!(
+            msg,
+            "elapsed: {}\nstatus: {status}",
+            format_duration(self.started.elapsed())
+        );
+        msg
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        *self
+            .operation
+            .stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.operation.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let panicked = std::thread::panicking();
+        let (level, status) = if panicked {
+            (Level::ERROR, "panicked")
+        } else {
+            (Level::INFO, "completed")
+        };
+        self.operation.handle.report(
+            self.operation.id,
+            level,
+            &to_wide(&self.operation.describe(status)),
+        );
+    }
+}
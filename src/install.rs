@@ -0,0 +1,86 @@
+use crate::error::{Error, SourceExistsError};
+use crate::names::validate_source_name;
+use crate::registry::{Hive, Key};
+
+/// Registry key below `HKEY_LOCAL_MACHINE` holding the event logs and their sources.
+pub const EVENT_LOG_KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog";
+
+/// A message file shipped with the .NET Framework that holds `%1` for every event ID, so
+/// descriptions written by this crate render as they are.
+#[cfg(feature = "default-message-file")]
+pub const DEFAULT_MESSAGE_FILE: &str =
+    r"%SystemRoot%\Microsoft.NET\Framework\v4.0.30319\EventLogMessages.dll";
+
+/// Event types the registered source may write, all of [`EventType`](crate::EventType)
+/// except success (which has no flag).
+const TYPES_SUPPORTED: u32 = 0x1f;
+
+fn source_key(log: &str, source: &str) -> String {
+    format!(r"{EVENT_LOG_KEY}\{log}\{source}")
+}
+
+/// Registers `source` in `log` (e.g. `Application`) with `message_file` as its
+/// `EventMessageFile`, so the Event Viewer can render the descriptions of its events.
+///
+/// `message_file` may contain environment variables such as `%SystemRoot%`. Registering
+/// a source again with the same log and message file is a no-op. Writing below
+/// `HKEY_LOCAL_MACHINE` requires administrator rights, so this is typically called from an
+/// installer.
+///
+/// # Errors
+/// Fails with [`Error::InvalidSourceName`] if the name cannot be registered, with
+/// [`Error::SourceExists`] if the source is registered in another log or with another
+/// message file and with [`Error::Registry`] if a key or value cannot be written.
+pub fn register_source(log: &str, source: &str, message_file: &str) -> Result<(), Error> {
+    validate_source_name(source).map_err(Error::InvalidSourceName)?;
+    if let Some(existing) = find_source(source) {
+        let same_file = existing
+            .message_file
+            .as_deref()
+            .is_some_and(|file| file.eq_ignore_ascii_case(message_file));
+        if !existing.log.eq_ignore_ascii_case(log) || !same_file {
+            return Err(Error::SourceExists(existing));
+        }
+    }
+
+    let key = Key::try_create(Hive::LocalMachine, &source_key(log, source))
+        .map_err(|code| Error::Registry { code })?;
+    key.try_write_string("EventMessageFile", message_file, true)
+        .and_then(|()| key.try_write_dword("TypesSupported", TYPES_SUPPORTED))
+        .map_err(|code| Error::Registry { code })
+}
+
+/// The `EventMessageFile` of `source` in `log`, `None` if the source is not registered
+/// there (or has no message file).
+#[must_use]
+pub fn registered_message_file(log: &str, source: &str) -> Option<String> {
+    Key::open(Hive::LocalMachine, &source_key(log, source))?.read_string("EventMessageFile")
+}
+
+/// Registers `source` in `log` with [`DEFAULT_MESSAGE_FILE`] unless it already has a
+/// message file, so its events render without the "description cannot be found" text.
+///
+/// # Errors
+/// See [`register_source`].
+#[cfg(feature = "default-message-file")]
+pub fn register_default_message_file(log: &str, source: &str) -> Result<(), Error> {
+    if registered_message_file(log, source).is_some() {
+        return Ok(());
+    }
+    register_source(log, source, DEFAULT_MESSAGE_FILE)
+}
+
+/// Where `source` is registered, searching all logs as source names are unique across
+/// them.
+#[must_use]
+pub fn find_source(source: &str) -> Option<SourceExistsError> {
+    let logs = Key::open(Hive::LocalMachine, EVENT_LOG_KEY)?;
+    logs.subkeys().into_iter().find_map(|log| {
+        let key = Key::open(Hive::LocalMachine, &source_key(&log, source))?;
+        Some(SourceExistsError {
+            source: source.to_owned(),
+            message_file: key.read_string("EventMessageFile"),
+            log,
+        })
+    })
+}
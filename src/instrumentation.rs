@@ -0,0 +1,501 @@
+use std::collections::HashSet;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+use tracing::Level;
+
+use crate::guid::format_guid;
+#[cfg(all(windows, feature = "manifest-provider"))]
+use crate::manifest::ManifestEvent;
+use crate::template::Template;
+
+/// Value of the first channel; lower values are reserved for the built-in channels.
+const FIRST_CHANNEL_VALUE: u8 = 16;
+/// At most 8 channels per provider are enabled in the event log.
+const MAX_CHANNELS: usize = 8;
+
+/// Type of a channel, which decides who reads it and whether it is enabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelType {
+    /// Events for administrators, enabled by default.
+    Admin,
+    /// Events for tools and support staff, enabled by default.
+    Operational,
+    /// High-volume events for diagnosis, disabled by default.
+    Analytic,
+    /// Events for developers, disabled by default.
+    Debug,
+}
+
+impl ChannelType {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "Admin",
+            Self::Operational => "Operational",
+            Self::Analytic => "Analytic",
+            Self::Debug => "Debug",
+        }
+    }
+
+    const fn enabled(self) -> bool {
+        matches!(self, Self::Admin | Self::Operational)
+    }
+}
+
+/// An event of an [`InstrumentationManifest`].
+#[derive(Debug, Clone)]
+pub struct EventDefinition {
+    id: u16,
+    version: u8,
+    level: Level,
+    channel: Option<String>,
+    task: Option<String>,
+    keywords: Vec<String>,
+    message: Option<Template>,
+    fields: Vec<String>,
+}
+
+impl EventDefinition {
+    #[must_use]
+    pub const fn new(id: u16, level: Level) -> Self {
+        Self {
+            id,
+            version: 0,
+            level,
+            channel: None,
+            task: None,
+            keywords: Vec::new(),
+            message: None,
+            fields: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The channel the event is written to, by the name given to
+    /// [`InstrumentationManifest::channel`].
+    #[must_use]
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// The task of the event, by the name given to [`InstrumentationManifest::task`].
+    #[must_use]
+    pub fn task(mut self, task: impl Into<String>) -> Self {
+        self.task = Some(task.into());
+        self
+    }
+
+    /// Adds a keyword, by the name given to [`InstrumentationManifest::keyword`].
+    #[must_use]
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keywords.push(keyword.into());
+        self
+    }
+
+    /// Adds a field to the event's template, after the message and the fields added
+    /// before.
+    #[must_use]
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        if name != "message" && !self.fields.contains(&name) {
+            self.fields.push(name);
+        }
+        self
+    }
+
+    /// The message text in [`Template`] syntax, e.g. `{user} signed in: {message}`. Slots
+    /// of fields not added yet are added in order of appearance. Without one, the message
+    /// is `{message}`.
+    #[must_use]
+    pub fn message(mut self, template: &str) -> Self {
+        let template = Template::parse(template);
+        let slots: Vec<String> = template.slots().map(str::to_owned).collect();
+        for slot in slots {
+            self = self.field(slot);
+        }
+        self.message = Some(template);
+        self
+    }
+
+    /// `%1` for the message, `%2` onwards for the fields in template order.
+    fn insert(&self, name: &str) -> String {
+        if name == "message" {
+            return "%1".to_owned();
+        }
+        self.fields
+            .iter()
+            .position(|field| field == name)
+            .map_or_else(|| format!("{{{name}}}"), |i| format!("%{}", i + 2))
+    }
+
+    fn key(&self) -> String {
+        format!("event.{}.{}", self.id, self.version)
+    }
+}
+
+/// Why an [`InstrumentationManifest`] cannot be generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    /// Two events have the same ID and version.
+    DuplicateEvent { id: u16, version: u8 },
+    /// An event refers to a channel, task or keyword that was not defined.
+    Undefined { id: u16, name: String },
+    /// More than 8 channels were defined.
+    TooManyChannels,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateEvent { id, version } => {
+                write!(f, "event {id} version {version} is defined twice")
+            }
+            Self::Undefined { id, name } => write!(f, "event {id} refers to undefined {name}"),
+            Self::TooManyChannels => write!(f, "at most {MAX_CHANNELS} channels are allowed"),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// Generates the instrumentation manifest (`.man`) of a provider, for `wevtutil im` and
+/// `mc.exe`, from the same definitions the code writes its events with.
+///
+/// Every event gets a template of `win:UnicodeString` data items: the message, then its
+/// fields in order, which is the payload [`ManifestLayer`](crate::ManifestLayer) writes.
+/// Its message text refers to them as `%1` (the message) and `%2` onwards.
+///
+/// ```
+/// use tracing::Level;
+/// use tracing_layer_win_eventlog::{ChannelType, EventDefinition, InstrumentationManifest};
+///
+/// let manifest = InstrumentationManifest::new("MyCompany-MyService", 0x1db28f2e_8f80_4027_8c5a_a11f7f10f62d)
+///     .message_file(r"%ProgramFiles%\MyService\myservice.exe")
+///     .channel("Operational", ChannelType::Operational)
+///     .task(1, "Login")
+///     .keyword(0x1, "Auth")
+///     .event(
+///         EventDefinition::new(100, Level::INFO)
+///             .channel("Operational")
+///             .task("Login")
+///             .keyword("Auth")
+///             .message("{user} signed in: {message}"),
+///     );
+/// let xml = manifest.to_xml().unwrap();
+/// assert!(xml.contains(r#"<data name="user" inType="win:UnicodeString"/>"#));
+/// ```
+#[derive(Debug, Clone)]
+pub struct InstrumentationManifest {
+    name: String,
+    id: u128,
+    message_file: String,
+    channels: Vec<(String, ChannelType)>,
+    tasks: Vec<(u16, String)>,
+    keywords: Vec<(u64, String)>,
+    events: Vec<EventDefinition>,
+}
+
+impl InstrumentationManifest {
+    /// The manifest of the provider `name` with the GUID `id`.
+    #[must_use]
+    pub fn new(name: impl Into<String>, id: u128) -> Self {
+        Self {
+            name: name.into(),
+            id,
+            message_file: String::new(),
+            channels: Vec::new(),
+            tasks: Vec::new(),
+            keywords: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// The binary the manifest is compiled into with `mc.exe` and `rc.exe`, as the
+    /// resource and message file of the provider.
+    #[must_use]
+    pub fn message_file(mut self, path: impl Into<String>) -> Self {
+        self.message_file = path.into();
+        self
+    }
+
+    /// Adds the channel `<provider>/<name>`. Channels are numbered from 16 in the order
+    /// they are added.
+    #[must_use]
+    pub fn channel(mut self, name: impl Into<String>, channel_type: ChannelType) -> Self {
+        self.channels.push((name.into(), channel_type));
+        self
+    }
+
+    #[must_use]
+    pub fn task(mut self, value: u16, name: impl Into<String>) -> Self {
+        self.tasks.push((value, name.into()));
+        self
+    }
+
+    /// Adds a keyword; `mc.exe` reserves the upper 16 bits of the mask for channels.
+    #[must_use]
+    pub fn keyword(mut self, mask: u64, name: impl Into<String>) -> Self {
+        self.keywords.push((mask, name.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn event(mut self, event: EventDefinition) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    fn validate(&self) -> Result<(), ManifestError> {
+        if self.channels.len() > MAX_CHANNELS {
+            return Err(ManifestError::TooManyChannels);
+        }
+        let mut seen = HashSet::new();
+        for event in &self.events {
+            if !seen.insert((event.id, event.version)) {
+                return Err(ManifestError::DuplicateEvent {
+                    id: event.id,
+                    version: event.version,
+                });
+            }
+            let undefined = |name: &str| ManifestError::Undefined {
+                id: event.id,
+                name: name.to_owned(),
+            };
+            if let Some(channel) = &event.channel {
+                if self.channel_index(channel).is_none() {
+                    return Err(undefined(&format!("channel {channel}")));
+                }
+            }
+            if let Some(task) = &event.task {
+                if !self.tasks.iter().any(|(_, name)| name == task) {
+                    return Err(undefined(&format!("task {task}")));
+                }
+            }
+            if let Some(keyword) = event
+                .keywords
+                .iter()
+                .find(|keyword| !self.keywords.iter().any(|(_, name)| name == *keyword))
+            {
+                return Err(undefined(&format!("keyword {keyword}")));
+            }
+        }
+        Ok(())
+    }
+
+    fn channel_index(&self, name: &str) -> Option<usize> {
+        self.channels
+            .iter()
+            .position(|(channel, _)| channel == name)
+    }
+
+    /// The manifest XML.
+    ///
+    /// # Errors
+    /// Fails if two events have the same ID and version, an event refers to an undefined
+    /// channel, task or keyword, or more than 8 channels are defined.
+    pub fn to_xml(&self) -> Result<String, ManifestError> {
+        self.validate()?;
+        let mut xml = String::new();
+        let file = escape(&self.message_file);
+        let _ = write!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<instrumentationManifest xmlns="http://schemas.microsoft.com/win/2004/08/events" xmlns:win="http://manifests.microsoft.com/win/2004/08/windows/events" xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <instrumentation>
+    <events>
+      <provider name="{}" guid="{{{}}}" symbol="{}" resourceFileName="{file}" messageFileName="{file}">
+"#,
+            escape(&self.name),
+            format_guid(self.id),
+            symbol(&self.name),
+        );
+
+        xml.push_str("        <events>\n");
+        for event in &self.events {
+            let _ = write!(
+                xml,
+                r#"          <event value="{}" version="{}" level="{}""#,
+                event.id,
+                event.version,
+                manifest_level(event.level)
+            );
+            if let Some(channel) = &event.channel {
+                let _ = write!(xml, r#" channel="{}""#, escape(channel));
+            }
+            if let Some(task) = &event.task {
+                let _ = write!(xml, r#" task="{}""#, escape(task));
+            }
+            if !event.keywords.is_empty() {
+                let _ = write!(xml, r#" keywords="{}""#, escape(&event.keywords.join(" ")));
+            }
+            let _ = writeln!(
+                xml,
+                r#" template="{0}" message="$(string.{0})"/>"#,
+                event.key()
+            );
+        }
+        xml.push_str("        </events>\n");
+
+        if !self.tasks.is_empty() {
+            xml.push_str("        <tasks>\n");
+            for (value, name) in &self.tasks {
+                let _ = writeln!(
+                    xml,
+                    r#"          <task name="{}" value="{value}"/>"#,
+                    escape(name)
+                );
+            }
+            xml.push_str("        </tasks>\n");
+        }
+        if !self.channels.is_empty() {
+            xml.push_str("        <channels>\n");
+            for (i, (name, channel_type)) in self.channels.iter().enumerate() {
+                let _ = writeln!(
+                    xml,
+                    r#"          <channel name="{}/{}" chid="{}" type="{}" enabled="{}" value="{}"/>"#,
+                    escape(&self.name),
+                    escape(name),
+                    escape(name),
+                    channel_type.as_str(),
+                    channel_type.enabled(),
+                    channel_value(i),
+                );
+            }
+            xml.push_str("        </channels>\n");
+        }
+        if !self.keywords.is_empty() {
+            xml.push_str("        <keywords>\n");
+            for (mask, name) in &self.keywords {
+                let _ = writeln!(
+                    xml,
+                    r#"          <keyword name="{}" mask="0x{mask:x}"/>"#,
+                    escape(name)
+                );
+            }
+            xml.push_str("        </keywords>\n");
+        }
+
+        xml.push_str("        <templates>\n");
+        for event in &self.events {
+            let _ = writeln!(xml, r#"          <template tid="{}">"#, event.key());
+            for name in std::iter::once("message").chain(event.fields.iter().map(String::as_str)) {
+                let _ = writeln!(
+                    xml,
+                    r#"            <data name="{}" inType="win:UnicodeString"/>"#,
+                    escape(name)
+                );
+            }
+            xml.push_str("          </template>\n");
+        }
+        xml.push_str("        </templates>\n");
+
+        xml.push_str("      </provider>\n    </events>\n  </instrumentation>\n");
+
+        xml.push_str("  <localization>\n    <resources culture=\"en-US\">\n      <stringTable>\n");
+        for event in &self.events {
+            let message = event.message.as_ref().map_or_else(
+                || "%1".to_owned(),
+                |template| template.message_text(|name| event.insert(name)),
+            );
+            let _ = writeln!(
+                xml,
+                r#"        <string id="{}" value="{}"/>"#,
+                event.key(),
+                escape(&message)
+            );
+        }
+        xml.push_str(
+            "      </stringTable>\n    </resources>\n  </localization>\n</instrumentationManifest>\n",
+        );
+        Ok(xml)
+    }
+
+    /// Writes the manifest XML to `path`.
+    ///
+    /// # Errors
+    /// Fails like [`InstrumentationManifest::to_xml`] or if the file cannot be written.
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let xml = self
+            .to_xml()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+        std::fs::write(path, xml)
+    }
+
+    /// The descriptors `mc.exe` generates for the events, for
+    /// [`ManifestLayer::with_event`](crate::ManifestLayer::with_event), so the layer and the
+    /// installed manifest cannot disagree. Each keyword mask includes the bit `mc.exe`
+    /// reserves for the event's channel.
+    #[cfg(all(windows, feature = "manifest-provider"))]
+    #[must_use]
+    pub fn manifest_events(&self) -> Vec<ManifestEvent> {
+        self.events
+            .iter()
+            .map(|event| {
+                let mut descriptor = ManifestEvent::new(event.id)
+                    .version(event.version)
+                    .level(crate::etw::etw_level(event.level));
+                let mut keyword = self
+                    .keywords
+                    .iter()
+                    .filter(|(_, name)| event.keywords.contains(name))
+                    .fold(0, |mask, (bits, _)| mask | bits);
+                if let Some(i) = event.channel.as_deref().and_then(|c| self.channel_index(c)) {
+                    descriptor = descriptor.channel(channel_value(i));
+                    keyword |= 1 << (63 - i);
+                }
+                if let Some(task) = &event.task {
+                    if let Some((value, _)) = self.tasks.iter().find(|(_, name)| name == task) {
+                        descriptor = descriptor.task(*value);
+                    }
+                }
+                descriptor.keyword(keyword)
+            })
+            .collect()
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn channel_value(index: usize) -> u8 {
+    FIRST_CHANNEL_VALUE + index as u8
+}
+
+const fn manifest_level(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => "win:Error",
+        Level::WARN => "win:Warning",
+        Level::INFO => "win:Informational",
+        _ => "win:Verbose",
+    }
+}
+
+/// The provider name as a C identifier, for the header `mc.exe` generates.
+fn symbol(name: &str) -> String {
+    let mut symbol: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if symbol.starts_with(|c: char| c.is_ascii_digit()) {
+        symbol.insert(0, '_');
+    }
+    symbol
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,131 @@
+/// Line separator used in event descriptions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub line_ending: LineEnding,
+    pub trim_trailing_whitespace: bool,
+    pub strip_ansi: bool,
+    pub single_line: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::Lf,
+            trim_trailing_whitespace: true,
+            strip_ansi: true,
+            single_line: false,
+        }
+    }
+}
+
+const NUL: u16 = 0;
+const LF: u16 = b'\n' as u16;
+const CR: u16 = b'\r' as u16;
+const ESC: u16 = 0x1b;
+
+const fn is_whitespace(c: u16) -> bool {
+    matches!(c, 0x20 | 0x09 | CR)
+}
+
+impl Layout {
+    /// Normalizes the line endings of a nul-terminated UTF-16 description, strips ANSI
+    /// escape sequences and trims trailing whitespace of every line and of the
+    /// description itself.
+    ///
+    /// In single-line mode every run of whitespace, line breaks included, becomes a single
+    /// space instead and the description is trimmed at both ends.
+    pub fn apply(&self, message: &[u16]) -> Vec<u16> {
+        let message = message.strip_suffix(&[NUL]).unwrap_or(message);
+        let stripped;
+        let message = if self.strip_ansi && message.contains(&ESC) {
+            stripped = strip_ansi(message);
+            &stripped[..]
+        } else {
+            message
+        };
+        if self.single_line {
+            return collapse_whitespace(message);
+        }
+        let mut out = Vec::with_capacity(message.len() + 1);
+
+        for (i, line) in message.split(|&c| c == LF).enumerate() {
+            if i > 0 {
+                if self.line_ending == LineEnding::CrLf {
+                    out.push(CR);
+                }
+                out.push(LF);
+            }
+            let line = line.strip_suffix(&[CR]).unwrap_or(line);
+            let line = if self.trim_trailing_whitespace {
+                let end = line
+                    .iter()
+                    .rposition(|&c| !is_whitespace(c))
+                    .map_or(0, |p| p + 1);
+                &line[..end]
+            } else {
+                line
+            };
+            out.extend_from_slice(line);
+        }
+
+        if self.trim_trailing_whitespace {
+            let end = out
+                .iter()
+                .rposition(|&c| !is_whitespace(c) && c != LF)
+                .map_or(0, |p| p + 1);
+            out.truncate(end);
+        }
+
+        out.push(NUL);
+        out
+    }
+}
+
+fn collapse_whitespace(message: &[u16]) -> Vec<u16> {
+    let mut out = Vec::with_capacity(message.len() + 1);
+    let mut pending_space = false;
+
+    for &c in message {
+        if is_whitespace(c) || c == LF {
+            pending_space = !out.is_empty();
+            continue;
+        }
+        if pending_space {
+            out.push(0x20);
+            pending_space = false;
+        }
+        out.push(c);
+    }
+
+    out.push(NUL);
+    out
+}
+
+/// Removes CSI sequences (`ESC [ … final`) and other two-character escapes.
+fn strip_ansi(message: &[u16]) -> Vec<u16> {
+    let mut out = Vec::with_capacity(message.len());
+    let mut chars = message.iter().copied();
+
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            out.push(c);
+            continue;
+        }
+        if chars.next() == Some(0x5b) {
+            for c in chars.by_ref() {
+                if (0x40..=0x7e).contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    out
+}
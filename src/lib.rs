@@ -1,4 +1,196 @@
+// Most of the crate only backs the Windows layer.
+#![cfg_attr(not(windows), allow(dead_code))]
+
+mod adaptive;
+#[cfg(windows)]
+mod affinity;
+#[cfg(windows)]
+mod banner;
+mod binary;
+mod blob;
+#[cfg(windows)]
+mod builder;
+mod catalog;
+#[cfg(windows)]
+pub mod chunk;
+mod coalesce;
+#[cfg(windows)]
+mod coexist;
+#[cfg(feature = "tokio")]
+mod correlation;
+#[cfg(windows)]
+mod decode;
+mod diagnostics;
+#[cfg(windows)]
+mod drift;
+#[cfg(not(feature = "lean"))]
+mod dual;
+#[cfg(windows)]
+mod error;
+#[cfg(all(windows, any(feature = "manifest-provider", feature = "tracelogging")))]
+mod etw;
+mod event_type;
+#[cfg(windows)]
 mod eventlog;
+mod export;
+mod fallback;
+mod flood;
+mod follows;
+#[cfg(not(feature = "lean"))]
+mod formatter;
+#[cfg(windows)]
+mod guard;
+mod guid;
+#[cfg(windows)]
+mod handle;
+#[cfg(windows)]
+mod heartbeat;
+#[cfg(windows)]
+mod install;
+mod instrumentation;
+mod layout;
+pub mod limits;
+#[cfg(feature = "log")]
+mod log_bridge;
+mod log_fields;
+#[cfg(all(windows, feature = "manifest-provider"))]
+mod manifest;
+mod mapping;
+mod missing;
+mod names;
+mod normalize;
+#[cfg(windows)]
+mod output;
+mod oversize;
+mod panic;
+#[cfg(windows)]
+pub mod preset;
+#[cfg(windows)]
+mod process;
+mod rate_limit;
+pub mod reader;
+mod record;
+#[cfg(windows)]
+mod registry;
+mod rollup;
+mod sampling;
+#[cfg(windows)]
+mod scoped;
+#[cfg(windows)]
+mod selftest;
+#[cfg(windows)]
+mod sid;
+#[cfg(windows)]
+mod sink;
+#[cfg(windows)]
+mod source;
+mod span_fields;
+mod span_name;
+mod spill;
+mod stack;
+#[cfg(windows)]
+mod stats;
+#[cfg(all(windows, feature = "stderr-capture"))]
+mod stderr;
+#[cfg(not(windows))]
+mod stub;
+mod target_id;
+mod template;
+mod time;
+#[cfg(all(windows, feature = "tracelogging"))]
+mod tracelogging;
+mod wide;
+#[cfg(windows)]
+mod worker;
+#[cfg(windows)]
+mod writer;
 
+pub use adaptive::AdaptiveVerbosity;
+#[cfg(windows)]
+pub use affinity::{bind_thread_source, ThreadSourceGuard};
+#[cfg(windows)]
+pub use banner::StartupBanner;
+pub use binary::DATA_FIELD;
+#[cfg(feature = "deflate")]
+pub use blob::Deflate;
+pub use blob::{Compression, DataBlob, BLOB_MAGIC, BLOB_VERSION, BLOB_VERSION_JSON};
+#[cfg(windows)]
+pub use builder::{init, EventLogLayerBuilder};
+pub use catalog::{ParseCatalogError, TemplateCatalog, TemplateCatalogs};
+pub use coalesce::DuplicateCoalescing;
+#[cfg(windows)]
+pub use coexist::{crate_versions, CRATE_VERSION};
+#[cfg(feature = "tokio")]
+pub use correlation::{correlation_id, with_correlation_id, CORRELATION_ID_FIELD};
+#[cfg(feature = "internal-diagnostics")]
+pub use diagnostics::{set_diagnostics_handler, Diagnostic};
+#[cfg(windows)]
+pub use drift::ConfigDrift;
+#[cfg(not(feature = "lean"))]
+pub use dual::{DualFormat, LEGACY_MESSAGE_FIELD};
+#[cfg(windows)]
+pub use error::{
+    BatchError, Error, ErrorClass, SourceExistsError, WriteError, WriteErrorKind,
+    ERROR_INVALID_HANDLE, ERROR_LOG_FILE_FULL, MISSING_FIELDS_FIELD,
+};
+pub use event_type::{EventType, EventTypeMapping, ParseEventTypeError, EVENT_TYPE_FIELD};
+#[cfg(windows)]
+pub use eventlog::{write_event, write_events, EventLogLayer, CATEGORY_FIELD};
+pub use fallback::Fallback;
+pub use flood::FloodProtection;
+#[cfg(not(feature = "lean"))]
+pub use formatter::{
+    DefaultFormatter, EventContext, EventFormatter, JsonFormatter, TemplateFormatter,
+};
+#[cfg(windows)]
+pub use guard::EventLogGuard;
+#[cfg(windows)]
+pub use handle::EventLogHandle;
+#[cfg(windows)]
+pub use heartbeat::Heartbeat;
+#[cfg(windows)]
+pub use install::{find_source, register_source, registered_message_file, EVENT_LOG_KEY};
+#[cfg(all(windows, feature = "default-message-file"))]
+pub use install::{register_default_message_file, DEFAULT_MESSAGE_FILE};
+pub use instrumentation::{ChannelType, EventDefinition, InstrumentationManifest, ManifestError};
+pub use layout::LineEnding;
+#[cfg(feature = "log")]
+pub use log_bridge::forward_log;
+#[cfg(all(windows, feature = "manifest-provider"))]
+pub use manifest::{ManifestEvent, ManifestLayer};
+pub use mapping::ValueMapping;
+pub use missing::MissingMessage;
+pub use names::{validate_source_name, SourceNameError, MAX_SOURCE_NAME_LEN};
+pub use normalize::FieldNameNormalization;
+pub use oversize::{OversizedMessage, DROPPED_FIELDS_FIELD, TRUNCATION_MARKER};
+pub use panic::PanicReport;
+#[cfg(windows)]
+pub use process::ContextScope;
+pub use rate_limit::RateLimit;
+pub use record::{EventRecord, FieldValue, SCHEMA_VERSION};
+pub use rollup::ROLLUP_FIELD;
+pub use sampling::ExponentialSampling;
+#[cfg(windows)]
+pub use scoped::ScopedEvent;
+#[cfg(windows)]
+pub use selftest::{self_test, SelfTestCheck, SelfTestReport, SELF_TEST_IDS};
+#[cfg(windows)]
+pub use sink::{CapturedEvent, EventSink, MockSink, SinkEvent};
+#[cfg(windows)]
+pub use source::{EventSource, EventSourceRegistry, HandleOwnership};
+pub use spill::Spillover;
+pub use stack::StackCompression;
+#[cfg(windows)]
+pub use stats::EventLogStats;
+#[cfg(all(windows, feature = "stderr-capture"))]
+pub use stderr::{capture_stderr, StderrCapture};
+#[cfg(not(windows))]
+pub use stub::EventLogLayer;
+pub use template::Template;
+#[cfg(all(windows, feature = "tracelogging"))]
+pub use tracelogging::{provider_id, TraceLoggingLayer};
+pub use wide::WideMessage;
+#[cfg(windows)]
+pub use worker::NonBlocking;
 #[cfg(windows)]
-pub use eventlog::EventLogLayer;
+pub use writer::EventLogWriter;
@@ -0,0 +1,47 @@
+//! Size accounting for prospective events.
+//!
+//! Descriptions are written as UTF-16 insertion strings, so their size depends on the
+//! UTF-16 encoding and not on the UTF-8 length of the Rust string.
+
+/// Maximum length of a single insertion string in UTF-16 code units.
+pub const MAX_STRING_LEN: usize = 31_839;
+
+/// Maximum combined size in bytes of the strings and the raw data of one event.
+pub const MAX_EVENT_SIZE: usize = 61_440;
+
+/// Length of `s` in UTF-16 code units, without the terminating nul.
+#[must_use]
+pub fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Encoded size of a prospective event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventSize {
+    /// Length of the description in UTF-16 code units, without the terminating nul.
+    pub description_len: usize,
+    /// Size of the raw data blob in bytes.
+    pub data_len: usize,
+}
+
+impl EventSize {
+    #[must_use]
+    pub fn of(description: &str, data: &[u8]) -> Self {
+        Self {
+            description_len: utf16_len(description),
+            data_len: data.len(),
+        }
+    }
+
+    /// Size in bytes as submitted, including the description's terminating nul.
+    #[must_use]
+    pub const fn total_bytes(&self) -> usize {
+        (self.description_len + 1) * 2 + self.data_len
+    }
+
+    /// Whether the event stays within [`MAX_STRING_LEN`] and [`MAX_EVENT_SIZE`].
+    #[must_use]
+    pub const fn fits(&self) -> bool {
+        self.description_len <= MAX_STRING_LEN && self.total_bytes() <= MAX_EVENT_SIZE
+    }
+}
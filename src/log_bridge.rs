@@ -0,0 +1,16 @@
+use tracing::level_filters::LevelFilter;
+use tracing_log::log::SetLoggerError;
+use tracing_log::{AsLog, LogTracer};
+
+/// Sets a `log` logger forwarding the records up to `max_level` to `tracing` as events, so
+/// dependencies using `log::info!` and friends reach the layer like tracing events.
+///
+/// The records carry their target, module path, file and line as the `log.*` fields the
+/// layer uses in place of the event's metadata, so target IDs, callsite messages and
+/// sampling work as for tracing events. Key-value pairs of records are not forwarded.
+///
+/// # Errors
+/// Fails if a `log` logger is already set.
+pub fn forward_log(max_level: LevelFilter) -> Result<(), SetLoggerError> {
+    LogTracer::init_with_filter(max_level.as_log())
+}
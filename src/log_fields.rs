@@ -0,0 +1,55 @@
+use tracing::Metadata;
+
+/// Metadata of events that arrive through `tracing-log`, which carries them as the
+/// reserved fields `log.target`, `log.module_path`, `log.file` and `log.line`.
+#[derive(Debug, Default, Clone)]
+pub struct LogMetadata {
+    pub target: Option<String>,
+    pub module_path: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+}
+
+impl LogMetadata {
+    /// Records a reserved field, returns `false` for any other field.
+    pub fn record_str(&mut self, name: &str, value: &str) -> bool {
+        let slot = match name {
+            "log.target" => &mut self.target,
+            "log.module_path" => &mut self.module_path,
+            "log.file" => &mut self.file,
+            _ => return false,
+        };
+        *slot = Some(value.to_owned());
+        true
+    }
+
+    pub fn record_u64(&mut self, name: &str, value: u64) -> bool {
+        if name != "log.line" {
+            return false;
+        }
+        self.line = Some(value);
+        true
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.target.is_none()
+            && self.module_path.is_none()
+            && self.file.is_none()
+            && self.line.is_none()
+    }
+
+    pub fn target<'a>(&'a self, metadata: &'a Metadata<'_>) -> &'a str {
+        self.target.as_deref().unwrap_or_else(|| metadata.target())
+    }
+
+    /// `file:line` of the original call site.
+    pub fn location(&self, metadata: &Metadata<'_>) -> String {
+        format!(
+            "{}:{}",
+            self.file.as_deref().or(metadata.file()).unwrap_or("?"),
+            self.line
+                .or_else(|| metadata.line().map(u64::from))
+                .unwrap_or_default()
+        )
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::Layer;
+use winapi::shared::evntprov::{EventWrite, EVENT_DESCRIPTOR};
+
+use crate::error::Error;
+use crate::etw::{data_descriptor, etw_level, FieldVisitor, Provider};
+use crate::guid::format_guid;
+use crate::normalize::FieldNameNormalization;
+use crate::record::FieldValue;
+use crate::target_id::TargetIds;
+use crate::wide::to_wide;
+
+/// The descriptor of an event defined in an instrumentation manifest, as `mc.exe`
+/// generates it for the header: the values must match the manifest for consumers to
+/// decode the event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestEvent {
+    id: u16,
+    version: u8,
+    channel: u8,
+    level: Option<u8>,
+    task: u16,
+    opcode: u8,
+    keyword: u64,
+}
+
+impl ManifestEvent {
+    /// Event `id`, version 0, without channel, task, opcode or keyword, with the ETW level
+    /// of the tracing level.
+    #[must_use]
+    pub const fn new(id: u16) -> Self {
+        Self {
+            id,
+            version: 0,
+            channel: 0,
+            level: None,
+            task: 0,
+            opcode: 0,
+            keyword: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Value of the channel, e.g. 16 for the first channel `mc.exe` numbers
+    /// automatically.
+    #[must_use]
+    pub const fn channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// ETW level written instead of the one mapped from the tracing level, for events whose
+    /// manifest defines a fixed level.
+    #[must_use]
+    pub const fn level(mut self, level: u8) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    #[must_use]
+    pub const fn task(mut self, task: u16) -> Self {
+        self.task = task;
+        self
+    }
+
+    #[must_use]
+    pub const fn opcode(mut self, opcode: u8) -> Self {
+        self.opcode = opcode;
+        self
+    }
+
+    /// Keyword mask of the event, including the bit `mc.exe` assigns to its channel (the
+    /// topmost bit for the first channel of the provider), without which an event does not
+    /// reach an event log channel.
+    #[must_use]
+    pub const fn keyword(mut self, keyword: u64) -> Self {
+        self.keyword = keyword;
+        self
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> u16 {
+        self.id
+    }
+
+    const fn descriptor(&self, level: u8) -> EVENT_DESCRIPTOR {
+        EVENT_DESCRIPTOR {
+            Id: self.id,
+            Version: self.version,
+            Channel: self.channel,
+            Level: match self.level {
+                Some(level) => level,
+                None => level,
+            },
+            Opcode: self.opcode,
+            Task: self.task,
+            Keyword: self.keyword,
+        }
+    }
+}
+
+/// A layer writing events through a manifest-based provider (`EventRegister` and
+/// `EventWrite`) instead of `ReportEventW`, so they land in the provider's own channels
+/// with their levels, tasks and keywords, e.g. `MyCompany-MyService/Operational`.
+///
+/// Each event needs an ID defined in the manifest: the `id` field, the ID mapped to its
+/// target with [`ManifestLayer::with_target_id`], or the default ID. Events without one
+/// are not written. The payload is the message followed by the other fields in the order
+/// they are declared at the callsite, each a nul-terminated UTF-16 string, so the template
+/// of an event lists a `win:UnicodeString` data item per field:
+///
+/// ```ignore
+/// let layer = ManifestLayer::new("MyCompany-MyService", 0x1db28f2e_8f80_4027_8c5a_a11f7f10f62d)?
+///     .with_event(ManifestEvent::new(100).channel(16).task(1).keyword(0x8000_0000_0000_0000));
+/// tracing_subscriber::registry().with(layer).init();
+///
+/// tracing::info!(id = 100, user = "alice", "signed in");
+/// ```
+///
+/// The manifest must be installed with `wevtutil im` for the events to reach a channel.
+///
+/// Besides the event ID, only the maximum level and the field name normalization carry
+/// over from [`EventLogLayer`](crate::EventLogLayer); target IDs are fixed per target, not
+/// ranges by level. The reserved `eventlog.*` fields, the missing message policy, span
+/// fields, layouts, templates, value mappings, sampling, rate limits, duplicate coalescing
+/// and flood protection do not apply: the reserved fields are written as ordinary fields,
+/// and an event without a message has an empty `%1`.
+#[derive(Debug)]
+pub struct ManifestLayer {
+    provider: Provider,
+    events: HashMap<u16, ManifestEvent>,
+    target_ids: TargetIds,
+    default_event_id: Option<u16>,
+    max_level: LevelFilter,
+    normalization: Option<FieldNameNormalization>,
+}
+
+impl ManifestLayer {
+    /// Registers the provider `name` with the GUID `id` of its manifest.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Register`] if `EventRegister` fails.
+    pub fn new(name: &str, id: u128) -> Result<Self, Error> {
+        Ok(Self {
+            provider: Provider::register(name, id)?,
+            events: HashMap::new(),
+            target_ids: TargetIds::default(),
+            default_event_id: None,
+            max_level: LevelFilter::TRACE,
+            normalization: None,
+        })
+    }
+
+    /// Writes the events with the ID of `event` with its descriptor. Events with other IDs
+    /// are written with the descriptor of [`ManifestEvent::new`].
+    #[must_use]
+    pub fn with_event(mut self, event: ManifestEvent) -> Self {
+        self.events.insert(event.id, event);
+        self
+    }
+
+    /// Event ID of events without an `id` field whose target is `prefix` or a module
+    /// below it, see [`EventLogLayer::with_target_id`](crate::EventLogLayer::with_target_id).
+    #[must_use]
+    pub fn with_target_id(mut self, prefix: &str, id: u16) -> Self {
+        self.target_ids.insert_fixed(prefix, u32::from(id));
+        self
+    }
+
+    /// Event ID of events with neither an `id` field nor a target ID.
+    #[must_use]
+    pub const fn with_default_event_id(mut self, id: u16) -> Self {
+        self.default_event_id = Some(id);
+        self
+    }
+
+    /// Most verbose level written, see
+    /// [`EventLogLayer::with_max_level`](crate::EventLogLayer::with_max_level).
+    #[must_use]
+    pub const fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Normalizes the field names like
+    /// [`EventLogLayer::with_field_name_normalization`](crate::EventLogLayer::with_field_name_normalization).
+    #[must_use]
+    pub fn with_field_name_normalization(mut self, normalization: FieldNameNormalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    #[must_use]
+    pub fn provider_name(&self) -> &str {
+        &self.provider.name
+    }
+
+    /// The provider ID formatted as a GUID.
+    #[must_use]
+    pub fn provider_id(&self) -> String {
+        format_guid(self.provider.id)
+    }
+}
+
+/// The value of an `id` field if it is a valid manifest event ID.
+fn event_id(value: &FieldValue) -> Option<u16> {
+    match value {
+        FieldValue::U64(id) => u16::try_from(*id).ok(),
+        FieldValue::I64(id) => u16::try_from(*id).ok(),
+        _ => None,
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ManifestLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.level() <= &self.max_level {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(
+        &self,
+        metadata: &Metadata<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) -> bool {
+        metadata.level() <= &self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+        let level = etw_level(*metadata.level());
+        // Keyword 0 asks whether any session listens at the level, the keywords of the
+        // event are checked by `EventWrite`.
+        if !self.provider.enabled(level, 0) {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::new(self.normalization.as_ref());
+        event.record(&mut visitor);
+        let mut id = None;
+        let mut message = None;
+        let mut fields = Vec::with_capacity(visitor.fields.len());
+        for (name, value) in &visitor.fields {
+            match name.as_str() {
+                "message" => message = Some(to_wide(&value.to_string())),
+                _ if name.eq_ignore_ascii_case("id") && event_id(value).is_some() => {
+                    id = event_id(value);
+                }
+                _ => fields.push(to_wide(&value.to_string())),
+            }
+        }
+        let Some(id) = id
+            .or_else(|| {
+                self.target_ids
+                    .get(visitor.log.target(metadata), *metadata.level())
+                    .and_then(|id| u16::try_from(id).ok())
+            })
+            .or(self.default_event_id)
+        else {
+            return;
+        };
+
+        let message = message.unwrap_or_else(|| vec![0]);
+        let data: Vec<Vec<u8>> = std::iter::once(&message)
+            .chain(&fields)
+            .map(|string| string.iter().flat_map(|unit| unit.to_le_bytes()).collect())
+            .collect();
+        let mut descriptors: Vec<_> = data.iter().map(|bytes| data_descriptor(bytes, 0)).collect();
+        let descriptor = self
+            .events
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| ManifestEvent::new(id))
+            .descriptor(level);
+        unsafe {
+            EventWrite(
+                self.provider.handle,
+                &descriptor,
+                u32::try_from(descriptors.len()).unwrap_or_default(),
+                descriptors.as_mut_ptr(),
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+const HRESULTS: &[(u32, &str)] = &[
+    (0x0000_0000, "S_OK"),
+    (0x0000_0001, "S_FALSE"),
+    (0x8000_4001, "E_NOTIMPL"),
+    (0x8000_4002, "E_NOINTERFACE"),
+    (0x8000_4003, "E_POINTER"),
+    (0x8000_4004, "E_ABORT"),
+    (0x8000_4005, "E_FAIL"),
+    (0x8000_FFFF, "E_UNEXPECTED"),
+    (0x8007_0005, "E_ACCESSDENIED"),
+    (0x8007_0006, "E_HANDLE"),
+    (0x8007_000E, "E_OUTOFMEMORY"),
+    (0x8007_0057, "E_INVALIDARG"),
+];
+
+const WIN32_ERRORS: &[(u32, &str)] = &[
+    (0, "ERROR_SUCCESS"),
+    (2, "ERROR_FILE_NOT_FOUND"),
+    (3, "ERROR_PATH_NOT_FOUND"),
+    (5, "ERROR_ACCESS_DENIED"),
+    (6, "ERROR_INVALID_HANDLE"),
+    (8, "ERROR_NOT_ENOUGH_MEMORY"),
+    (87, "ERROR_INVALID_PARAMETER"),
+    (122, "ERROR_INSUFFICIENT_BUFFER"),
+    (183, "ERROR_ALREADY_EXISTS"),
+    (1460, "ERROR_TIMEOUT"),
+    (1502, "ERROR_LOG_FILE_FULL"),
+];
+
+#[derive(Debug, Clone, Default)]
+struct Names {
+    names: HashMap<u64, String>,
+    hex: bool,
+}
+
+/// Display names for well-known values of enum-like fields.
+///
+/// A mapped value is written as `NAME (value)`, e.g. `result: E_ACCESSDENIED (0x80070005)`.
+/// Values without a name are written unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ValueMapping {
+    fields: HashMap<String, Names>,
+}
+
+impl ValueMapping {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `value` of `field` as `name`.
+    #[must_use]
+    pub fn map(mut self, field: impl Into<String>, value: u64, name: impl Into<String>) -> Self {
+        self.fields
+            .entry(field.into())
+            .or_default()
+            .names
+            .insert(value, name.into());
+        self
+    }
+
+    /// Writes the raw values of `field` in hexadecimal.
+    #[must_use]
+    pub fn hex(mut self, field: impl Into<String>) -> Self {
+        self.fields.entry(field.into()).or_default().hex = true;
+        self
+    }
+
+    /// Maps common HRESULTs of `field` to their names.
+    #[must_use]
+    pub fn hresults(self, field: impl Into<String>) -> Self {
+        let field = field.into();
+        HRESULTS
+            .iter()
+            .fold(self.hex(field.clone()), |mapping, (value, name)| {
+                mapping.map(field.clone(), u64::from(*value), *name)
+            })
+    }
+
+    /// Maps common Win32 error codes of `field` to their names.
+    #[must_use]
+    pub fn win32_errors(self, field: impl Into<String>) -> Self {
+        let field = field.into();
+        WIN32_ERRORS.iter().fold(self, |mapping, (value, name)| {
+            mapping.map(field.clone(), u64::from(*value), *name)
+        })
+    }
+
+    /// Replaces the mapped values in `fields`, returning the names of the changed fields.
+    pub(crate) fn apply(&self, fields: &mut HashMap<String, String>) -> Vec<String> {
+        let mut mapped = Vec::new();
+        for (field, names) in &self.fields {
+            let Some(value) = fields.get_mut(field) else {
+                continue;
+            };
+            let Some(code) = parse_code(value) else {
+                continue;
+            };
+            let Some(name) = names.names.get(&code) else {
+                continue;
+            };
+            *value = if names.hex {
+                format!("{name} (0x{code:08X})")
+            } else {
+                format!("{name} ({code})")
+            };
+            mapped.push(field.clone());
+        }
+        mapped
+    }
+}
+
+/// Parses a recorded field value as a code.
+///
+/// Negative values are read as 32-bit codes, as HRESULTs and NTSTATUS codes are often
+/// recorded as `i32`. Strings holding a hexadecimal `0x` literal are accepted as well.
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn parse_code(value: &str) -> Option<u64> {
+    let value = value.trim_matches('"');
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    match value.parse::<i64>() {
+        Ok(code) if code < 0 => i32::try_from(code).ok().map(|code| u64::from(code as u32)),
+        Ok(code) => Some(code as u64),
+        Err(_) => value.parse().ok(),
+    }
+}
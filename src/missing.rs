@@ -0,0 +1,15 @@
+use crate::template::Template;
+
+/// How to synthesize a message for events without a `message` field.
+#[derive(Debug, Clone, Default)]
+pub enum MissingMessage {
+    /// Leave the message out, the description starts with the fields.
+    #[default]
+    Omit,
+    /// Use the event's target and callsite name, e.g. `my_app::db: event src/db.rs:42`.
+    Callsite,
+    /// Use the value of the given field, which is then no longer listed separately.
+    Field(String),
+    /// Render the given template from the event's fields.
+    Template(Template),
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+/// Longest source name accepted, the limit of a registry key name.
+pub const MAX_SOURCE_NAME_LEN: usize = 255;
+
+/// Names that cannot be used as a source as they belong to the built-in logs.
+const RESERVED_NAMES: [&str; 3] = ["Application", "Security", "System"];
+
+/// Why a source name is invalid, see [`validate_source_name`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceNameError {
+    Empty,
+    /// Longer than [`MAX_SOURCE_NAME_LEN`] characters.
+    TooLong {
+        len: usize,
+    },
+    /// A backslash, which separates registry keys, or a control character.
+    InvalidCharacter(char),
+    /// The name of a built-in log.
+    Reserved(String),
+    /// Both `'` and `"`, so the name cannot be quoted in the XPath filter of an event log
+    /// query and events of the source could not be read back.
+    MixedQuotes,
+}
+
+impl fmt::Display for SourceNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the source name is empty"),
+            Self::TooLong { len } => write!(
+                f,
+                "the source name has {len} characters, at most {MAX_SOURCE_NAME_LEN} are allowed"
+            ),
+            Self::InvalidCharacter(c) => {
+                write!(f, "the source name contains the invalid character {c:?}")
+            }
+            Self::Reserved(name) => write!(f, "{name} is reserved for a built-in log"),
+            Self::MixedQuotes => write!(
+                f,
+                "the source name contains both ' and \", which cannot be quoted in a query"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SourceNameError {}
+
+/// Checks that `name` can be registered as an event source.
+///
+/// # Errors
+/// Fails if the name is empty, too long, contains a backslash or control character or both
+/// kinds of quotes, or is the name of a built-in log.
+pub fn validate_source_name(name: &str) -> Result<(), SourceNameError> {
+    if name.trim().is_empty() {
+        return Err(SourceNameError::Empty);
+    }
+    let len = name.chars().count();
+    if len > MAX_SOURCE_NAME_LEN {
+        return Err(SourceNameError::TooLong { len });
+    }
+    if let Some(c) = name.chars().find(|c| *c == '\\' || c.is_control()) {
+        return Err(SourceNameError::InvalidCharacter(c));
+    }
+    check_quotable(name)?;
+    if let Some(reserved) = RESERVED_NAMES
+        .iter()
+        .find(|reserved| reserved.eq_ignore_ascii_case(name))
+    {
+        return Err(SourceNameError::Reserved((*reserved).to_owned()));
+    }
+    Ok(())
+}
+
+/// Fails with [`SourceNameError::MixedQuotes`] if `name` cannot be an XPath string literal,
+/// which has no escapes: it may contain `'` or `"`, not both.
+pub(crate) fn check_quotable(name: &str) -> Result<(), SourceNameError> {
+    if name.contains('\'') && name.contains('"') {
+        return Err(SourceNameError::MixedQuotes);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ordinary_names() {
+        for name in [
+            "hello_world",
+            "My Service",
+            "O'Brien",
+            "say \"hi\"",
+            "Applications",
+        ] {
+            assert_eq!(validate_source_name(name), Ok(()), "{name}");
+        }
+        assert_eq!(
+            validate_source_name(&"x".repeat(MAX_SOURCE_NAME_LEN)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_names_that_cannot_be_registered() {
+        assert_eq!(validate_source_name(" "), Err(SourceNameError::Empty));
+        assert_eq!(
+            validate_source_name(&"é".repeat(MAX_SOURCE_NAME_LEN + 1)),
+            Err(SourceNameError::TooLong {
+                len: MAX_SOURCE_NAME_LEN + 1
+            })
+        );
+        assert_eq!(
+            validate_source_name("app\\worker"),
+            Err(SourceNameError::InvalidCharacter('\\'))
+        );
+        assert_eq!(
+            validate_source_name("app\tworker"),
+            Err(SourceNameError::InvalidCharacter('\t'))
+        );
+        assert_eq!(
+            validate_source_name("security"),
+            Err(SourceNameError::Reserved("Security".to_owned()))
+        );
+    }
+
+    #[test]
+    fn rejects_names_with_both_quotes() {
+        assert_eq!(
+            validate_source_name("it's \"quoted\""),
+            Err(SourceNameError::MixedQuotes)
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+/// Normalization of field names, applied before formatting and routing.
+///
+/// Prefixes are stripped first (the first matching one), then dots are replaced and
+/// finally the case is folded.
+#[derive(Debug, Clone, Default)]
+pub struct FieldNameNormalization {
+    strip_prefixes: Vec<String>,
+    dots_to_underscores: bool,
+    lowercase: bool,
+}
+
+impl FieldNameNormalization {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn strip_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.strip_prefixes.push(prefix.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn dots_to_underscores(mut self) -> Self {
+        self.dots_to_underscores = true;
+        self
+    }
+
+    #[must_use]
+    pub const fn lowercase(mut self) -> Self {
+        self.lowercase = true;
+        self
+    }
+
+    pub(crate) fn apply<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut name = Cow::Borrowed(
+            self.strip_prefixes
+                .iter()
+                .find_map(|p| name.strip_prefix(p.as_str()).filter(|n| !n.is_empty()))
+                .unwrap_or(name),
+        );
+
+        if self.dots_to_underscores && name.contains('.') {
+            name = Cow::Owned(name.replace('.', "_"));
+        }
+        if self.lowercase && name.chars().any(char::is_uppercase) {
+            name = Cow::Owned(name.to_lowercase());
+        }
+
+        name
+    }
+}
@@ -0,0 +1,467 @@
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use tracing::Level;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::error::{BatchError, Error, ErrorClass, WriteError};
+use crate::event_type::{EventType, EventTypeMapping};
+use crate::fallback::FallbackSink;
+use crate::flood::Occurrence;
+use crate::layout::Layout;
+use crate::oversize::OversizedMessage;
+use crate::record::EventRecord;
+use crate::sid::UserSid;
+use crate::sink::{EventSink, SinkEvent};
+use crate::source::{Claim, EventSource, EventSourceRegistry};
+use crate::spill::SpillFile;
+use crate::stats::Counters;
+use crate::time::format_system_time;
+use crate::wide::{to_wide, WideMessage};
+
+pub type ErrorHandler = Arc<dyn Fn(&WriteError) + Send + Sync>;
+
+/// Where and how descriptions are written, shared by the layer and its handles.
+#[derive(Clone)]
+pub struct Output {
+    pub log_name: String,
+    /// Remote machine to write to, `None` for the local one.
+    pub server: Option<String>,
+    pub source: Option<Arc<EventSource>>,
+    /// Set if `source` is shared through [`EventSourceRegistry`].
+    pub claim: Option<Arc<Claim>>,
+    pub layout: Layout,
+    pub event_types: EventTypeMapping,
+    pub on_error: Option<ErrorHandler>,
+    pub spill: Option<Arc<SpillFile>>,
+    pub fallback: Option<Arc<FallbackSink>>,
+    /// Account events are attributed to, `None` for no user.
+    pub user_sid: Option<Arc<UserSid>>,
+    pub oversized: OversizedMessage,
+    /// Set if the source is registered on the first write, shared by the clones.
+    pub lazy: Option<Arc<LazySource>>,
+    /// Replaces the source for every write if set.
+    pub sink: Option<Arc<dyn EventSink>>,
+    pub stats: Arc<Counters>,
+}
+
+/// A source registered on the first write instead of up front, and again on later writes
+/// as long as registering fails.
+#[derive(Debug)]
+pub struct LazySource {
+    /// Whether to use the handle of [`EventSourceRegistry`].
+    shared: bool,
+    source: RwLock<Option<Arc<EventSource>>>,
+    /// Set with `source` if it is shared.
+    claim: OnceLock<Arc<Claim>>,
+}
+
+impl LazySource {
+    pub const fn new(shared: bool) -> Self {
+        Self {
+            shared,
+            source: RwLock::new(None),
+            claim: OnceLock::new(),
+        }
+    }
+
+    fn get(&self) -> Option<Arc<EventSource>> {
+        self.source
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Output")
+            .field("log_name", &self.log_name)
+            .field("server", &self.server)
+            .field("source", &self.source)
+            .field("claim", &self.claim)
+            .field("layout", &self.layout)
+            .field("event_types", &self.event_types)
+            .field("on_error", &self.on_error.is_some())
+            .field("spill", &self.spill)
+            .field("fallback", &self.fallback)
+            .field("user_sid", &self.user_sid.is_some())
+            .field("oversized", &self.oversized)
+            .field("lazy", &self.lazy)
+            .field("sink", &self.sink.is_some())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl Output {
+    pub fn new(log_name: String) -> Self {
+        let source = EventSourceRegistry::get_or_register(&log_name);
+        Self {
+            claim: source.as_ref().map(EventSource::claim),
+            source,
+            ..Self::unregistered(log_name, None)
+        }
+    }
+
+    /// Registers the source on every write until it succeeds.
+    pub fn unregistered(log_name: String, server: Option<String>) -> Self {
+        Self {
+            log_name,
+            server,
+            source: None,
+            claim: None,
+            layout: Layout::default(),
+            event_types: EventTypeMapping::default(),
+            on_error: None,
+            spill: None,
+            fallback: None,
+            user_sid: None,
+            oversized: OversizedMessage::default(),
+            lazy: None,
+            sink: None,
+            stats: Arc::default(),
+        }
+    }
+
+    pub fn with_source(source: Arc<EventSource>) -> Self {
+        let output =
+            Self::unregistered(source.name().to_owned(), source.server().map(str::to_owned));
+        Self {
+            claim: source.is_shared().then(|| source.claim()),
+            source: Some(source),
+            ..output
+        }
+    }
+
+    /// Writes a nul-terminated UTF-16 `message` after applying the layout, with `data` as
+    /// the raw data of the event. The event type follows `level` unless overridden.
+    /// `inserts` are further insertion strings (`%2` onwards), written as is.
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_report(
+        &self,
+        id: u32,
+        level: Level,
+        event_type: Option<EventType>,
+        category: u16,
+        message: &[u16],
+        inserts: &[Vec<u16>],
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let event_type = event_type.unwrap_or_else(|| self.event_types.get(level));
+        let too_long = |len| Error::TooLong { len };
+        let message = self.layout.apply(message);
+        let message = message.strip_suffix(&[0]).unwrap_or(&message).to_vec();
+        let message =
+            WideMessage::from_utf16(self.oversized.description(message).map_err(too_long)?);
+        let user_sid = self.user_sid.as_deref();
+        let inserts = inserts
+            .iter()
+            .map(|insert| {
+                let insert = insert.strip_suffix(&[0]).unwrap_or(insert).to_vec();
+                self.oversized.insert(insert).map(WideMessage::from_utf16)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(too_long)?;
+        let strings: Vec<_> = std::iter::once(&message).chain(&inserts).collect();
+        let event = SinkEvent {
+            id,
+            level,
+            event_type,
+            category,
+            strings: &strings,
+            data,
+            user_sid,
+        };
+        let result = match &self.sink {
+            Some(sink) => sink.write(&event),
+            None => self
+                .current_source()
+                .and_then(|source| source.write(&event)),
+        };
+        match result {
+            Ok(()) => self.stats.written(1),
+            Err(_) => self.stats.failed(),
+        }
+        result
+    }
+
+    /// Like [`Output::try_report`], handing failures to the error handler.
+    #[allow(clippy::too_many_arguments)]
+    pub fn report(
+        &self,
+        id: u32,
+        level: Level,
+        event_type: Option<EventType>,
+        category: u16,
+        message: &[u16],
+        inserts: &[Vec<u16>],
+        data: &[u8],
+    ) -> bool {
+        let Err(error) = self.try_report(id, level, event_type, category, message, inserts, data)
+        else {
+            if let Some((id, notice)) = self.fallback.as_ref().and_then(|f| f.recovered()) {
+                let _ = self.try_report(id, Level::WARN, None, 0, &to_wide(&notice), &[], &[]);
+            }
+            if let Some(spill) = self.spill.as_ref().filter(|spill| spill.is_pending()) {
+                self.replay(spill);
+            }
+            return true;
+        };
+
+        let text = |wide: &[u16]| String::from_utf16_lossy(wide.strip_suffix(&[0]).unwrap_or(wide));
+        let mut record = EventRecord::new(self.log_name.clone(), id, level, text(message));
+        record.event_type = event_type;
+        record.category = category;
+        record.inserts = inserts.iter().map(|insert| text(insert)).collect();
+        record.data = data.to_vec();
+        if let Some(spill) = &self.spill {
+            // Retrying a permanent failure on replay would fail again.
+            if error.class() != ErrorClass::Permanent {
+                spill.append(&record);
+                diagnostics::emit(|| Diagnostic::Spilled {
+                    source: self.log_name.clone(),
+                });
+            }
+        }
+        if let Some(fallback) = &self.fallback {
+            fallback.write(&record);
+        }
+        self.handle_error(&WriteError::new(record, &error));
+        false
+    }
+
+    /// Writes the spilled records and a summary of the outage.
+    fn replay(&self, spill: &SpillFile) {
+        let Some(spilled) = spill.take() else {
+            return;
+        };
+        if let Err(e) = self.write_described(&spilled.records, describe_spilled) {
+            spill.restore(&spilled, e.written);
+            return;
+        }
+        diagnostics::emit(|| Diagnostic::Replayed {
+            source: self.log_name.clone(),
+            events: spilled.records.len(),
+        });
+
+        let id = spill.summary_id();
+        let mut summary = format!(
+            "ID: {id}\n\nmessage: event log outage, {} events replayed, {} dropped\n",
+            spilled.records.len(),
+            spilled.dropped
+        );
+        if let Some(first) = spilled.first_at {
+            let _ = writeln!(summary, "first_failure: {}", format_system_time(first));
+        }
+        if let Some(last) = spilled.last_at {
+            let _ = writeln!(summary, "last_failure: {}", format_system_time(last));
+        }
+        let _ = self.try_report(id, Level::WARN, None, 0, &to_wide(&summary), &[], &[]);
+    }
+
+    /// Writes `records` in order, stopping at the first failure.
+    ///
+    /// The message of a record becomes the description, followed by its fields and its
+    /// original timestamp. Messages that already are a description of this crate (starting
+    /// with `ID: `), e.g. those of failed writes, are kept as they are. The event type,
+    /// category, insertion strings and raw data of the record are written as well.
+    pub fn try_write_records(&self, records: &[EventRecord]) -> Result<(), BatchError> {
+        self.write_described(records, describe_record)
+    }
+
+    fn write_described(
+        &self,
+        records: &[EventRecord],
+        describe: fn(&EventRecord, &mut String),
+    ) -> Result<(), BatchError> {
+        let result = self.write_records(records, describe);
+        let written = result
+            .as_ref()
+            .map_or_else(|e| e.written, |()| records.len());
+        self.stats.written(written as u64);
+        if result.is_err() {
+            self.stats.failed();
+        }
+        result
+    }
+
+    fn write_records(
+        &self,
+        records: &[EventRecord],
+        describe: fn(&EventRecord, &mut String),
+    ) -> Result<(), BatchError> {
+        let sink: Arc<dyn EventSink> = match &self.sink {
+            Some(sink) => Arc::clone(sink),
+            None => self
+                .current_source()
+                .map_err(|error| BatchError { written: 0, error })?,
+        };
+
+        let mut text = String::new();
+        let mut wide = Vec::new();
+        for (written, record) in records.iter().enumerate() {
+            text.clear();
+            describe(record, &mut text);
+            if text.contains('\0') {
+                return Err(BatchError {
+                    written,
+                    error: Error::InvalidMessage,
+                });
+            }
+            wide.clear();
+            wide.extend(text.encode_utf16());
+            wide.push(0);
+            let mut message = self.layout.apply(&wide);
+            if message.last() == Some(&0) {
+                message.pop();
+            }
+            let too_long = |len| BatchError {
+                written,
+                error: Error::TooLong { len },
+            };
+            let message =
+                WideMessage::from_utf16(self.oversized.description(message).map_err(too_long)?);
+            let inserts = record
+                .inserts
+                .iter()
+                .map(|insert| {
+                    self.oversized
+                        .insert(insert.encode_utf16().collect())
+                        .map(WideMessage::from_utf16)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(too_long)?;
+            let strings: Vec<_> = std::iter::once(&message).chain(&inserts).collect();
+
+            sink.write(&SinkEvent {
+                id: record.id,
+                level: record.level,
+                event_type: record
+                    .event_type
+                    .unwrap_or_else(|| self.event_types.get(record.level)),
+                category: record.category,
+                strings: &strings,
+                data: &record.data,
+                user_sid: self.user_sid.as_deref(),
+            })
+            .map_err(|error| BatchError { written, error })?;
+        }
+        Ok(())
+    }
+
+    pub fn report_occurrence(&self, occurrence: &Occurrence) -> bool {
+        let bound;
+        let output = match &occurrence.source {
+            Some(source) => {
+                bound = Self {
+                    log_name: source.name().to_owned(),
+                    server: source.server().map(str::to_owned),
+                    source: Some(Arc::clone(source)),
+                    ..self.clone()
+                };
+                &bound
+            }
+            None => self,
+        };
+        output.report(
+            occurrence.id,
+            occurrence.level,
+            occurrence.event_type,
+            occurrence.category,
+            &occurrence.message,
+            &occurrence.inserts,
+            &occurrence.data,
+        )
+    }
+
+    /// The source to write through: the one held, the one registered lazily on the first
+    /// write, or a registration for this write only.
+    fn current_source(&self) -> Result<Arc<EventSource>, Error> {
+        if let Some(source) = &self.source {
+            return Ok(Arc::clone(source));
+        }
+        let Some(lazy) = &self.lazy else {
+            return self.register().map(Arc::new);
+        };
+        if let Some(source) = lazy.get() {
+            return Ok(source);
+        }
+
+        let mut slot = lazy.source.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(source) = slot.as_ref() {
+            return Ok(Arc::clone(source));
+        }
+        let source = if lazy.shared && self.server.is_none() {
+            let source = EventSourceRegistry::try_get_or_register(&self.log_name)?;
+            let _ = lazy.claim.set(source.claim());
+            source
+        } else {
+            self.register().map(Arc::new)?
+        };
+        *slot = Some(Arc::clone(&source));
+        Ok(source)
+    }
+
+    /// The source held or registered lazily, if any.
+    pub fn held_source(&self) -> Option<Arc<EventSource>> {
+        self.source
+            .clone()
+            .or_else(|| self.lazy.as_ref().and_then(|lazy| lazy.get()))
+    }
+
+    /// The claim on the shared source held or registered lazily, if any.
+    pub fn claim(&self) -> Option<&Arc<Claim>> {
+        self.claim
+            .as_ref()
+            .or_else(|| self.lazy.as_ref().and_then(|lazy| lazy.claim.get()))
+    }
+
+    fn register(&self) -> Result<EventSource, Error> {
+        let source = EventSource::try_register_on(self.server.as_deref(), &self.log_name);
+        diagnostics::emit(|| Diagnostic::RegistrationRetry {
+            source: self.log_name.clone(),
+            succeeded: source.is_ok(),
+        });
+        source
+    }
+
+    pub fn handle_error(&self, error: &WriteError) {
+        match &self.on_error {
+            Some(on_error) => on_error(error),
+            None => eprintln!("{error}"),
+        }
+    }
+}
+
+/// The description of a spilled record: a description of this crate gets its original
+/// timestamp like in [`describe_record`], the `%1` of a message file event or a template
+/// is written as it was.
+fn describe_spilled(record: &EventRecord, text: &mut String) {
+    if record.message.starts_with("ID: ") {
+        describe_record(record, text);
+    } else {
+        text.push_str(&record.message);
+    }
+}
+
+fn describe_record(record: &EventRecord, text: &mut String) {
+    if record.message.starts_with("ID: ") {
+        text.push_str(&record.message);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    } else {
+        let _ = write!(text, "ID: {}\n\n", record.id);
+        if !record.message.is_empty() {
+            let _ = writeln!(text, "message: {}", record.message);
+        }
+    }
+    for (name, value) in &record.fields {
+        let _ = writeln!(text, "{name}: {value}");
+    }
+    let _ = writeln!(
+        text,
+        "original_timestamp: {}",
+        format_system_time(record.timestamp)
+    );
+}
@@ -0,0 +1,115 @@
+use crate::limits::MAX_STRING_LEN;
+
+/// Appended to a description or insertion string cut to [`MAX_STRING_LEN`].
+pub const TRUNCATION_MARKER: &str = " [truncated]";
+
+/// Line added to a description whose longest fields were left out, listing their names.
+pub const DROPPED_FIELDS_FIELD: &str = "dropped_fields";
+
+const LF: u16 = b'\n' as u16;
+const CR: u16 = b'\r' as u16;
+
+/// What to do with a description or insertion string longer than [`MAX_STRING_LEN`]
+/// UTF-16 code units, which `ReportEventW` rejects or cuts short. An over-long string is
+/// never passed to the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OversizedMessage {
+    /// Cut the string and end it with [`TRUNCATION_MARKER`].
+    #[default]
+    Truncate,
+    /// Leave out the longest `name: value` lines of the description until it fits,
+    /// listing them in a [`DROPPED_FIELDS_FIELD`] line; the first line and the message
+    /// are kept. Truncates if that is not enough, and insertion strings of message file
+    /// events.
+    DropFields,
+    /// Do not write the event, hand it to the error handler as
+    /// [`WriteErrorKind::TooLong`](crate::WriteErrorKind::TooLong).
+    Reject,
+}
+
+impl OversizedMessage {
+    /// Fits the description `units` (without the terminating nul) into
+    /// [`MAX_STRING_LEN`], or returns its length if it is rejected.
+    pub(crate) fn description(self, units: Vec<u16>) -> Result<Vec<u16>, usize> {
+        if units.len() <= MAX_STRING_LEN {
+            return Ok(units);
+        }
+        match self {
+            Self::Truncate => Ok(truncate(units)),
+            Self::DropFields => Ok(truncate(drop_fields(&units))),
+            Self::Reject => Err(units.len()),
+        }
+    }
+
+    /// Like [`OversizedMessage::description`] for an insertion string after the first,
+    /// which has no fields to drop.
+    pub(crate) fn insert(self, units: Vec<u16>) -> Result<Vec<u16>, usize> {
+        if units.len() <= MAX_STRING_LEN {
+            return Ok(units);
+        }
+        match self {
+            Self::Truncate | Self::DropFields => Ok(truncate(units)),
+            Self::Reject => Err(units.len()),
+        }
+    }
+}
+
+fn truncate(mut units: Vec<u16>) -> Vec<u16> {
+    if units.len() <= MAX_STRING_LEN {
+        return units;
+    }
+    let marker: Vec<u16> = TRUNCATION_MARKER.encode_utf16().collect();
+    let mut end = MAX_STRING_LEN - marker.len();
+    // Keep surrogate pairs whole.
+    if (0xD800..0xDC00).contains(&units[end - 1]) {
+        end -= 1;
+    }
+    units.truncate(end);
+    units.extend(marker);
+    units
+}
+
+fn drop_fields(units: &[u16]) -> Vec<u16> {
+    let lines: Vec<&[u16]> = units.split(|&c| c == LF).collect();
+    let message: Vec<u16> = "message: ".encode_utf16().collect();
+    let mut candidates: Vec<usize> = (1..lines.len())
+        .filter(|&i| !lines[i].starts_with(&message) && lines[i].contains(&u16::from(b':')))
+        .collect();
+    candidates.sort_by_key(|&i| std::cmp::Reverse(lines[i].len()));
+
+    let mut kept = vec![true; lines.len()];
+    let mut names = Vec::new();
+    let mut len = units.len();
+    for i in candidates {
+        if len <= MAX_STRING_LEN {
+            break;
+        }
+        kept[i] = false;
+        len -= lines[i].len() + 1;
+        let line = String::from_utf16_lossy(lines[i]);
+        let name = line.split_once(':').map_or(line.as_str(), |(name, _)| name);
+        len += name.encode_utf16().count() + 2;
+        names.push(name.to_owned());
+    }
+
+    let mut out = Vec::with_capacity(len);
+    for (i, line) in lines.iter().enumerate() {
+        if kept[i] {
+            if i > 0 {
+                out.push(LF);
+            }
+            out.extend_from_slice(line);
+        }
+    }
+    if !names.is_empty() {
+        if out.last().is_some_and(|&c| c != LF) {
+            // Keep the line endings of the layout.
+            if lines[0].last() == Some(&CR) {
+                out.push(CR);
+            }
+            out.push(LF);
+        }
+        out.extend(format!("{DROPPED_FIELDS_FIELD}: {}", names.join(", ")).encode_utf16());
+    }
+    out
+}
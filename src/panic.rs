@@ -0,0 +1,65 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+
+use crate::wide::to_wide;
+
+/// A panic broken down into separate fields for automated crash triage.
+#[derive(Debug)]
+pub struct PanicReport {
+    pub message: String,
+    pub thread: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+impl PanicReport {
+    /// Captures the payload, location, current thread and a backtrace of a panic. Must be
+    /// called from within the panic hook to capture the panicking thread.
+    #[must_use]
+    pub fn capture(info: &PanicHookInfo<'_>) -> Self {
+        Self {
+            message: payload_message(info.payload()),
+            thread: std::thread::current()
+                .name()
+                .unwrap_or("<unnamed>")
+                .to_owned(),
+            location: info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    pub(crate) fn describe(&self, id: u32) -> String {
+        format!("ID: {id}\n\nmessage: panicked: {}\n", self.message)
+    }
+
+    /// The fields as insertion strings `%2` to `%5`: payload, thread, location (empty if
+    /// unknown) and backtrace.
+    pub(crate) fn inserts(&self) -> Vec<Vec<u16>> {
+        [
+            self.message.as_str(),
+            &self.thread,
+            self.location.as_deref().unwrap_or_default(),
+            &self.backtrace,
+        ]
+        .into_iter()
+        .map(to_wide)
+        .collect()
+    }
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        return (*s).to_owned();
+    }
+    if let Some(s) = payload.downcast_ref::<String>() {
+        return s.clone();
+    }
+    #[cfg(feature = "anyhow")]
+    if let Some(e) = payload.downcast_ref::<anyhow::Error>() {
+        return format!("{e:#}");
+    }
+    "Box<dyn Any>".to_owned()
+}
@@ -0,0 +1,38 @@
+//! Ready-made subscriber configurations.
+
+use tracing::Subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::{fmt, Layer as _};
+
+use crate::EventLogLayer;
+
+/// Colored console output for everything and WARN and above to the event log.
+///
+/// ```ignore
+/// use tracing_subscriber::util::SubscriberInitExt as _;
+///
+/// tracing_layer_win_eventlog::preset::console_and_eventlog(
+///     EventLogLayer::new("hello_world".to_owned()),
+/// )
+/// .init();
+/// ```
+pub fn console_and_eventlog(eventlog: EventLogLayer) -> impl Subscriber + Send + Sync {
+    console_and_eventlog_with_levels(eventlog, LevelFilter::TRACE, LevelFilter::WARN)
+}
+
+/// Like [`console_and_eventlog`] with explicit levels for both outputs. ANSI escape
+/// sequences are always stripped from the event log descriptions.
+pub fn console_and_eventlog_with_levels(
+    eventlog: EventLogLayer,
+    console_level: LevelFilter,
+    eventlog_level: LevelFilter,
+) -> impl Subscriber + Send + Sync {
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_filter(console_level))
+        .with(
+            eventlog
+                .with_ansi_stripped(true)
+                .with_filter(eventlog_level),
+        )
+}
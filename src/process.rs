@@ -0,0 +1,118 @@
+use std::mem::size_of;
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::processthreadsapi::{GetCurrentProcessId, ProcessIdToSessionId};
+use winapi::um::tlhelp32::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+};
+use winapi::um::winuser::{
+    GetProcessWindowStation, GetUserObjectInformationW, UOI_FLAGS, USEROBJECTFLAGS, WSF_VISIBLE,
+};
+
+/// Where captured process context is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextScope {
+    /// Only on the startup event written when the layer is attached to a subscriber.
+    StartupEvent,
+    /// On the startup event and on every event.
+    AllEvents,
+}
+
+#[derive(Debug)]
+pub struct StaticContext {
+    pub scope: ContextScope,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+struct ProcessEntry {
+    pid: u32,
+    parent_pid: u32,
+    image: String,
+}
+
+fn process_entries() -> Vec<ProcessEntry> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Vec::new();
+    }
+
+    let mut entries = Vec::new();
+    let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = u32::try_from(size_of::<PROCESSENTRY32W>()).unwrap_or_default();
+
+    let mut found = unsafe { Process32FirstW(snapshot, &mut entry) } != 0;
+    while found {
+        let len = entry
+            .szExeFile
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(entry.szExeFile.len());
+        entries.push(ProcessEntry {
+            pid: entry.th32ProcessID,
+            parent_pid: entry.th32ParentProcessID,
+            image: String::from_utf16_lossy(&entry.szExeFile[..len]),
+        });
+        found = unsafe { Process32NextW(snapshot, &mut entry) } != 0;
+    }
+
+    unsafe {
+        CloseHandle(snapshot);
+    }
+
+    entries
+}
+
+/// Captures the parent process ID and image name of the current process.
+pub fn ancestry(scope: ContextScope) -> StaticContext {
+    let pid = unsafe { GetCurrentProcessId() };
+    let entries = process_entries();
+
+    let mut fields = vec![("pid", pid.to_string())];
+
+    if let Some(parent_pid) = entries.iter().find(|e| e.pid == pid).map(|e| e.parent_pid) {
+        fields.push(("parent_pid", parent_pid.to_string()));
+        if let Some(parent) = entries.iter().find(|e| e.pid == parent_pid) {
+            fields.push(("parent_image", parent.image.clone()));
+        }
+    }
+
+    StaticContext { scope, fields }
+}
+
+fn has_visible_window_station() -> bool {
+    let station = unsafe { GetProcessWindowStation() };
+    if station.is_null() {
+        return false;
+    }
+
+    let mut flags: USEROBJECTFLAGS = unsafe { std::mem::zeroed() };
+    let mut needed = 0;
+    let result = unsafe {
+        GetUserObjectInformationW(
+            station.cast(),
+            i32::try_from(UOI_FLAGS).unwrap_or_default(),
+            std::ptr::addr_of_mut!(flags).cast(),
+            u32::try_from(size_of::<USEROBJECTFLAGS>()).unwrap_or_default(),
+            &mut needed,
+        )
+    };
+
+    result != 0 && flags.dwFlags & WSF_VISIBLE != 0
+}
+
+/// Captures the Windows session ID and whether the process runs interactively.
+///
+/// Services run in session 0 without a visible window station and are reported as
+/// not interactive.
+pub fn session(scope: ContextScope) -> StaticContext {
+    let mut session_id = 0;
+    let known = unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id) } != 0;
+
+    let mut fields = Vec::new();
+    if known {
+        fields.push(("session_id", session_id.to_string()));
+    }
+    let interactive = known && session_id != 0 && has_visible_window_station();
+    fields.push(("interactive", interactive.to_string()));
+
+    StaticContext { scope, fields }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::Level;
+
+use crate::time::{format_duration, format_system_time};
+use crate::wide::to_wide;
+
+const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+const fn index(level: Level) -> usize {
+    match level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        _ => 4,
+    }
+}
+
+/// Per level rate limits.
+///
+/// Of the events at a limited level, at most `max` are written per `window` (measured from
+/// the first event), whatever their ID or callsite. Further events are dropped and
+/// counted; a summary with the number of suppressed events is written at their level with
+/// ID `id`. Nothing is written when the window closes: the summary waits for the next
+/// event of any level after that, or for the layer or its guard to be dropped.
+#[derive(Clone, Copy)]
+pub struct RateLimit {
+    limits: [Option<(u32, Duration)>; 5],
+    id: u32,
+}
+
+impl RateLimit {
+    pub const DEFAULT_ID: u32 = 1007;
+
+    /// No limits yet, summaries with [`RateLimit::DEFAULT_ID`].
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            limits: [None; 5],
+            id: Self::DEFAULT_ID,
+        }
+    }
+
+    /// Writes at most `max` events of `level` per `window`.
+    #[must_use]
+    pub const fn with_limit(mut self, level: Level, max: u32, window: Duration) -> Self {
+        self.limits[index(level)] = Some((if max == 0 { 1 } else { max }, window));
+        self
+    }
+
+    /// Writes at most `max` events of `level` per second.
+    #[must_use]
+    pub const fn per_second(self, level: Level, max: u32) -> Self {
+        self.with_limit(level, max, Duration::from_secs(1))
+    }
+
+    /// Event ID of the summaries.
+    #[must_use]
+    pub const fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        map.entry(&"id", &self.id);
+        for (level, limit) in LEVELS.iter().zip(&self.limits) {
+            if let Some((max, window)) = limit {
+                map.entry(&level.as_str(), &format_args!("{max} per {window:?}"));
+            }
+        }
+        map.finish()
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    written: u32,
+    suppressed: u64,
+    first_suppressed: Option<SystemTime>,
+    last_suppressed: Option<SystemTime>,
+}
+
+impl Window {
+    const fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            written: 0,
+            suppressed: 0,
+            first_suppressed: None,
+            last_suppressed: None,
+        }
+    }
+
+    fn summary(
+        &self,
+        id: u32,
+        level: Level,
+        (max, window): (u32, Duration),
+    ) -> Option<(u32, Level, Vec<u16>)> {
+        let (Some(first), Some(last)) = (self.first_suppressed, self.last_suppressed) else {
+            return None;
+        };
+
+        Some((
+            id,
+            level,
+            to_wide(&format!(
+                "ID: {id}\n\nmessage: rate limit suppressed {} {level} events\nwritten: {max}\nwindow: {}\nfirst_suppressed: {}\nlast_suppressed: {}\n",
+                self.suppressed,
+                format_duration(window),
+                format_system_time(first),
+                format_system_time(last),
+            )),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimit,
+    windows: Mutex<[Option<Window>; 5]>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimit) -> Self {
+        Self {
+            config,
+            windows: Mutex::new([None, None, None, None, None]),
+        }
+    }
+
+    pub const fn config(&self) -> &RateLimit {
+        &self.config
+    }
+
+    /// Whether an event of `level` is within its limit, and the summaries of the windows
+    /// that closed since the last event.
+    pub fn admit(&self, level: Level) -> (bool, Vec<(u32, Level, Vec<u16>)>) {
+        let Ok(mut windows) = self.windows.lock() else {
+            return (true, Vec::new());
+        };
+
+        let now = Instant::now();
+        let mut summaries = Vec::new();
+        for (i, slot) in windows.iter_mut().enumerate() {
+            let Some((max, window)) = self.config.limits[i] else {
+                continue;
+            };
+            if slot
+                .as_ref()
+                .is_some_and(|w| now.duration_since(w.started) >= window)
+            {
+                summaries.extend(
+                    slot.take()
+                        .and_then(|w| w.summary(self.config.id, LEVELS[i], (max, window))),
+                );
+            }
+        }
+
+        let i = index(level);
+        let Some((max, _)) = self.config.limits[i] else {
+            return (true, summaries);
+        };
+        let window = windows[i].get_or_insert_with(|| Window::new(now));
+        if window.written < max {
+            window.written += 1;
+            return (true, summaries);
+        }
+
+        let at = SystemTime::now();
+        window.suppressed += 1;
+        window.first_suppressed.get_or_insert(at);
+        window.last_suppressed = Some(at);
+        (false, summaries)
+    }
+
+    pub fn drain(&self) -> Vec<(u32, Level, Vec<u16>)> {
+        let Ok(mut windows) = self.windows.lock() else {
+            return Vec::new();
+        };
+        windows
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let limit = self.config.limits[i]?;
+                slot.take()?.summary(self.config.id, LEVELS[i], limit)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_over_the_limit_are_summarized() {
+        let limiter =
+            RateLimiter::new(RateLimit::new().with_limit(Level::INFO, 2, Duration::from_secs(60)));
+        let admitted: Vec<_> = (0..4).map(|_| limiter.admit(Level::INFO).0).collect();
+        assert_eq!(admitted, [true, true, false, false]);
+        assert!(limiter.admit(Level::WARN).0);
+
+        let summaries = limiter.drain();
+        assert_eq!(summaries.len(), 1);
+        let (id, level, message) = &summaries[0];
+        assert_eq!((*id, *level), (RateLimit::DEFAULT_ID, Level::INFO));
+        assert!(String::from_utf16_lossy(message).contains("suppressed 2 INFO events"));
+        assert!(limiter.drain().is_empty());
+    }
+
+    #[test]
+    fn windows_without_suppressed_events_have_no_summary() {
+        let limiter = RateLimiter::new(RateLimit::new().with_limit(Level::WARN, 1, Duration::ZERO));
+        for _ in 0..3 {
+            let (admitted, summaries) = limiter.admit(Level::WARN);
+            assert!(admitted);
+            assert!(summaries.is_empty());
+        }
+        assert!(limiter.drain().is_empty());
+    }
+}
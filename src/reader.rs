@@ -0,0 +1,772 @@
+//! Helpers for reading back events written by this crate.
+
+use std::ops::RangeInclusive;
+use std::time::SystemTime;
+use tracing::Level;
+
+#[cfg(feature = "serde")]
+use crate::blob::BLOB_VERSION_JSON;
+pub use crate::export::write_csv;
+#[cfg(feature = "serde")]
+pub use crate::export::write_jsonl;
+
+use crate::binary::decode_hex;
+use crate::blob::{self, Compression};
+#[cfg(windows)]
+use crate::names::check_quotable;
+use crate::record::{EventRecord, FieldValue};
+use crate::time::{format_system_time, parse_system_time};
+
+/// Decodes the fields of a data blob written with [`DataBlob`](crate::DataBlob).
+///
+/// Compressed blobs are decoded with the codec of matching ID from `codecs`; with the
+/// `deflate` feature [`Deflate`](crate::Deflate) is always available. Returns `None` if
+/// the data is not a blob of this crate or no codec matches.
+#[must_use]
+pub fn decode_data(data: &[u8], codecs: &[&dyn Compression]) -> Option<Vec<(String, FieldValue)>> {
+    let (version, payload) = unpack(data, codecs)?;
+    match version {
+        #[cfg(feature = "serde")]
+        BLOB_VERSION_JSON => serde_json::from_slice::<EventRecord>(&payload)
+            .ok()
+            .map(|record| record.fields.into_iter().collect()),
+        version => blob::decode_fields(version, &payload),
+    }
+}
+
+/// Decodes the whole record of a data blob written with the `serde` feature.
+#[cfg(feature = "serde")]
+#[must_use]
+pub fn decode_record(data: &[u8], codecs: &[&dyn Compression]) -> Option<EventRecord> {
+    match unpack(data, codecs)? {
+        (BLOB_VERSION_JSON, payload) => serde_json::from_slice(&payload).ok(),
+        _ => None,
+    }
+}
+
+fn unpack(data: &[u8], codecs: &[&dyn Compression]) -> Option<(u8, Vec<u8>)> {
+    #[cfg(feature = "deflate")]
+    {
+        let mut all = codecs.to_vec();
+        all.push(&crate::blob::Deflate);
+        blob::unpack(data, &all)
+    }
+    #[cfg(not(feature = "deflate"))]
+    blob::unpack(data, codecs)
+}
+
+/// A query for events, rendered as the XPath filter of `EvtQuery`.
+///
+/// ```ignore
+/// use std::time::{Duration, SystemTime};
+/// use tracing::Level;
+/// use tracing_layer_win_eventlog::reader::Query;
+///
+/// let events = Query::new()
+///     .source("hello_world")
+///     .level(Level::ERROR)
+///     .since(SystemTime::now() - Duration::from_secs(24 * 3600))
+///     .max_results(100)
+///     .run()?;
+/// # Ok::<(), tracing_layer_win_eventlog::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Query {
+    log: String,
+    source: Option<String>,
+    ids: Vec<RangeInclusive<u32>>,
+    levels: Vec<u8>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    max_results: Option<usize>,
+    newest_first: bool,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self {
+            log: "Application".to_owned(),
+            source: None,
+            ids: Vec::new(),
+            levels: Vec::new(),
+            since: None,
+            until: None,
+            max_results: None,
+            newest_first: false,
+        }
+    }
+}
+
+impl Query {
+    /// Queries all events of the `Application` log.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The log (channel) to query, `Application` by default.
+    #[must_use]
+    pub fn log(mut self, log: impl Into<String>) -> Self {
+        self.log = log.into();
+        self
+    }
+
+    #[must_use]
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Adds `ids` to the matched event IDs; all IDs match if no range is added.
+    #[must_use]
+    pub fn ids(mut self, ids: RangeInclusive<u32>) -> Self {
+        self.ids.push(ids);
+        self
+    }
+
+    /// Adds `level` to the matched levels; all levels match if none is added.
+    ///
+    /// `INFO`, `DEBUG` and `TRACE` are all written as information events and cannot be
+    /// told apart by the query.
+    #[must_use]
+    pub fn level(mut self, level: Level) -> Self {
+        let level = system_level(level);
+        if !self.levels.contains(&level) {
+            self.levels.push(level);
+        }
+        self
+    }
+
+    #[must_use]
+    pub const fn since(mut self, since: SystemTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    #[must_use]
+    pub const fn until(mut self, until: SystemTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    #[must_use]
+    pub const fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Returns the newest events first instead of the oldest.
+    #[must_use]
+    pub const fn newest_first(mut self) -> Self {
+        self.newest_first = true;
+        self
+    }
+
+    /// The XPath filter of the query.
+    ///
+    /// A source name containing both `'` and `"` cannot be quoted, so the filter is not
+    /// valid for it; [`run`](Self::run) and `tail` fail with
+    /// [`Error::InvalidSourceName`](crate::Error::InvalidSourceName) instead.
+    #[must_use]
+    pub fn xpath(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(source) = &self.source {
+            conditions.push(format!("Provider[@Name={}]", quote(source)));
+        }
+        if !self.ids.is_empty() {
+            let ids: Vec<_> = self
+                .ids
+                .iter()
+                .map(|ids| format!("(EventID >= {} and EventID <= {})", ids.start(), ids.end()))
+                .collect();
+            conditions.push(format!("({})", ids.join(" or ")));
+        }
+        if !self.levels.is_empty() {
+            let levels: Vec<_> = self.levels.iter().map(|l| format!("Level={l}")).collect();
+            conditions.push(format!("({})", levels.join(" or ")));
+        }
+        if let Some(since) = self.since {
+            conditions.push(format!(
+                "TimeCreated[@SystemTime>='{}']",
+                format_system_time(since)
+            ));
+        }
+        if let Some(until) = self.until {
+            conditions.push(format!(
+                "TimeCreated[@SystemTime<='{}']",
+                format_system_time(until)
+            ));
+        }
+
+        if conditions.is_empty() {
+            "*".to_owned()
+        } else {
+            format!("*[System[{}]]", conditions.join(" and "))
+        }
+    }
+
+    /// A `wevtutil` command line printing the matching events as text.
+    #[must_use]
+    pub fn wevtutil_command(&self) -> String {
+        let mut command = format!(
+            "wevtutil qe \"{}\" /q:\"{}\" /f:text",
+            self.log,
+            self.xpath()
+        );
+        if self.newest_first {
+            command.push_str(" /rd:true");
+        }
+        if let Some(max_results) = self.max_results {
+            command.push_str(&format!(" /c:{max_results}"));
+        }
+        command
+    }
+
+    /// An Event Viewer custom view showing the matching events.
+    ///
+    /// Saved as an `.xml` file in `%ProgramData%\Microsoft\Event Viewer\Views`, the view
+    /// appears under *Custom Views*; the file can also be opened with *Import Custom View*.
+    #[must_use]
+    pub fn custom_view(&self, name: &str, description: &str) -> String {
+        let log = escape_xml(&self.log);
+        format!(
+            "<ViewerConfig>\r\n  <QueryConfig>\r\n    <QueryParams>\r\n      <UserQuery />\r\n    </QueryParams>\r\n    <QueryNode>\r\n      <Name>{}</Name>\r\n      <Description>{}</Description>\r\n      <QueryList>\r\n        <Query Id=\"0\" Path=\"{log}\">\r\n          <Select Path=\"{log}\">{}</Select>\r\n        </Query>\r\n      </QueryList>\r\n    </QueryNode>\r\n  </QueryConfig>\r\n</ViewerConfig>\r\n",
+            escape_xml(name),
+            escape_xml(description),
+            escape_xml(&self.xpath()),
+        )
+    }
+
+    /// Runs the query.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Query`](crate::Error::Query) if the log cannot be queried, e.g.
+    /// because it does not exist, and with
+    /// [`Error::InvalidSourceName`](crate::Error::InvalidSourceName) if the source name
+    /// cannot be quoted in the filter.
+    #[cfg(windows)]
+    pub fn run(&self) -> Result<Vec<EventRecord>, crate::Error> {
+        self.check_source()?;
+        evt::query(
+            &self.log,
+            &self.xpath(),
+            self.newest_first,
+            self.max_results.unwrap_or(usize::MAX),
+        )
+    }
+
+    /// Runs the query and writes the events to `path` as CSV, see [`write_csv`].
+    ///
+    /// Returns the number of exported events.
+    ///
+    /// # Errors
+    /// Fails if the query or writing the file fails.
+    #[cfg(windows)]
+    pub fn export_csv(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<usize> {
+        let records = self.run().map_err(std::io::Error::other)?;
+        write_csv(
+            &records,
+            std::io::BufWriter::new(std::fs::File::create(path)?),
+        )?;
+        Ok(records.len())
+    }
+
+    /// Runs the query and writes the events to `path` as JSON Lines, see [`write_jsonl`].
+    ///
+    /// Returns the number of exported events.
+    ///
+    /// # Errors
+    /// Fails if the query or writing the file fails.
+    #[cfg(all(windows, feature = "serde"))]
+    pub fn export_jsonl(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<usize> {
+        let records = self.run().map_err(std::io::Error::other)?;
+        write_jsonl(
+            &records,
+            std::io::BufWriter::new(std::fs::File::create(path)?),
+        )?;
+        Ok(records.len())
+    }
+
+    #[cfg(windows)]
+    fn check_source(&self) -> Result<(), crate::Error> {
+        self.source
+            .as_deref()
+            .map_or(Ok(()), check_quotable)
+            .map_err(crate::Error::InvalidSourceName)
+    }
+
+    /// Watches for future events matching the query.
+    ///
+    /// Time ranges and the result limits do not apply to subscriptions.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Query`](crate::Error::Query) if `EvtSubscribe` fails and like
+    /// [`run`](Self::run) if the source name cannot be quoted.
+    #[cfg(all(windows, feature = "tokio"))]
+    pub fn tail(&self) -> Result<Tail, crate::Error> {
+        self.check_source()?;
+        let query = Self {
+            since: None,
+            until: None,
+            ..self.clone()
+        };
+        evt::subscribe(&query.log, &query.xpath())
+    }
+}
+
+/// Watches the `Application` log for future events of `source`.
+///
+/// ```no_run
+/// use futures_core::Stream;
+/// use std::pin::Pin;
+///
+/// # async fn watch() -> Result<(), tracing_layer_win_eventlog::Error> {
+/// let mut events = tracing_layer_win_eventlog::reader::tail("hello_world")?;
+/// while let Some(event) = std::future::poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await {
+///     println!("{}: {}", event.id, event.message);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// Fails with [`Error::Query`](crate::Error::Query) if `EvtSubscribe` fails.
+#[cfg(all(windows, feature = "tokio"))]
+pub fn tail(source: &str) -> Result<Tail, crate::Error> {
+    Query::new().source(source).tail()
+}
+
+/// A stream of events delivered by an `EvtSubscribe` subscription.
+///
+/// The subscription is closed on drop.
+#[cfg(all(windows, feature = "tokio"))]
+#[derive(Debug)]
+pub struct Tail {
+    subscription: Option<evt::Handle>,
+    context: *mut tokio::sync::mpsc::UnboundedSender<EventRecord>,
+    events: tokio::sync::mpsc::UnboundedReceiver<EventRecord>,
+}
+
+// The subscription handle may be closed from any thread, the context is only touched
+// by the subscription callback until then.
+#[cfg(all(windows, feature = "tokio"))]
+unsafe impl Send for Tail {}
+
+#[cfg(all(windows, feature = "tokio"))]
+impl futures_core::Stream for Tail {
+    type Item = EventRecord;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+#[cfg(all(windows, feature = "tokio"))]
+impl Drop for Tail {
+    fn drop(&mut self) {
+        // Closing waits for running callbacks, only then the context can be freed.
+        drop(self.subscription.take());
+        drop(unsafe { Box::from_raw(self.context) });
+    }
+}
+
+const fn system_level(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 2,
+        Level::WARN => 3,
+        Level::INFO | Level::DEBUG | Level::TRACE => 4,
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{value}\"")
+    } else {
+        format!("'{value}'")
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Parses an event rendered as XML by `EvtRender` or `wevtutil qe /f:xml`.
+///
+/// The description is taken from the first `Data` element, the fields from the data blob
+/// in `Binary`, if the event carries one.
+#[must_use]
+pub fn parse_event_xml(xml: &str) -> Option<EventRecord> {
+    let source = attribute(xml, "Provider", "Name")?;
+    let id = element_text(xml, "EventID")?.trim().parse().ok()?;
+    let level = match element_text(xml, "Level").map(str::trim) {
+        Some("1" | "2") => Level::ERROR,
+        Some("3") => Level::WARN,
+        _ => Level::INFO,
+    };
+    let message = element_text(xml, "Data").map(unescape).unwrap_or_default();
+
+    let mut record = EventRecord::new(source, id, level, message);
+    if let Some(timestamp) = attribute(xml, "TimeCreated", "SystemTime")
+        .as_deref()
+        .and_then(parse_system_time)
+    {
+        record.timestamp = timestamp;
+    }
+
+    if let Some(data) = element_text(xml, "Binary").and_then(|hex| decode_hex(hex.trim())) {
+        #[cfg(feature = "serde")]
+        if let Some(blob) = decode_record(&data, &[]) {
+            record.level = blob.level;
+            record.fields = blob.fields;
+            return Some(record);
+        }
+        if let Some(fields) = decode_data(&data, &[]) {
+            record.fields = fields.into_iter().collect();
+        }
+    }
+    Some(record)
+}
+
+/// Returns the value of `name` on the first `element` tag.
+fn attribute(xml: &str, element: &str, name: &str) -> Option<String> {
+    let start = find_tag(xml, element)?;
+    let tag = &xml[start..start + xml[start..].find('>')?];
+    let value = &tag[tag.find(&format!("{name}="))? + name.len() + 1..];
+    let quote = value.chars().next()?;
+    let value = &value[1..];
+    Some(unescape(&value[..value.find(quote)?]))
+}
+
+/// Returns the raw text of the first `element`, empty for `<element/>`.
+fn element_text<'a>(xml: &'a str, element: &str) -> Option<&'a str> {
+    let start = find_tag(xml, element)?;
+    let open_end = start + xml[start..].find('>')?;
+    if xml[..open_end].ends_with('/') {
+        return Some("");
+    }
+    let rest = &xml[open_end + 1..];
+    Some(&rest[..rest.find(&format!("</{element}>"))?])
+}
+
+fn find_tag(xml: &str, element: &str) -> Option<usize> {
+    let open = format!("<{element}");
+    let mut offset = 0;
+    while let Some(pos) = xml[offset..].find(&open) {
+        let start = offset + pos;
+        match xml[start + open.len()..].chars().next() {
+            Some('>' | '/' | ' ' | '\t' | '\r' | '\n') => return Some(start),
+            _ => offset = start + open.len(),
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(windows)]
+mod evt {
+    use winapi::shared::winerror::{ERROR_INSUFFICIENT_BUFFER, ERROR_NO_MORE_ITEMS};
+    use winapi::um::winbase::INFINITE;
+    use winapi::um::winevt::{
+        EvtClose, EvtNext, EvtQuery, EvtQueryChannelPath, EvtQueryForwardDirection,
+        EvtQueryReverseDirection, EvtRender, EvtRenderEventXml, EVT_HANDLE,
+    };
+
+    use super::parse_event_xml;
+    #[cfg(feature = "tokio")]
+    use super::Tail;
+    use crate::error::Error;
+    use crate::record::EventRecord;
+    use crate::wide::to_wide;
+
+    const BATCH: usize = 16;
+
+    /// Closes the wrapped handle on drop.
+    #[derive(Debug)]
+    pub struct Handle(pub EVT_HANDLE);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe {
+                EvtClose(self.0);
+            }
+        }
+    }
+
+    fn last_error() -> Error {
+        Error::Query {
+            code: Error::last_os_code(),
+        }
+    }
+
+    pub fn query(
+        log: &str,
+        xpath: &str,
+        newest_first: bool,
+        max_results: usize,
+    ) -> Result<Vec<EventRecord>, Error> {
+        let log = to_wide(log);
+        let xpath = to_wide(xpath);
+        let direction = if newest_first {
+            EvtQueryReverseDirection
+        } else {
+            EvtQueryForwardDirection
+        };
+
+        let results = unsafe {
+            EvtQuery(
+                std::ptr::null_mut(),
+                log.as_ptr(),
+                xpath.as_ptr(),
+                EvtQueryChannelPath | direction,
+            )
+        };
+        if results.is_null() {
+            return Err(last_error());
+        }
+        let results = Handle(results);
+
+        let mut records = Vec::new();
+        while records.len() < max_results {
+            let mut events = [std::ptr::null_mut(); BATCH];
+            let mut returned = 0;
+            let ok = unsafe {
+                EvtNext(
+                    results.0,
+                    BATCH as u32,
+                    events.as_mut_ptr(),
+                    INFINITE,
+                    0,
+                    &mut returned,
+                )
+            };
+            if ok == 0 {
+                if Error::last_os_code() == ERROR_NO_MORE_ITEMS {
+                    break;
+                }
+                return Err(last_error());
+            }
+
+            let events: Vec<_> = events[..returned as usize]
+                .iter()
+                .map(|&event| Handle(event))
+                .collect();
+            for event in events {
+                if records.len() == max_results {
+                    break;
+                }
+                if let Some(record) = parse_event_xml(&render_xml(event.0)?) {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn render_xml(event: EVT_HANDLE) -> Result<String, Error> {
+        let mut used = 0;
+        let mut count = 0;
+        let mut buffer: Vec<u16> = Vec::new();
+
+        for _ in 0..2 {
+            let ok = unsafe {
+                EvtRender(
+                    std::ptr::null_mut(),
+                    event,
+                    EvtRenderEventXml,
+                    u32::try_from(buffer.len() * 2).unwrap_or(u32::MAX),
+                    buffer.as_mut_ptr().cast(),
+                    &mut used,
+                    &mut count,
+                )
+            };
+            if ok != 0 {
+                let len = (used as usize / 2).min(buffer.len());
+                let xml = String::from_utf16_lossy(&buffer[..len]);
+                return Ok(xml.trim_end_matches('\0').to_owned());
+            }
+            if Error::last_os_code() != ERROR_INSUFFICIENT_BUFFER {
+                return Err(last_error());
+            }
+            buffer = vec![0; used as usize / 2 + 1];
+        }
+        Err(last_error())
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn subscribe(log: &str, xpath: &str) -> Result<Tail, Error> {
+        use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+        use winapi::shared::minwindef::DWORD;
+        use winapi::um::winevt::{
+            EvtSubscribe, EvtSubscribeActionDeliver, EvtSubscribeToFutureEvents,
+            EVT_SUBSCRIBE_NOTIFY_ACTION,
+        };
+        use winapi::um::winnt::PVOID;
+
+        unsafe extern "system" fn deliver(
+            action: EVT_SUBSCRIBE_NOTIFY_ACTION,
+            context: PVOID,
+            event: EVT_HANDLE,
+        ) -> DWORD {
+            if action == EvtSubscribeActionDeliver {
+                let sender = unsafe { &*context.cast::<UnboundedSender<EventRecord>>() };
+                if let Some(record) = render_xml(event).ok().and_then(|xml| parse_event_xml(&xml)) {
+                    let _ = sender.send(record);
+                }
+            }
+            0
+        }
+
+        let log = to_wide(log);
+        let xpath = to_wide(xpath);
+        let (sender, events) = unbounded_channel();
+        let context = Box::into_raw(Box::new(sender));
+
+        let subscription = unsafe {
+            EvtSubscribe(
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                log.as_ptr(),
+                xpath.as_ptr(),
+                std::ptr::null_mut(),
+                context.cast(),
+                Some(deliver),
+                EvtSubscribeToFutureEvents,
+            )
+        };
+        if subscription.is_null() {
+            let error = last_error();
+            drop(unsafe { Box::from_raw(context) });
+            return Err(error);
+        }
+
+        Ok(Tail {
+            subscription: Some(Handle(subscription)),
+            context,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::binary::encode_hex;
+    use crate::blob::DataBlob;
+
+    #[test]
+    fn quotes_with_the_quote_the_value_does_not_contain() {
+        assert_eq!(quote("hello_world"), "'hello_world'");
+        assert_eq!(quote("O'Brien"), "\"O'Brien\"");
+    }
+
+    #[test]
+    fn an_empty_query_matches_everything() {
+        assert_eq!(Query::new().xpath(), "*");
+    }
+
+    #[test]
+    fn xpath_joins_all_conditions() {
+        let query = Query::new()
+            .source("hello_world")
+            .ids(1000..=1005)
+            .ids(4..=4)
+            .level(Level::ERROR)
+            .level(Level::INFO)
+            .level(Level::DEBUG)
+            .since(UNIX_EPOCH + Duration::from_secs(1_714_564_800))
+            .until(UNIX_EPOCH + Duration::from_secs(1_714_568_400));
+        assert_eq!(
+            query.xpath(),
+            "*[System[Provider[@Name='hello_world'] \
+             and ((EventID >= 1000 and EventID <= 1005) or (EventID >= 4 and EventID <= 4)) \
+             and (Level=2 or Level=4) \
+             and TimeCreated[@SystemTime>='2024-05-01T12:00:00.000Z'] \
+             and TimeCreated[@SystemTime<='2024-05-01T13:00:00.000Z']]]"
+        );
+    }
+
+    fn event_xml(data: &str, binary: &str) -> String {
+        format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+             <Provider Name='hello &amp; world'/><EventID Qualifiers='0'>42</EventID>\
+             <Level>3</Level><TimeCreated SystemTime='2024-05-01T12:00:01.1234567Z'/>\
+             </System><EventData>{data}{binary}</EventData></Event>"
+        )
+    }
+
+    #[test]
+    fn parses_rendered_events() {
+        let xml = event_xml("<Data>ID: 42&#xA;&#xA;message: free &lt; 10%</Data>", "");
+        let record = parse_event_xml(&xml).unwrap();
+        assert_eq!(record.source, "hello & world");
+        assert_eq!(record.id, 42);
+        assert_eq!(record.level, Level::WARN);
+        assert_eq!(record.message, "ID: 42\n\nmessage: free < 10%");
+        assert_eq!(
+            record.timestamp,
+            UNIX_EPOCH + Duration::new(1_714_564_801, 123_456_700)
+        );
+        assert!(record.fields.is_empty());
+
+        let record = parse_event_xml(&event_xml("<Data/>", "")).unwrap();
+        assert_eq!(record.message, "");
+        assert_eq!(parse_event_xml("<Event><System/></Event>"), None);
+    }
+
+    #[test]
+    fn reads_fields_from_the_data_blob() {
+        let mut written = EventRecord::new("hello & world", 42, Level::WARN, "free < 10%");
+        written.fields.insert("free".to_owned(), FieldValue::U64(9));
+        let binary = format!(
+            "<Binary>{}</Binary>",
+            encode_hex(&DataBlob::new().encode(&written))
+        );
+        let record = parse_event_xml(&event_xml("<Data>free &lt; 10%</Data>", &binary)).unwrap();
+        assert_eq!(record.fields, written.fields);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+use tracing::Level;
+
+use crate::event_type::EventType;
+
+/// Version of the serialized [`EventRecord`] schema, written as its `schema` member.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// A field value with the type it was recorded with.
+///
+/// Values recorded through `Debug` and 128-bit integers are kept as their `Debug`
+/// representation in `Str`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum FieldValue {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U64(v) => write!(f, "{v}"),
+            Self::I64(v) => write!(f, "{v}"),
+            Self::F64(v) => write!(f, "{v}"),
+            Self::Bool(v) => write!(f, "{v}"),
+            Self::Str(v) => f.write_str(v),
+        }
+    }
+}
+
+/// An event as handed to the event log.
+///
+/// With the `serde` feature, records serialize to schema version [`SCHEMA_VERSION`]:
+///
+/// ```json
+/// {
+///   "schema": 2,
+///   "source": "hello_world",
+///   "id": 42,
+///   "level": "WARN",
+///   "message": "disk almost full",
+///   "timestamp_ms": 1714564801000,
+///   "fields": { "free": { "u64": 1024 }, "volume": { "str": "C:" } }
+/// }
+/// ```
+///
+/// `level` is one of `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR`, `timestamp_ms` counts
+/// milliseconds since the Unix epoch. Every field value is an object with a single member
+/// naming its type: `u64`, `i64`, `f64`, `bool` or `str`. Records of schema version 1,
+/// which held all field values as strings, are still read; other versions are rejected.
+/// The event type, category, insertion strings and raw data are not serialized.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "schema::Record", try_from = "schema::Record")
+)]
+pub struct EventRecord {
+    pub source: String,
+    pub id: u32,
+    pub level: Level,
+    /// The description of the event; in data blobs the message field only.
+    pub message: String,
+    pub timestamp: SystemTime,
+    /// Fields of the event, empty for failed writes.
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Event type of a failed write; `None` follows the level.
+    pub event_type: Option<EventType>,
+    /// Category of a failed write.
+    pub category: u16,
+    /// Insertion strings of a failed write after the description (`%2` onwards).
+    pub inserts: Vec<String>,
+    /// Raw data of a failed write.
+    pub data: Vec<u8>,
+}
+
+impl EventRecord {
+    #[must_use]
+    pub fn new(
+        source: impl Into<String>,
+        id: u32,
+        level: Level,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            id,
+            level,
+            message: message.into(),
+            timestamp: SystemTime::now(),
+            fields: BTreeMap::new(),
+            event_type: None,
+            category: 0,
+            inserts: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod schema {
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{EventRecord, FieldValue, SCHEMA_VERSION};
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[serde(untagged)]
+    enum Fields {
+        Typed(BTreeMap<String, FieldValue>),
+        /// Schema version 1.
+        Strings(BTreeMap<String, String>),
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct Record {
+        schema: u32,
+        source: String,
+        id: u32,
+        level: String,
+        message: String,
+        timestamp_ms: u64,
+        #[serde(default = "no_fields")]
+        fields: Fields,
+    }
+
+    const fn no_fields() -> Fields {
+        Fields::Typed(BTreeMap::new())
+    }
+
+    impl From<EventRecord> for Record {
+        fn from(record: EventRecord) -> Self {
+            let timestamp_ms = record
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+                .unwrap_or_default();
+            Self {
+                schema: SCHEMA_VERSION,
+                source: record.source,
+                id: record.id,
+                level: record.level.as_str().to_owned(),
+                message: record.message,
+                timestamp_ms,
+                fields: Fields::Typed(record.fields),
+            }
+        }
+    }
+
+    impl TryFrom<Record> for EventRecord {
+        type Error = String;
+
+        fn try_from(record: Record) -> Result<Self, Self::Error> {
+            if !(1..=SCHEMA_VERSION).contains(&record.schema) {
+                return Err(format!("unsupported schema version {}", record.schema));
+            }
+            Ok(Self {
+                source: record.source,
+                id: record.id,
+                level: record
+                    .level
+                    .parse()
+                    .map_err(|_| format!("invalid level {}", record.level))?,
+                message: record.message,
+                timestamp: UNIX_EPOCH + Duration::from_millis(record.timestamp_ms),
+                fields: match record.fields {
+                    Fields::Typed(fields) => fields,
+                    Fields::Strings(fields) => fields
+                        .into_iter()
+                        .map(|(name, value)| (name, FieldValue::Str(value)))
+                        .collect(),
+                },
+                event_type: None,
+                category: 0,
+                inserts: Vec::new(),
+                data: Vec::new(),
+            })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    fn record() -> EventRecord {
+        let mut record = EventRecord::new("hello_world", 42, Level::WARN, "disk almost full");
+        record.timestamp = UNIX_EPOCH + Duration::from_millis(1_714_564_801_000);
+        record.fields = BTreeMap::from([
+            ("free".to_owned(), FieldValue::U64(1024)),
+            ("delta".to_owned(), FieldValue::I64(-3)),
+            ("ratio".to_owned(), FieldValue::F64(0.5)),
+            ("critical".to_owned(), FieldValue::Bool(false)),
+            ("volume".to_owned(), FieldValue::Str("C:".to_owned())),
+        ]);
+        record
+    }
+
+    #[test]
+    fn round_trips_through_the_current_schema() {
+        let json = serde_json::to_value(record()).unwrap();
+        assert_eq!(json["schema"], SCHEMA_VERSION);
+        assert_eq!(json["fields"]["free"], serde_json::json!({ "u64": 1024 }));
+        assert_eq!(
+            serde_json::from_value::<EventRecord>(json).unwrap(),
+            record()
+        );
+    }
+
+    #[test]
+    fn reads_schema_1_with_string_values() {
+        let json = r#"{
+            "schema": 1,
+            "source": "hello_world",
+            "id": 42,
+            "level": "WARN",
+            "message": "disk almost full",
+            "timestamp_ms": 1714564801000,
+            "fields": { "free": "1024", "volume": "C:" }
+        }"#;
+        let read: EventRecord = serde_json::from_str(json).unwrap();
+        assert_eq!(read.fields["free"], FieldValue::Str("1024".to_owned()));
+        assert_eq!(read.fields["volume"], FieldValue::Str("C:".to_owned()));
+        assert_eq!(read.timestamp, record().timestamp);
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_levels() {
+        let mut json = serde_json::to_value(record()).unwrap();
+        json["schema"] = (SCHEMA_VERSION + 1).into();
+        assert!(serde_json::from_value::<EventRecord>(json.clone()).is_err());
+        json["schema"] = SCHEMA_VERSION.into();
+        json["level"] = "FATAL".into();
+        assert!(serde_json::from_value::<EventRecord>(json).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use winapi::shared::minwindef::HKEY;
+use winapi::um::winnt::{
+    KEY_READ, KEY_WRITE, REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE, REG_SZ,
+};
+use winapi::um::winreg::{
+    RegCloseKey, RegCreateKeyExW, RegEnumKeyExW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW,
+    HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE,
+};
+
+use crate::wide::to_wide;
+
+/// Root of a registry key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hive {
+    LocalMachine,
+    CurrentUser,
+}
+
+/// An opened registry key, closed on drop.
+pub struct Key(HKEY);
+
+impl Hive {
+    const fn root(self) -> HKEY {
+        match self {
+            Self::LocalMachine => HKEY_LOCAL_MACHINE,
+            Self::CurrentUser => HKEY_CURRENT_USER,
+        }
+    }
+}
+
+impl Key {
+    /// Opens `path` below `hive`, creating it if missing.
+    pub fn create(hive: Hive, path: &str) -> Option<Self> {
+        Self::try_create(hive, path).ok()
+    }
+
+    /// Like [`Key::create`], returning the Win32 error code on failure.
+    pub fn try_create(hive: Hive, path: &str) -> Result<Self, u32> {
+        let root = hive.root();
+        let path = to_wide(path);
+        let mut key = std::ptr::null_mut();
+
+        let status = unsafe {
+            RegCreateKeyExW(
+                root,
+                path.as_ptr(),
+                0,
+                std::ptr::null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                KEY_READ | KEY_WRITE,
+                std::ptr::null_mut(),
+                &mut key,
+                std::ptr::null_mut(),
+            )
+        };
+
+        match status {
+            0 => Ok(Self(key)),
+            code => Err(u32::try_from(code).unwrap_or_default()),
+        }
+    }
+
+    /// Opens an existing key for reading.
+    pub fn open(hive: Hive, path: &str) -> Option<Self> {
+        let path = to_wide(path);
+        let mut key = std::ptr::null_mut();
+        let status = unsafe { RegOpenKeyExW(hive.root(), path.as_ptr(), 0, KEY_READ, &mut key) };
+        (status == 0).then_some(Self(key))
+    }
+
+    /// Names of the direct subkeys.
+    pub fn subkeys(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        // Key names are limited to 255 characters.
+        let mut buffer = [0u16; 256];
+        for index in 0.. {
+            let mut len = buffer.len() as u32;
+            let status = unsafe {
+                RegEnumKeyExW(
+                    self.0,
+                    index,
+                    buffer.as_mut_ptr(),
+                    &mut len,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if status != 0 {
+                break;
+            }
+            names.push(String::from_utf16_lossy(&buffer[..len as usize]));
+        }
+        names
+    }
+
+    pub fn read_string(&self, name: &str) -> Option<String> {
+        let name = to_wide(name);
+        let mut len = 0;
+        let mut kind = 0;
+
+        let status = unsafe {
+            RegQueryValueExW(
+                self.0,
+                name.as_ptr(),
+                std::ptr::null_mut(),
+                &mut kind,
+                std::ptr::null_mut(),
+                &mut len,
+            )
+        };
+        if status != 0 || (kind != REG_SZ && kind != REG_EXPAND_SZ) {
+            return None;
+        }
+
+        let mut buffer = vec![0u16; len as usize / 2 + 1];
+        let status = unsafe {
+            RegQueryValueExW(
+                self.0,
+                name.as_ptr(),
+                std::ptr::null_mut(),
+                &mut kind,
+                buffer.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if status != 0 {
+            return None;
+        }
+
+        let value = &buffer[..(len as usize / 2).min(buffer.len())];
+        let value = value.strip_suffix(&[0]).unwrap_or(value);
+        Some(String::from_utf16_lossy(value))
+    }
+
+    pub fn write_string(&self, name: &str, value: &str) -> bool {
+        self.try_write_string(name, value, false).is_ok()
+    }
+
+    /// Writes a `REG_SZ` value, or a `REG_EXPAND_SZ` value if `expand` is set.
+    pub fn try_write_string(&self, name: &str, value: &str, expand: bool) -> Result<(), u32> {
+        let value = to_wide(value);
+        self.set(
+            name,
+            if expand { REG_EXPAND_SZ } else { REG_SZ },
+            value.as_ptr().cast(),
+            value.len() * 2,
+        )
+    }
+
+    pub fn try_write_dword(&self, name: &str, value: u32) -> Result<(), u32> {
+        self.set(name, REG_DWORD, std::ptr::from_ref(&value).cast(), 4)
+    }
+
+    fn set(&self, name: &str, kind: u32, data: *const u8, len: usize) -> Result<(), u32> {
+        let name = to_wide(name);
+        let status = unsafe {
+            RegSetValueExW(
+                self.0,
+                name.as_ptr(),
+                0,
+                kind,
+                data,
+                u32::try_from(len).unwrap_or(u32::MAX),
+            )
+        };
+        match status {
+            0 => Ok(()),
+            code => Err(u32::try_from(code).unwrap_or_default()),
+        }
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.0);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span;
+
+/// Span field marking a span for an "operation failed" rollup on close.
+pub const ROLLUP_FIELD: &str = "eventlog.rollup";
+
+#[derive(Debug)]
+pub struct Rollup {
+    pub opened: Instant,
+    pub errors: u64,
+    pub first_error: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl Rollup {
+    pub fn from_attributes(attrs: &span::Attributes<'_>) -> Option<Self> {
+        attrs.metadata().fields().field(ROLLUP_FIELD)?;
+
+        let mut marker = Marker(false);
+        attrs.record(&mut marker);
+
+        marker.0.then(|| Self {
+            opened: Instant::now(),
+            errors: 0,
+            first_error: None,
+            last_error: None,
+        })
+    }
+
+    pub fn record_error(&mut self, message: Option<&str>) {
+        self.errors += 1;
+        let message = message.unwrap_or_default().to_owned();
+        if self.first_error.is_none() {
+            self.first_error = Some(message.clone());
+        }
+        self.last_error = Some(message);
+    }
+}
+
+struct Marker(bool);
+
+impl Visit for Marker {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == ROLLUP_FIELD {
+            self.0 = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
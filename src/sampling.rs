@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::callsite::Identifier;
+use tracing::{Level, Metadata};
+
+use crate::log_fields::LogMetadata;
+use crate::wide::to_wide;
+
+/// Exponential sampling of repeated events from the same callsite.
+///
+/// Of the events at `level`, occurrences 1, 2, 4, 8, 16, … of each callsite are written.
+/// Once a callsite stayed quiet for `reset_after`, counting starts over and a summary with
+/// the total number of occurrences is written, though not before the next event at `level`
+/// from any callsite, or the layer or its guard being dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialSampling {
+    level: Level,
+    reset_after: Duration,
+}
+
+impl ExponentialSampling {
+    #[must_use]
+    pub const fn new(level: Level, reset_after: Duration) -> Self {
+        Self { level, reset_after }
+    }
+}
+
+impl Default for ExponentialSampling {
+    fn default() -> Self {
+        Self::new(Level::WARN, Duration::from_secs(300))
+    }
+}
+
+#[derive(Debug)]
+struct Callsite {
+    name: String,
+    id: u32,
+    count: u64,
+    last_seen: Instant,
+}
+
+impl Callsite {
+    fn summary(&self, level: Level) -> Option<(u32, Level, Vec<u16>)> {
+        let written = u64::from(self.count.ilog2()) + 1;
+        if self.count == written {
+            return None;
+        }
+
+        Some((
+            self.id,
+            level,
+            to_wide(&format!(
+                "ID: {}\n\nmessage: sampling summary\ncallsite: {}\noccurrences: {}\nnot_written: {}\n",
+                self.id,
+                self.name,
+                self.count,
+                self.count - written
+            )),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct Sampler {
+    config: ExponentialSampling,
+    callsites: Mutex<HashMap<(Identifier, Option<String>), Callsite>>,
+}
+
+impl Sampler {
+    pub fn new(config: ExponentialSampling) -> Self {
+        Self {
+            config,
+            callsites: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub const fn config(&self) -> &ExponentialSampling {
+        &self.config
+    }
+
+    /// Returns whether the event is written and the summaries that are due.
+    ///
+    /// Events from `tracing-log` share one callsite per level, so they are told apart by
+    /// their original target and location.
+    pub fn admit(
+        &self,
+        metadata: &'static Metadata<'static>,
+        log: &LogMetadata,
+        id: u32,
+    ) -> (bool, Vec<(u32, Level, Vec<u16>)>) {
+        if *metadata.level() != self.config.level {
+            return (true, Vec::new());
+        }
+
+        let Ok(mut callsites) = self.callsites.lock() else {
+            return (true, Vec::new());
+        };
+
+        let now = Instant::now();
+        let mut summaries = Vec::new();
+        callsites.retain(|_, callsite| {
+            if now.duration_since(callsite.last_seen) < self.config.reset_after {
+                return true;
+            }
+            summaries.extend(callsite.summary(self.config.level));
+            false
+        });
+
+        let origin = (!log.is_empty())
+            .then(|| format!("{} ({})", log.target(metadata), log.location(metadata)));
+        let callsite = callsites
+            .entry((metadata.callsite(), origin.clone()))
+            .or_insert_with(|| Callsite {
+                name: origin.unwrap_or_else(|| {
+                    format!(
+                        "{}::{} ({})",
+                        metadata.target(),
+                        metadata.name(),
+                        log.location(metadata)
+                    )
+                }),
+                id,
+                count: 0,
+                last_seen: now,
+            });
+        callsite.count += 1;
+        callsite.id = id;
+        callsite.last_seen = now;
+
+        (callsite.count.is_power_of_two(), summaries)
+    }
+
+    pub fn drain(&self) -> Vec<(u32, Level, Vec<u16>)> {
+        let Ok(mut callsites) = self.callsites.lock() else {
+            return Vec::new();
+        };
+        callsites
+            .drain()
+            .filter_map(|(_, c)| c.summary(self.config.level))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_counts_the_occurrences_not_written() {
+        let cases = [
+            (1, None),
+            (2, None),
+            (3, Some(1)),
+            (4, Some(1)),
+            (5, Some(2)),
+            (8, Some(4)),
+        ];
+        for (count, not_written) in cases {
+            let callsite = Callsite {
+                name: "app::event (src/main.rs:1)".to_owned(),
+                id: 9,
+                count,
+                last_seen: Instant::now(),
+            };
+            let summary = callsite.summary(Level::WARN).map(|(_, _, message)| {
+                String::from_utf16_lossy(message.strip_suffix(&[0]).unwrap_or(&message))
+            });
+            let expected = not_written.map(|n| format!("occurrences: {count}\nnot_written: {n}\n"));
+            assert_eq!(summary.is_some(), expected.is_some(), "{count} occurrences");
+            if let (Some(summary), Some(expected)) = (summary, expected) {
+                assert!(summary.ends_with(&expected), "{summary}");
+            }
+        }
+    }
+}
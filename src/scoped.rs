@@ -0,0 +1,74 @@
+use std::fmt::{Display, Write as _};
+use std::time::Instant;
+use tracing::Level;
+
+use crate::handle::EventLogHandle;
+use crate::time::format_duration;
+use crate::wide::to_wide;
+
+/// Writes a completion event when dropped, for instrumenting a critical section without a
+/// span.
+///
+/// The event has the ID, message and fields given at construction plus `elapsed` and
+/// `panicked`. It is an INFO event, or an ERROR event if the guard is dropped while the
+/// thread is unwinding from a panic.
+///
+/// ```ignore
+/// let _scope = handle.scoped(7001, "nightly import").field("batch", batch_id);
+/// import(batch_id)?;
+/// ```
+#[derive(Debug)]
+#[must_use = "the event is written when the guard is dropped"]
+pub struct ScopedEvent {
+    handle: EventLogHandle,
+    id: u32,
+    message: String,
+    fields: Vec<(String, String)>,
+    started: Instant,
+}
+
+impl ScopedEvent {
+    /// Starts timing the scope now.
+    pub fn new(handle: &EventLogHandle, id: u32, message: impl Into<String>) -> Self {
+        Self {
+            handle: handle.clone(),
+            id,
+            message: message.into(),
+            fields: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Adds a field to the completion event.
+    pub fn field(mut self, name: impl Into<String>, value: impl Display) -> Self {
+        self.record(name, value);
+        self
+    }
+
+    /// Adds a field to the completion event from within the scope, e.g. a result count.
+    pub fn record(&mut self, name: impl Into<String>, value: impl Display) {
+        self.fields.push((name.into(), value.to_string()));
+    }
+
+    fn describe(&self, panicked: bool) -> String {
+        let mut msg = format!("ID: {}\n\nmessage: {}\n", self.id, self.message);
+        for (name, value) in &self.fields {
+            let _ = writeln!(msg, "{name}: {value}");
+        }
+        let _ = write!(
+            msg,
+            "elapsed: {}\npanicked: {panicked}",
+            format_duration(self.started.elapsed())
+        );
+        msg
+    }
+}
+
+impl Drop for ScopedEvent {
+    fn drop(&mut self) {
+        let panicked = std::thread::panicking();
+        let level = if panicked { Level::ERROR } else { Level::INFO };
+        self.handle
+            .report(self.id, level, &to_wide(&self.describe(panicked)));
+    }
+}
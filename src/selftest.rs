@@ -0,0 +1,150 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::event_type::EventType;
+use crate::reader::Query;
+use crate::source::EventSource;
+use crate::wide::{to_wide, WideMessage};
+
+/// Event IDs written by [`self_test`], one per event type.
+pub const SELF_TEST_IDS: RangeInclusive<u32> = 9990..=9995;
+
+const EVENT_TYPES: [EventType; 6] = [
+    EventType::Error,
+    EventType::Warning,
+    EventType::Information,
+    EventType::Success,
+    EventType::AuditSuccess,
+    EventType::AuditFailure,
+];
+
+/// How long [`self_test`] waits for the written events to show up in the log.
+const READ_BACK_ATTEMPTS: u32 = 10;
+const READ_BACK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The outcome of [`self_test`].
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    pub source: String,
+    /// One check per event type.
+    pub checks: Vec<SelfTestCheck>,
+    /// Why the events could not be read back, if querying the log failed.
+    pub query_error: Option<Error>,
+}
+
+/// One test event of a [`SelfTestReport`].
+#[derive(Debug, Clone)]
+pub struct SelfTestCheck {
+    pub event_type: EventType,
+    pub id: u32,
+    /// Why the event could not be written, if it was not.
+    pub write_error: Option<Error>,
+    /// Whether the event was read back from the log.
+    pub found: bool,
+}
+
+impl SelfTestCheck {
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.write_error.is_none() && self.found
+    }
+}
+
+impl SelfTestReport {
+    /// Whether every test event was written and read back.
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.query_error.is_none() && self.checks.iter().all(SelfTestCheck::passed)
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Self-test of event source {}", self.source)?;
+        for check in &self.checks {
+            write!(f, "{} (ID {}): ", check.event_type, check.id)?;
+            match &check.write_error {
+                Some(error) => writeln!(f, "{error}")?,
+                None if check.found => writeln!(f, "ok")?,
+                None => writeln!(f, "written, but not found in the log")?,
+            }
+        }
+        if let Some(error) = &self.query_error {
+            writeln!(f, "reading back: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies that events of `source` can be written to and read back from the
+/// `Application` log.
+///
+/// Writes one event per event type with the IDs in [`SELF_TEST_IDS`], then queries them
+/// back for up to two seconds.
+///
+/// # Errors
+/// Fails if the source cannot be registered; everything after that is part of the report.
+pub fn self_test(source: &str) -> Result<SelfTestReport, Error> {
+    let event_source = EventSource::try_register(source)?;
+    let started = SystemTime::now();
+    let run = format!(
+        "{}-{}",
+        std::process::id(),
+        started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    );
+
+    let mut checks: Vec<SelfTestCheck> = SELF_TEST_IDS
+        .zip(EVENT_TYPES)
+        .map(|(id, event_type)| {
+            let message = WideMessage::from_utf16(to_wide(&format!(
+                "Self-test {event_type} event (run {run})"
+            )));
+            SelfTestCheck {
+                event_type,
+                id,
+                write_error: event_source
+                    .try_report(id, event_type, 0, None, &[&message], &[])
+                    .err(),
+                found: false,
+            }
+        })
+        .collect();
+
+    let query = Query::new()
+        .source(source)
+        .ids(SELF_TEST_IDS)
+        .since(started - Duration::from_secs(1));
+    let mut query_error = None;
+    for attempt in 1..=READ_BACK_ATTEMPTS {
+        match query.run() {
+            Ok(records) => {
+                query_error = None;
+                for check in &mut checks {
+                    check.found = records
+                        .iter()
+                        .any(|r| r.id == check.id && r.message.contains(&run));
+                }
+            }
+            Err(error) => query_error = Some(error),
+        }
+        let done = checks
+            .iter()
+            .all(|check| check.found || check.write_error.is_some());
+        if done || attempt == READ_BACK_ATTEMPTS {
+            break;
+        }
+        thread::sleep(READ_BACK_INTERVAL);
+    }
+
+    Ok(SelfTestReport {
+        source: source.to_owned(),
+        checks,
+        query_error,
+    })
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use winapi::shared::sddl::ConvertStringSidToSidW;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+use winapi::um::securitybaseapi::{GetLengthSid, GetTokenInformation};
+use winapi::um::winbase::{LocalFree, LookupAccountSidW};
+use winapi::um::winnt::{TokenUser, HANDLE, PSID, SID_NAME_USE, TOKEN_QUERY, TOKEN_USER};
+
+use crate::wide::to_wide;
+
+pub fn is_sid_field(name: &str) -> bool {
+    name.eq_ignore_ascii_case("sid") || name.to_lowercase().ends_with("_sid")
+}
+
+/// Resolves string SIDs to their `DOMAIN\user` form, caching every lookup.
+#[derive(Debug, Default)]
+pub struct SidResolver {
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl SidResolver {
+    pub fn resolve(&self, sid: &str) -> Option<String> {
+        if !sid.starts_with("S-") {
+            return None;
+        }
+
+        let Ok(mut cache) = self.cache.lock() else {
+            return lookup_account_sid(sid);
+        };
+
+        cache
+            .entry(sid.to_owned())
+            .or_insert_with(|| lookup_account_sid(sid))
+            .clone()
+    }
+}
+
+fn lookup_account_sid(sid: &str) -> Option<String> {
+    let wide = to_wide(sid);
+    let mut psid: PSID = std::ptr::null_mut();
+
+    if unsafe { ConvertStringSidToSidW(wide.as_ptr(), &mut psid) } == 0 {
+        return None;
+    }
+
+    let mut name = vec![0u16; 256];
+    let mut domain = vec![0u16; 256];
+    let mut account = None;
+
+    for _ in 0..2 {
+        let mut name_len = u32::try_from(name.len()).unwrap_or(u32::MAX);
+        let mut domain_len = u32::try_from(domain.len()).unwrap_or(u32::MAX);
+        let mut sid_use: SID_NAME_USE = 0;
+
+        let result = unsafe {
+            LookupAccountSidW(
+                std::ptr::null(),
+                psid,
+                name.as_mut_ptr(),
+                &mut name_len,
+                domain.as_mut_ptr(),
+                &mut domain_len,
+                &mut sid_use,
+            )
+        };
+
+        if result != 0 {
+            let name = String::from_utf16_lossy(&name[..name_len as usize]);
+            let domain = String::from_utf16_lossy(&domain[..domain_len as usize]);
+            account = Some(if domain.is_empty() {
+                name
+            } else {
+                format!("{domain}\\{name}")
+            });
+            break;
+        }
+
+        if name_len as usize <= name.len() && domain_len as usize <= domain.len() {
+            break;
+        }
+        name.resize(name_len as usize, 0);
+        domain.resize(domain_len as usize, 0);
+    }
+
+    unsafe {
+        LocalFree(psid);
+    }
+
+    account
+}
+
+/// The SID of the account the process runs as, passed as `lpUserSid` to `ReportEventW`.
+#[derive(Debug)]
+pub struct UserSid(Vec<u8>);
+
+impl UserSid {
+    /// Looks up the user of the process token, `None` if it cannot be queried.
+    pub fn current() -> Option<Self> {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return None;
+        }
+
+        let mut len = 0;
+        unsafe {
+            GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len);
+        }
+        // `u64` elements keep the buffer aligned for `TOKEN_USER`.
+        let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+        let result = unsafe {
+            GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len)
+        };
+        unsafe {
+            CloseHandle(token);
+        }
+        if result == 0 {
+            return None;
+        }
+
+        let sid = unsafe { (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid };
+        let sid_len = unsafe { GetLengthSid(sid) } as usize;
+        let bytes = unsafe { std::slice::from_raw_parts(sid.cast::<u8>(), sid_len) };
+        Some(Self(bytes.to_vec()))
+    }
+
+    /// Pointer to the SID, valid as long as `self`.
+    pub fn as_psid(&self) -> PSID {
+        self.0.as_ptr().cast_mut().cast()
+    }
+}
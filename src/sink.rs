@@ -0,0 +1,166 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::Level;
+
+use crate::error::Error;
+use crate::event_type::EventType;
+use crate::sid::UserSid;
+use crate::source::EventSource;
+use crate::wide::WideMessage;
+
+/// An event as it is handed to the event log, after the layout, the length limit and the
+/// event type mapping were applied.
+#[derive(Debug, Clone, Copy)]
+pub struct SinkEvent<'a> {
+    pub id: u32,
+    pub level: Level,
+    pub event_type: EventType,
+    pub category: u16,
+    /// The description (`%1`), followed by further insertion strings.
+    pub strings: &'a [&'a WideMessage],
+    /// Raw data of the event.
+    pub data: &'a [u8],
+    pub(crate) user_sid: Option<&'a UserSid>,
+}
+
+/// Where the layer and its handles write their events, see
+/// [`EventLogLayer::with_sink`](crate::EventLogLayer::with_sink).
+///
+/// [`EventSource`] writes to the event log with `ReportEventW` and is used unless another
+/// sink is set; [`MockSink`] captures the events for tests. Spillover, the fallback and the
+/// error handler apply to the errors of every sink.
+pub trait EventSink: Send + Sync {
+    /// # Errors
+    /// Fails if the event cannot be written; the error is handled like a failed
+    /// `ReportEventW`.
+    fn write(&self, event: &SinkEvent<'_>) -> Result<(), Error>;
+}
+
+impl EventSink for EventSource {
+    fn write(&self, event: &SinkEvent<'_>) -> Result<(), Error> {
+        self.try_report(
+            event.id,
+            event.event_type,
+            event.category,
+            event.user_sid,
+            event.strings,
+            event.data,
+        )
+    }
+}
+
+impl<S: EventSink + ?Sized> EventSink for Arc<S> {
+    fn write(&self, event: &SinkEvent<'_>) -> Result<(), Error> {
+        (**self).write(event)
+    }
+}
+
+/// An event captured by a [`MockSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedEvent {
+    pub id: u32,
+    pub level: Level,
+    pub event_type: EventType,
+    pub category: u16,
+    /// The description, as it would appear in the event log.
+    pub body: String,
+    /// Insertion strings after the description (`%2` onwards).
+    pub inserts: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+/// A sink keeping the events in memory instead of writing them, for unit tests that
+/// assert on what the layer would write without a registered source.
+///
+/// Clones share the captured events, so one clone can be handed to the layer and another
+/// kept by the test:
+///
+/// ```
+/// use tracing_layer_win_eventlog::{EventLogLayer, MockSink};
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let sink = MockSink::new();
+/// let layer = EventLogLayer::builder()
+///     .source("hello_world")
+///     .sink(sink.clone())
+///     .build()?; // does not register the source
+/// tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+///     tracing::warn!(id = 42, "disk almost full");
+/// });
+/// assert_eq!(sink.events()[0].id, 42);
+/// # Ok::<(), tracing_layer_win_eventlog::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockSink {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+    failure: Arc<Mutex<Option<Error>>>,
+}
+
+impl MockSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events captured so far, oldest first.
+    #[must_use]
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the events captured so far and forgets them.
+    #[must_use]
+    pub fn take(&self) -> Vec<CapturedEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn clear(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Fails every following write with `error` instead of capturing the event, until
+    /// called with `None`, e.g. to test the error handler or the fallback.
+    pub fn fail_with(&self, error: Option<Error>) {
+        *self.failure.lock().unwrap_or_else(PoisonError::into_inner) = error;
+    }
+}
+
+impl EventSink for MockSink {
+    fn write(&self, event: &SinkEvent<'_>) -> Result<(), Error> {
+        if let Some(error) = self
+            .failure
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+        {
+            return Err(error);
+        }
+        let (body, inserts) = event.strings.split_first().map_or_else(
+            || (String::new(), Vec::new()),
+            |(body, inserts)| {
+                (
+                    body.to_string(),
+                    inserts.iter().map(ToString::to_string).collect(),
+                )
+            },
+        );
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(CapturedEvent {
+                id: event.id,
+                level: event.level,
+                event_type: event.event_type,
+                category: event.category,
+                body,
+                inserts,
+                data: event.data.to_vec(),
+            });
+        Ok(())
+    }
+}
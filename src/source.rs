@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock, Weak};
+use std::time::{Duration, Instant};
+use tracing::Level;
+use winapi::shared::minwindef::DWORD;
+use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceW, ReportEventW};
+use winapi::um::winnt::HANDLE;
+
+use crate::coexist::{self, Marker};
+use crate::diagnostics::{self, Diagnostic};
+use crate::error::{Error, ERROR_INVALID_HANDLE};
+use crate::event_type::EventType;
+use crate::limits::MAX_STRING_LEN;
+use crate::sid::UserSid;
+use crate::wide::{to_wide, WideMessage};
+
+/// Who deregisters a handle passed to [`EventSource::from_raw_handle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleOwnership {
+    /// The crate deregisters the handle when the [`EventSource`] is dropped.
+    Owned,
+    /// The code that registered the handle deregisters it, after the [`EventSource`] and
+    /// every layer using it are gone.
+    Borrowed,
+}
+
+/// A registered event source handle, deregistered on drop.
+///
+/// Obtain a shared instance through [`EventSourceRegistry::get_or_register`], or register
+/// one whose lifetime the application controls with [`EventSource::try_register`] and
+/// hand it to [`EventLogLayer::from_source`](crate::EventLogLayer::from_source).
+#[derive(Debug)]
+pub struct EventSource {
+    name: String,
+    /// UNC name of the remote machine, `None` for the local one.
+    server: Option<String>,
+    /// Null once deregistered.
+    handle: RwLock<HANDLE>,
+    ownership: HandleOwnership,
+    /// Records this version of the crate as a holder of a local source, see
+    /// [`crate_versions`](crate::crate_versions).
+    _marker: Option<Marker>,
+    recovery: Mutex<Recovery>,
+    /// Set by [`EventSource::deregister`], after which the handle is not registered again.
+    closed: AtomicBool,
+    /// Handed out by [`EventSourceRegistry`] to every layer of the source in the process.
+    shared: bool,
+    /// Layers holding a [`Claim`] on the shared handle that have not released it yet.
+    claims: AtomicUsize,
+}
+
+/// A layer's hold on a shared [`EventSource`], shared by the clones of its output.
+///
+/// Releasing the last claim deregisters the handle, so the guard of the last layer of a
+/// source deregisters it even though the registry and direct writers may still hold the
+/// `Arc`. A layer dropped without a guard gives up its claim without deregistering.
+#[derive(Debug)]
+pub(crate) struct Claim {
+    source: Weak<EventSource>,
+    released: AtomicBool,
+}
+
+impl Claim {
+    /// Gives up the claim and deregisters the source if no other layer holds one.
+    pub(crate) fn release(&self) {
+        if self.released.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        if let Some(source) = self.source.upgrade() {
+            if source.claims.fetch_sub(1, Ordering::AcqRel) == 1 {
+                source.deregister();
+            }
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if !self.released.swap(true, Ordering::AcqRel) {
+            if let Some(source) = self.source.upgrade() {
+                source.claims.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    }
+}
+
+/// Backoff between attempts to register a source again whose handle went stale, e.g.
+/// after the event log service restarted.
+#[derive(Debug)]
+struct Recovery {
+    next_attempt: Option<Instant>,
+    delay: Duration,
+}
+
+impl Recovery {
+    const MIN_DELAY: Duration = Duration::from_secs(1);
+    const MAX_DELAY: Duration = Duration::from_secs(300);
+
+    const fn new() -> Self {
+        Self {
+            next_attempt: None,
+            delay: Self::MIN_DELAY,
+        }
+    }
+}
+
+/// Whether a failed `ReportEventW` means the handle no longer reaches the event log
+/// service: `ERROR_INVALID_HANDLE`, `RPC_S_UNKNOWN_IF`, `RPC_S_SERVER_UNAVAILABLE`,
+/// `RPC_S_CALL_FAILED` or `EPT_S_NOT_REGISTERED`. Other transient errors, e.g.
+/// `ERROR_BUSY`, do not get better with a new handle.
+const fn is_stale_handle(code: u32) -> bool {
+    matches!(code, ERROR_INVALID_HANDLE | 1717 | 1722 | 1726 | 1753)
+}
+
+fn register_handle(server: Option<&str>, name: &str) -> HANDLE {
+    let wide_name = to_wide(name);
+    let wide_server = server.map(to_wide);
+    unsafe {
+        RegisterEventSourceW(
+            wide_server
+                .as_ref()
+                .map_or(std::ptr::null(), |server| server.as_ptr()),
+            wide_name.as_ptr(),
+        )
+    }
+}
+
+// The handle returned by `RegisterEventSourceW` may be used from any thread.
+unsafe impl Send for EventSource {}
+unsafe impl Sync for EventSource {}
+
+impl EventSource {
+    /// Registers `name`, independently of the handles in [`EventSourceRegistry`].
+    ///
+    /// # Errors
+    /// Fails if `RegisterEventSourceW` fails.
+    pub fn try_register(name: &str) -> Result<Self, Error> {
+        Self::try_register_on(None, name)
+    }
+
+    /// Registers `name` on `server` (a UNC name such as `\\logs01`), or on the local
+    /// machine if `None`. Writes go to the event log of that machine over RPC.
+    ///
+    /// # Errors
+    /// Fails if `RegisterEventSourceW` fails, e.g. with `RPC_S_SERVER_UNAVAILABLE` (1722)
+    /// if the server cannot be reached.
+    pub fn try_register_on(server: Option<&str>, name: &str) -> Result<Self, Error> {
+        let handle = register_handle(server, name);
+        if handle.is_null() {
+            return Err(Error::Register {
+                source: name.to_owned(),
+                code: Error::last_os_code(),
+            });
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            server: server.map(str::to_owned),
+            handle: RwLock::new(handle),
+            ownership: HandleOwnership::Owned,
+            _marker: server.is_none().then(|| coexist::claim(name)).flatten(),
+            recovery: Mutex::new(Recovery::new()),
+            closed: AtomicBool::new(false),
+            shared: false,
+            claims: AtomicUsize::new(0),
+        })
+    }
+
+    /// Wraps a handle `name` was registered with by other code, e.g. the C++ side of the
+    /// process, instead of registering the source a second time.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Register`] and [`ERROR_INVALID_HANDLE`](crate::ERROR_INVALID_HANDLE)
+    /// if `handle` is null.
+    ///
+    /// # Safety
+    /// `handle` must have been returned by `RegisterEventSourceW`. With
+    /// [`HandleOwnership::Borrowed`] it must stay registered as long as the source is in
+    /// use; with [`HandleOwnership::Owned`] no other code may deregister it.
+    pub unsafe fn from_raw_handle(
+        name: &str,
+        handle: HANDLE,
+        ownership: HandleOwnership,
+    ) -> Result<Self, Error> {
+        if handle.is_null() {
+            return Err(Error::Register {
+                source: name.to_owned(),
+                code: ERROR_INVALID_HANDLE,
+            });
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            server: None,
+            handle: RwLock::new(handle),
+            ownership,
+            _marker: coexist::claim(name),
+            recovery: Mutex::new(Recovery::new()),
+            closed: AtomicBool::new(false),
+            shared: false,
+            claims: AtomicUsize::new(0),
+        })
+    }
+
+    /// Deregisters the handle now instead of on drop, unless it is borrowed. Later writes
+    /// fail with [`ERROR_INVALID_HANDLE`](crate::ERROR_INVALID_HANDLE).
+    pub(crate) fn deregister(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        let mut handle = self.handle.write().unwrap_or_else(PoisonError::into_inner);
+        if !handle.is_null() && self.ownership == HandleOwnership::Owned {
+            unsafe {
+                DeregisterEventSource(*handle);
+            }
+        }
+        *handle = std::ptr::null_mut();
+    }
+
+    /// Whether other layers and writers may hold this handle through
+    /// [`EventSourceRegistry`], so only dropping the last of them deregisters it.
+    pub(crate) const fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Records a layer using this shared handle until it releases the returned claim.
+    pub(crate) fn claim(self: &Arc<Self>) -> Arc<Claim> {
+        self.claims.fetch_add(1, Ordering::AcqRel);
+        Arc::new(Claim {
+            source: Arc::downgrade(self),
+            released: AtomicBool::new(false),
+        })
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The remote machine the source is registered on, `None` for the local one.
+    #[must_use]
+    pub fn server(&self) -> Option<&str> {
+        self.server.as_deref()
+    }
+
+    /// Writes `message` as the description of an event and returns whether it was written,
+    /// see [`EventSource::try_write`] for why it was not.
+    pub fn write(&self, event_id: u32, level: Level, message: &str) -> bool {
+        self.try_write(event_id, level, message).is_ok()
+    }
+
+    /// Writes `message` as the description of an event.
+    ///
+    /// # Errors
+    /// Fails if the message contains a nul character or `ReportEventW` fails, e.g. with
+    /// [`ERROR_LOG_FILE_FULL`](crate::ERROR_LOG_FILE_FULL).
+    pub fn try_write(&self, event_id: u32, level: Level, message: &str) -> Result<(), Error> {
+        self.try_report(
+            event_id,
+            level.into(),
+            0,
+            None,
+            &[&WideMessage::new(message)?],
+            &[],
+        )
+    }
+
+    /// Writes an already encoded `message` as the description of an event of `event_type`.
+    ///
+    /// # Errors
+    /// Fails if `ReportEventW` fails.
+    pub fn try_write_wide(
+        &self,
+        event_id: u32,
+        event_type: EventType,
+        message: &WideMessage,
+    ) -> Result<(), Error> {
+        self.try_report(event_id, event_type, 0, None, &[message], &[])
+    }
+
+    /// Writes an event with `strings` as its insertion strings `%1` to `%n`. Fails with
+    /// [`Error::TooLong`] instead of passing a string above the length limit.
+    ///
+    /// If the handle went stale, e.g. because the event log service restarted, the source
+    /// is registered again and the event written once more. Failed registrations are
+    /// retried with a backoff from 1 second up to 5 minutes, not on every write.
+    pub(crate) fn try_report(
+        &self,
+        event_id: u32,
+        event_type: EventType,
+        category: u16,
+        user_sid: Option<&UserSid>,
+        strings: &[&WideMessage],
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if let Some(string) = strings.iter().find(|s| s.as_units().len() > MAX_STRING_LEN) {
+            return Err(Error::TooLong {
+                len: string.as_units().len(),
+            });
+        }
+        let used = *self.handle.read().unwrap_or_else(PoisonError::into_inner);
+        match self.report_once(event_id, event_type, category, user_sid, strings, data) {
+            Err(Error::Write { code, .. }) if is_stale_handle(code) && self.recover(used) => self
+                .report_once(event_id, event_type, category, user_sid, strings, data)
+                .map_err(|error| match error {
+                    Error::Write { code, .. } => Error::Write { code, attempt: 2 },
+                    error => error,
+                }),
+            result => result,
+        }
+    }
+
+    /// Replaces the handle `stale` with a new registration, unless another write already
+    /// did, and returns whether there is a new handle to retry with.
+    fn recover(&self, stale: HANDLE) -> bool {
+        if self.ownership == HandleOwnership::Borrowed || self.closed.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut recovery = self.recovery.lock().unwrap_or_else(PoisonError::into_inner);
+        {
+            let handle = self.handle.read().unwrap_or_else(PoisonError::into_inner);
+            if *handle != stale && !handle.is_null() {
+                return true;
+            }
+        }
+        let now = Instant::now();
+        if recovery.next_attempt.is_some_and(|at| now < at) {
+            return false;
+        }
+
+        let fresh = register_handle(self.server.as_deref(), &self.name);
+        diagnostics::emit(|| Diagnostic::RegistrationRetry {
+            source: self.name.clone(),
+            succeeded: !fresh.is_null(),
+        });
+        if fresh.is_null() {
+            recovery.next_attempt = Some(now + recovery.delay);
+            recovery.delay = (recovery.delay * 2).min(Recovery::MAX_DELAY);
+            return false;
+        }
+        *recovery = Recovery::new();
+
+        let mut handle = self.handle.write().unwrap_or_else(PoisonError::into_inner);
+        if self.closed.load(Ordering::Relaxed) {
+            unsafe {
+                DeregisterEventSource(fresh);
+            }
+            return false;
+        }
+        let stale = std::mem::replace(&mut *handle, fresh);
+        if !stale.is_null() {
+            unsafe {
+                DeregisterEventSource(stale);
+            }
+        }
+        true
+    }
+
+    fn report_once(
+        &self,
+        event_id: u32,
+        event_type: EventType,
+        category: u16,
+        user_sid: Option<&UserSid>,
+        strings: &[&WideMessage],
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let handle = self.handle.read().unwrap_or_else(PoisonError::into_inner);
+        if handle.is_null() {
+            return Err(Error::Write {
+                code: ERROR_INVALID_HANDLE,
+                attempt: 1,
+            });
+        }
+        let mut strings: Vec<_> = strings.iter().map(|s| s.as_ptr()).collect();
+        let result = unsafe {
+            ReportEventW(
+                *handle,
+                event_type.raw(),
+                category,
+                event_id as DWORD,
+                user_sid.map_or(std::ptr::null_mut(), UserSid::as_psid),
+                u16::try_from(strings.len()).unwrap_or(u16::MAX),
+                u32::try_from(data.len()).unwrap_or(u32::MAX),
+                strings.as_mut_ptr(),
+                if data.is_empty() {
+                    std::ptr::null_mut()
+                } else {
+                    data.as_ptr() as *mut _
+                },
+            )
+        };
+
+        if result == 0 {
+            return Err(Error::Write {
+                code: Error::last_os_code(),
+                attempt: 1,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for EventSource {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Weak<EventSource>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Weak<EventSource>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Process-wide registry of event source handles.
+///
+/// Every layer and direct writer asking for the same source shares one handle, which is
+/// deregistered when the last user drops it, or when the guard of the last layer using it
+/// is dropped. This avoids handle churn in applications that rebuild their subscriber,
+/// e.g. on configuration reload.
+#[derive(Debug)]
+pub struct EventSourceRegistry;
+
+impl EventSourceRegistry {
+    /// Returns the handle for `name`, registering it if no one holds it yet.
+    ///
+    /// Source names are case-insensitive, so are the keys of the registry.
+    #[must_use]
+    pub fn get_or_register(name: &str) -> Option<Arc<EventSource>> {
+        Self::try_get_or_register(name)
+            .map_err(|e| {
+                diagnostics::emit(|| Diagnostic::RegistrationFailed {
+                    source: name.to_owned(),
+                    code: e.code(),
+                });
+            })
+            .ok()
+    }
+
+    /// Like [`EventSourceRegistry::get_or_register`], returning why registration failed.
+    ///
+    /// # Errors
+    /// Fails if `RegisterEventSourceW` fails.
+    pub fn try_get_or_register(name: &str) -> Result<Arc<EventSource>, Error> {
+        let Ok(mut registry) = registry().lock() else {
+            return EventSource::try_register(name).map(Arc::new);
+        };
+
+        let key = name.to_lowercase();
+        // A closed handle is never registered again, so it is replaced rather than shared.
+        if let Some(source) = registry
+            .get(&key)
+            .and_then(Weak::upgrade)
+            .filter(|source| !source.closed.load(Ordering::Relaxed))
+        {
+            return Ok(source);
+        }
+
+        let mut source = EventSource::try_register(name)?;
+        source.shared = true;
+        let source = Arc::new(source);
+        registry.retain(|_, s| s.strong_count() > 0);
+        registry.insert(key, Arc::downgrade(&source));
+        Ok(source)
+    }
+
+    /// Whether a handle for `name` is currently held by anyone.
+    #[must_use]
+    pub fn is_registered(name: &str) -> bool {
+        registry().lock().is_ok_and(|registry| {
+            registry
+                .get(&name.to_lowercase())
+                .is_some_and(|s| s.strong_count() > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn borrowed_source() -> Arc<EventSource> {
+        let handle = std::ptr::NonNull::<u8>::dangling().as_ptr().cast();
+        let source =
+            unsafe { EventSource::from_raw_handle("claims", handle, HandleOwnership::Borrowed) };
+        Arc::new(source.unwrap())
+    }
+
+    #[test]
+    fn releasing_the_last_claim_deregisters() {
+        let source = borrowed_source();
+        let first = source.claim();
+        let second = source.claim();
+        first.release();
+        first.release();
+        assert!(!source.closed.load(Ordering::Relaxed));
+        second.release();
+        assert!(source.closed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn dropping_a_claim_does_not_deregister() {
+        let source = borrowed_source();
+        drop(source.claim());
+        assert!(!source.closed.load(Ordering::Relaxed));
+        source.claim().release();
+        assert!(source.closed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn only_handle_and_rpc_errors_are_stale() {
+        for code in [ERROR_INVALID_HANDLE, 1717, 1722, 1726, 1753] {
+            assert!(is_stale_handle(code), "{code}");
+        }
+        // ERROR_NOT_ENOUGH_MEMORY, ERROR_BUSY, WAIT_TIMEOUT, RPC_S_SERVER_TOO_BUSY
+        for code in [8, 170, 258, 1723] {
+            assert!(!is_stale_handle(code), "{code}");
+        }
+    }
+}
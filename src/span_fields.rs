@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use tracing::field::{Field, Visit};
+
+/// The fields of a span, added to the events inside it.
+#[derive(Debug, Default)]
+pub struct SpanFields {
+    /// The `Debug` form of each value and, for strings, the string itself.
+    values: HashMap<&'static str, (String, Option<String>)>,
+}
+
+impl SpanFields {
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str, Option<&str>)> {
+        self.values
+            .iter()
+            .map(|(name, (value, raw))| (*name, value.as_str(), raw.as_deref()))
+    }
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.values
+            .insert(field.name(), (format!("{value:?}"), Some(value.to_owned())));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.values
+            .insert(field.name(), (format!("{value:?}"), None));
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt::Write;
+use tracing::field::{Field, Visit};
+
+/// Display name of a span with selected field values substituted, e.g. `request[42]`.
+#[derive(Debug)]
+pub struct SpanLabel {
+    name: &'static str,
+    values: Vec<(&'static str, Option<String>)>,
+}
+
+impl SpanLabel {
+    pub fn new(name: &'static str, fields: &[&'static str]) -> Self {
+        Self {
+            name,
+            values: fields.iter().map(|field| (*field, None)).collect(),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let values: Vec<&str> = self
+            .values
+            .iter()
+            .filter_map(|(_, value)| value.as_deref())
+            .collect();
+        if values.is_empty() {
+            return self.name.to_owned();
+        }
+
+        let mut label = self.name.to_owned();
+        let _ = write!(label, "[{}]", values.join(", "));
+        label
+    }
+
+    fn set(&mut self, field: &Field, value: String) {
+        if let Some((_, slot)) = self.values.iter_mut().find(|(f, _)| *f == field.name()) {
+            *slot = Some(value);
+        }
+    }
+}
+
+impl Visit for SpanLabel {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, format!("{value:?}"));
+    }
+}
@@ -0,0 +1,338 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::Level;
+
+use crate::binary::{decode_hex, encode_hex};
+use crate::event_type::EventType;
+use crate::record::EventRecord;
+
+/// Appends events that could not be written to a bounded file and replays them once
+/// writes succeed again.
+///
+/// After the replay a WARN event (ID 1002 by default) summarizes the outage: how many
+/// events were replayed, how many were dropped because the file was full, and when the
+/// first and the last write failed. Records left over by a previous run are replayed
+/// as well.
+#[derive(Debug, Clone)]
+pub struct Spillover {
+    path: PathBuf,
+    max_bytes: u64,
+    id: u32,
+}
+
+impl Spillover {
+    pub const DEFAULT_ID: u32 = 1002;
+
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes,
+            id: Self::DEFAULT_ID,
+        }
+    }
+
+    /// Event ID of the outage summary.
+    #[must_use]
+    pub const fn id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Outage {
+    dropped: u64,
+    first_at: Option<SystemTime>,
+    last_at: Option<SystemTime>,
+}
+
+/// The records of an outage taken for replay.
+#[derive(Debug)]
+pub struct Spilled {
+    pub records: Vec<EventRecord>,
+    pub dropped: u64,
+    pub first_at: Option<SystemTime>,
+    pub last_at: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+pub struct SpillFile {
+    config: Spillover,
+    outage: Mutex<Outage>,
+    pending: AtomicBool,
+}
+
+impl SpillFile {
+    pub fn new(config: Spillover) -> Self {
+        let leftover = std::fs::metadata(&config.path).is_ok_and(|m| m.len() > 0);
+        Self {
+            config,
+            outage: Mutex::new(Outage::default()),
+            pending: AtomicBool::new(leftover),
+        }
+    }
+
+    pub const fn summary_id(&self) -> u32 {
+        self.config.id
+    }
+
+    /// Whether records wait for replay.
+    pub fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    pub fn append(&self, record: &EventRecord) {
+        let Ok(mut outage) = self.outage.lock() else {
+            return;
+        };
+        let now = SystemTime::now();
+        outage.first_at.get_or_insert(now);
+        outage.last_at = Some(now);
+
+        let line = serialize(record);
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)
+            .and_then(|mut file| {
+                if file.metadata()?.len() + line.len() as u64 > self.config.max_bytes {
+                    return Ok(false);
+                }
+                file.write_all(line.as_bytes())?;
+                Ok(true)
+            });
+        if !matches!(written, Ok(true)) {
+            outage.dropped += 1;
+        }
+        self.pending.store(true, Ordering::Relaxed);
+    }
+
+    /// Takes all spilled records and empties the file.
+    pub fn take(&self) -> Option<Spilled> {
+        let mut outage = self.outage.lock().ok()?;
+        if !self.pending.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+
+        let records = File::open(&self.config.path)
+            .map(|file| {
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| deserialize(&line))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let _ = File::create(&self.config.path);
+
+        let outage = std::mem::take(&mut *outage);
+        Some(Spilled {
+            records,
+            dropped: outage.dropped,
+            first_at: outage.first_at,
+            last_at: outage.last_at,
+        })
+    }
+
+    /// Puts records back that could not be replayed.
+    pub fn restore(&self, spilled: &Spilled, from: usize) {
+        let Ok(mut outage) = self.outage.lock() else {
+            return;
+        };
+        let restored: io::Result<()> = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)
+            .and_then(|mut file| {
+                for record in &spilled.records[from..] {
+                    file.write_all(serialize(record).as_bytes())?;
+                }
+                Ok(())
+            });
+        if restored.is_err() {
+            outage.dropped += (spilled.records.len() - from) as u64;
+        }
+        outage.dropped += spilled.dropped;
+        outage.first_at = spilled.first_at.or(outage.first_at);
+        outage.last_at = outage.last_at.or(spilled.last_at);
+        self.pending.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Prefix of lines that carry the event type, category, raw data and insertion strings.
+const V2: &str = "v2";
+
+/// One record per line: the version `v2`, timestamp in milliseconds, ID, level, event type
+/// (empty if it follows the level), category, raw data in hex, source, message and the
+/// further insertion strings, separated by tabs, with backslashes, tabs and line breaks
+/// escaped. Lines without a version, of earlier releases, end after the message.
+fn serialize(record: &EventRecord) -> String {
+    let timestamp = record
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let mut line = format!(
+        "{V2}\t{timestamp}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        record.id,
+        record.level,
+        record.event_type.map_or("", EventType::as_str),
+        record.category,
+        encode_hex(&record.data),
+        escape(&record.source),
+        escape(&record.message)
+    );
+    for insert in &record.inserts {
+        line.push('\t');
+        line.push_str(&escape(insert));
+    }
+    line.push('\n');
+    line
+}
+
+fn deserialize(line: &str) -> Option<EventRecord> {
+    let Some(line) = line.strip_prefix(V2).and_then(|l| l.strip_prefix('\t')) else {
+        let mut parts = line.splitn(5, '\t');
+        let timestamp = parts.next()?.parse().ok()?;
+        let id = parts.next()?.parse().ok()?;
+        let level = Level::from_str(parts.next()?).ok()?;
+        let source = unescape(parts.next()?);
+        let message = unescape(parts.next()?);
+        return Some(record(timestamp, source, id, level, message));
+    };
+
+    let mut parts = line.split('\t');
+    let timestamp = parts.next()?.parse().ok()?;
+    let id = parts.next()?.parse().ok()?;
+    let level = Level::from_str(parts.next()?).ok()?;
+    let event_type = match parts.next()? {
+        "" => None,
+        event_type => Some(event_type.parse().ok()?),
+    };
+    let category = parts.next()?.parse().ok()?;
+    let data = decode_hex(parts.next()?)?;
+    let source = unescape(parts.next()?);
+    let message = unescape(parts.next()?);
+
+    let mut record = record(timestamp, source, id, level, message);
+    record.event_type = event_type;
+    record.category = category;
+    record.data = data;
+    record.inserts = parts.map(unescape).collect();
+    Some(record)
+}
+
+fn record(timestamp: u64, source: String, id: u32, level: Level, message: String) -> EventRecord {
+    let mut record = EventRecord::new(source, id, level, message);
+    record.timestamp = UNIX_EPOCH + Duration::from_millis(timestamp);
+    record
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> EventRecord {
+        let mut record =
+            EventRecord::new("hello\tworld", 7, Level::ERROR, "ID: 7\n\nmessage: a\\b");
+        record.timestamp = UNIX_EPOCH + Duration::from_millis(1_714_564_801_000);
+        record
+    }
+
+    #[test]
+    fn lines_keep_the_whole_event() {
+        let mut record = record();
+        record.event_type = Some(EventType::AuditFailure);
+        record.category = 3;
+        record.inserts = vec!["alice\r\n".to_owned(), String::new()];
+        record.data = vec![0xde, 0xad, 0xbe, 0xef];
+
+        let line = serialize(&record);
+        assert!(line.starts_with("v2\t1714564801000\t7\tERROR\taudit_failure\t3\tdeadbeef\t"));
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(deserialize(line.trim_end_matches('\n')), Some(record));
+    }
+
+    #[test]
+    fn event_type_following_the_level_stays_unset() {
+        let record = record();
+        let line = serialize(&record);
+        assert_eq!(deserialize(line.trim_end_matches('\n')), Some(record));
+    }
+
+    #[test]
+    fn lines_without_a_version_are_read() {
+        let line = "1714564801000\t7\tERROR\thello\\tworld\tID: 7\\n\\nmessage: a\\\\b";
+        assert_eq!(deserialize(line), Some(record()));
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        assert_eq!(
+            deserialize("v2\t1714564801000\t7\tERROR\tloud\t0\t\tsource\tmessage"),
+            None
+        );
+        assert_eq!(
+            deserialize("v2\t1714564801000\t7\tERROR\t\t0\txyz\tsource\tmessage"),
+            None
+        );
+        assert_eq!(deserialize("not a record"), None);
+    }
+
+    #[test]
+    fn taken_records_can_be_restored() {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-layer-win-eventlog-{}-restore.spill",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let spill = SpillFile::new(Spillover::new(&path, 1024 * 1024));
+        assert!(!spill.is_pending());
+
+        let mut second = record();
+        second.id = 8;
+        spill.append(&record());
+        spill.append(&second);
+        let spilled = spill.take().unwrap();
+        assert_eq!(spilled.records, [record(), second.clone()]);
+        assert!(spill.take().is_none());
+
+        spill.restore(&spilled, 1);
+        let restored = spill.take().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(restored.records, [second]);
+        assert_eq!(restored.first_at, spilled.first_at);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::time::format_system_time;
+
+/// Replaces repeated stack traces with a reference to their first occurrence.
+///
+/// When a value of one of the stack fields (by default `backtrace`, `stack` and
+/// `stack_trace`) was already written within `window`, it is replaced by
+/// `stack #<fingerprint>, first seen <time>`. The first occurrence carries the
+/// fingerprint as `<field>_fingerprint`, so the full trace can still be found.
+#[derive(Debug, Clone)]
+pub struct StackCompression {
+    window: Duration,
+    fields: Vec<String>,
+}
+
+impl StackCompression {
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            fields: vec!["backtrace".into(), "stack".into(), "stack_trace".into()],
+        }
+    }
+
+    /// Treats values of `name` as stack traces as well.
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<String>) -> Self {
+        self.fields.push(name.into());
+        self
+    }
+}
+
+impl Default for StackCompression {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300))
+    }
+}
+
+#[derive(Debug)]
+struct Seen {
+    first_at: SystemTime,
+    last_seen: Instant,
+}
+
+#[derive(Debug)]
+pub struct StackDeduper {
+    config: StackCompression,
+    seen: Mutex<HashMap<u64, Seen>>,
+}
+
+impl StackDeduper {
+    pub fn new(config: StackCompression) -> Self {
+        Self {
+            config,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub const fn config(&self) -> &StackCompression {
+        &self.config
+    }
+
+    /// Compresses the stack fields in `fields` in place.
+    ///
+    /// Returns the names of the fields that were replaced by a reference.
+    pub fn compress(&self, fields: &mut HashMap<String, String>) -> Vec<String> {
+        let Ok(mut seen) = self.seen.lock() else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        seen.retain(|_, s| now.duration_since(s.last_seen) < self.config.window);
+
+        let mut replaced = Vec::new();
+        for name in &self.config.fields {
+            let Some(value) = fields.get_mut(name) else {
+                continue;
+            };
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            let hash = hasher.finish();
+            let fingerprint = format!("{:06x}", hash & 0xff_ffff);
+
+            match seen.get_mut(&hash) {
+                Some(s) => {
+                    s.last_seen = now;
+                    *value = format!(
+                        "stack #{fingerprint}, first seen {}",
+                        format_system_time(s.first_at)
+                    );
+                    replaced.push(name.clone());
+                }
+                None => {
+                    seen.insert(
+                        hash,
+                        Seen {
+                            first_at: SystemTime::now(),
+                            last_seen: now,
+                        },
+                    );
+                    fields.insert(format!("{name}_fingerprint"), fingerprint);
+                }
+            }
+        }
+        replaced
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Counts of the events of a layer and its handles since the layer was built, see
+/// [`EventLogLayer::stats`](crate::EventLogLayer::stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct EventLogStats {
+    /// Events the event log (or the sink) accepted.
+    pub written: u64,
+    /// Events whose write failed, including those then written to the spillover file or
+    /// the fallback.
+    pub failed: u64,
+    /// Events the layer left out on purpose: below the adaptive verbosity, not sampled,
+    /// over a rate limit, coalesced as a duplicate or suppressed by flood protection.
+    pub filtered: u64,
+    /// Events dropped because the queue of the non-blocking writer was full.
+    pub queue_overflow: u64,
+    /// Events that waited for room in a full queue, see
+    /// [`NonBlocking::block_for`](crate::NonBlocking::block_for); those still dropped after
+    /// the timeout also count as queue overflows.
+    pub queue_waits: u64,
+}
+
+impl EventLogStats {
+    /// Events that were neither written nor tried: filtered and queue overflows.
+    #[must_use]
+    pub const fn dropped(&self) -> u64 {
+        self.filtered + self.queue_overflow
+    }
+
+    pub(crate) fn describe(&self, id: u32) -> String {
+        format!(
+            "ID: {id}\n\nmessage: event log layer statistics\nwritten: {}\nfailed: {}\nfiltered: {}\nqueue_overflow: {}\nqueue_waits: {}\n",
+            self.written, self.failed, self.filtered, self.queue_overflow, self.queue_waits
+        )
+    }
+}
+
+/// The counters behind [`EventLogStats`], shared by the clones of an output.
+#[derive(Debug, Default)]
+pub struct Counters {
+    written: AtomicU64,
+    failed: AtomicU64,
+    filtered: AtomicU64,
+    queue_overflow: AtomicU64,
+    queue_waits: AtomicU64,
+}
+
+impl Counters {
+    pub fn written(&self, events: u64) {
+        self.written.fetch_add(events, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn filtered(&self) {
+        self.filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn queue_overflow(&self) {
+        self.queue_overflow.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn queue_wait(&self) {
+        self.queue_waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> EventLogStats {
+        EventLogStats {
+            written: self.written.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            filtered: self.filtered.load(Ordering::Relaxed),
+            queue_overflow: self.queue_overflow.load(Ordering::Relaxed),
+            queue_waits: self.queue_waits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Writes the statistics as an event at most once per interval, with the next event
+/// after it elapsed.
+#[derive(Debug)]
+pub struct StatsSummary {
+    pub id: u32,
+    pub interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl StatsSummary {
+    pub fn new(id: u32, interval: Duration) -> Self {
+        Self {
+            id,
+            interval,
+            next: Mutex::new(Instant::now() + interval),
+        }
+    }
+
+    /// Whether the summary is due, starting the next interval if it is.
+    pub fn due(&self) -> bool {
+        let now = Instant::now();
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        if now < *next {
+            return false;
+        }
+        *next = now + self.interval;
+        true
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::{self, Write as _};
+use std::thread::JoinHandle;
+use tracing::Level;
+use winapi::shared::minwindef::DWORD;
+use winapi::um::fileapi::{ReadFile, WriteFile};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::namedpipeapi::CreatePipe;
+use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+use winapi::um::winbase::STD_ERROR_HANDLE;
+use winapi::um::winnt::HANDLE;
+
+use crate::handle::EventLogHandle;
+use crate::writer::EventLogWriter;
+
+/// Redirects the process's stderr into the event log until dropped.
+///
+/// Dropping restores the original stderr handle and waits until all captured output has
+/// been written. If the event log rejects a line, the remaining output is passed through
+/// to the original stderr instead.
+#[derive(Debug)]
+pub struct StderrCapture {
+    original: HANDLE,
+    write: HANDLE,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Moves a handle into the reader thread.
+struct SendHandle(HANDLE);
+
+unsafe impl Send for SendHandle {}
+
+/// Redirects stderr (as seen through `GetStdHandle`) into the event log, writing each
+/// line as a WARN event with ID `id`.
+///
+/// # Errors
+/// Fails if the pipe cannot be created or the std handle cannot be replaced.
+pub fn capture_stderr(handle: EventLogHandle, id: u32) -> io::Result<StderrCapture> {
+    let original = unsafe { GetStdHandle(STD_ERROR_HANDLE) };
+
+    let mut read: HANDLE = std::ptr::null_mut();
+    let mut write: HANDLE = std::ptr::null_mut();
+    if unsafe { CreatePipe(&mut read, &mut write, std::ptr::null_mut(), 0) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if unsafe { SetStdHandle(STD_ERROR_HANDLE, write) } == 0 {
+        let err = io::Error::last_os_error();
+        unsafe {
+            CloseHandle(read);
+            CloseHandle(write);
+        }
+        return Err(err);
+    }
+
+    let reader = SendHandle(read);
+    let passthrough = SendHandle(original);
+    let thread = std::thread::Builder::new()
+        .name("eventlog-stderr".to_owned())
+        .spawn(move || {
+            let reader = reader;
+            let passthrough = passthrough;
+            let mut writer = EventLogWriter::new(handle, Level::WARN, id).with_origin("stderr");
+            let mut forwarding = true;
+            let mut buf = [0u8; 4096];
+
+            loop {
+                let mut read: DWORD = 0;
+                let ok = unsafe {
+                    ReadFile(
+                        reader.0,
+                        buf.as_mut_ptr().cast(),
+                        DWORD::try_from(buf.len()).unwrap_or_default(),
+                        &mut read,
+                        std::ptr::null_mut(),
+                    )
+                };
+                if ok == 0 || read == 0 {
+                    break;
+                }
+
+                let chunk = &buf[..read as usize];
+                if forwarding && writer.write_all(chunk).is_err() {
+                    forwarding = false;
+                }
+                if !forwarding {
+                    write_handle(passthrough.0, chunk);
+                }
+            }
+
+            if forwarding {
+                let _ = writer.flush();
+            }
+            unsafe {
+                CloseHandle(reader.0);
+            }
+        })?;
+
+    Ok(StderrCapture {
+        original,
+        write,
+        thread: Some(thread),
+    })
+}
+
+fn write_handle(handle: HANDLE, mut data: &[u8]) {
+    while !data.is_empty() {
+        let mut written: DWORD = 0;
+        let ok = unsafe {
+            WriteFile(
+                handle,
+                data.as_ptr().cast(),
+                DWORD::try_from(data.len()).unwrap_or(DWORD::MAX),
+                &mut written,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 || written == 0 {
+            return;
+        }
+        data = &data[written as usize..];
+    }
+}
+
+impl Drop for StderrCapture {
+    fn drop(&mut self) {
+        unsafe {
+            SetStdHandle(STD_ERROR_HANDLE, self.original);
+            CloseHandle(self.write);
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use tracing::Subscriber;
+use tracing_subscriber::Layer;
+
+/// Stand-in for the event log layer on platforms without an event log.
+///
+/// It can be added to a subscriber unconditionally and discards every event, so libraries
+/// and CI on Linux or macOS build without `cfg(windows)` around the subscriber setup. The
+/// configuration methods of the Windows layer are not available.
+#[derive(Debug, Clone)]
+pub struct EventLogLayer {
+    log_name: String,
+}
+
+impl EventLogLayer {
+    #[must_use]
+    pub const fn new(log_name: String) -> Self {
+        Self { log_name }
+    }
+
+    /// The source name the layer would write as on Windows.
+    #[must_use]
+    pub fn log_name(&self) -> &str {
+        &self.log_name
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventLogLayer {}
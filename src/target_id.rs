@@ -0,0 +1,77 @@
+use std::cmp::Reverse;
+use tracing::Level;
+
+/// Event IDs of events without an `id` field by target prefix, see
+/// [`EventLogLayer::with_target_id`](crate::EventLogLayer::with_target_id).
+#[derive(Debug, Clone, Default)]
+pub struct TargetIds {
+    /// Longest prefix first, so the most specific one wins.
+    prefixes: Vec<(String, TargetId)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TargetId {
+    Fixed(u32),
+    /// The base plus the per-level offset, 0 for TRACE up to 4 for ERROR.
+    Range(u32),
+}
+
+impl TargetIds {
+    pub fn insert_fixed(&mut self, prefix: &str, id: u32) {
+        self.insert(prefix, TargetId::Fixed(id));
+    }
+
+    pub fn insert_range(&mut self, prefix: &str, base: u32) {
+        self.insert(prefix, TargetId::Range(base));
+    }
+
+    fn insert(&mut self, prefix: &str, id: TargetId) {
+        let prefix = prefix.strip_suffix("::*").unwrap_or(prefix).to_owned();
+        self.prefixes.retain(|(p, _)| *p != prefix);
+        self.prefixes.push((prefix, id));
+        self.prefixes
+            .sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// The ID for `target`, matching prefixes at module boundaries: `db` matches `db` and
+    /// `db::pool` but not `dbus`.
+    pub fn get(&self, target: &str, level: Level) -> Option<u32> {
+        let (_, id) = self.prefixes.iter().find(|(prefix, _)| {
+            target
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })?;
+        Some(match *id {
+            TargetId::Fixed(id) => id,
+            TargetId::Range(base) => base.saturating_add(level_offset(level)),
+        })
+    }
+
+    /// `prefix=id` or `prefix=base+level` per mapping, for the layer settings.
+    pub fn describe(&self) -> String {
+        let mut entries: Vec<String> = self
+            .prefixes
+            .iter()
+            .map(|(prefix, id)| match id {
+                TargetId::Fixed(id) => format!("{prefix}={id}"),
+                TargetId::Range(base) => format!("{prefix}={base}+level"),
+            })
+            .collect();
+        entries.sort();
+        format!("{entries:?}")
+    }
+}
+
+const fn level_offset(level: Level) -> u32 {
+    match level {
+        Level::TRACE => 0,
+        Level::DEBUG => 1,
+        Level::INFO => 2,
+        Level::WARN => 3,
+        Level::ERROR => 4,
+    }
+}
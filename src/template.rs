@@ -0,0 +1,167 @@
+/// A message shape preregistered for an event ID.
+///
+/// `{name}` marks a slot that is filled with the value of the field `name` (`{message}`
+/// for the message); `{{` and `}}` are literal braces. The literal parts are encoded to
+/// UTF-16 once, so rendering only copies them and encodes the field values.
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(Vec<u16>),
+    Slot(String),
+}
+
+impl Template {
+    #[must_use]
+    pub fn parse(template: &str) -> Self {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        literal.push('{');
+                        literal.push_str(&name);
+                        continue;
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(literal.encode_utf16().collect()));
+                        literal.clear();
+                    }
+                    segments.push(Segment::Slot(name));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal.encode_utf16().collect()));
+        }
+
+        Self { segments }
+    }
+
+    /// Names of the slots in order of appearance.
+    pub fn slots(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Slot(name) => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Like [`Template::render`], as a `String` without the terminating nul.
+    pub fn render_string<'a>(&self, value: impl Fn(&str) -> Option<&'a str>) -> String {
+        let mut rendered = self.render(value);
+        rendered.pop();
+        String::from_utf16_lossy(&rendered)
+    }
+
+    /// Renders a nul-terminated UTF-16 description. Slots without a value are kept as
+    /// `{name}` so missing fields stay visible.
+    pub fn render<'a>(&self, value: impl Fn(&str) -> Option<&'a str>) -> Vec<u16> {
+        let mut out = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => out.extend_from_slice(literal),
+                Segment::Slot(name) => match value(name) {
+                    Some(v) => out.extend(v.encode_utf16()),
+                    None => out.extend(format!("{{{name}}}").encode_utf16()),
+                },
+            }
+        }
+        out.push(0);
+        out
+    }
+
+    /// The template as a message text of a message table or manifest: every slot replaced
+    /// with `insert(name)`, e.g. `%2`, and `%`, line breaks and tabs in the literal parts
+    /// escaped for `FormatMessageW`.
+    pub(crate) fn message_text(&self, insert: impl Fn(&str) -> String) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    for c in String::from_utf16_lossy(literal).chars() {
+                        match c {
+                            '%' => out.push_str("%%"),
+                            '\n' => out.push_str("%n"),
+                            '\t' => out.push_str("%t"),
+                            '\r' => {}
+                            c => out.push(c),
+                        }
+                    }
+                }
+                Segment::Slot(name) => out.push_str(&insert(name)),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(name: &str) -> Option<&'static str> {
+        match name {
+            "message" => Some("disk almost full"),
+            "volume" => Some("C:"),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_slots_in_order() {
+        let template = Template::parse("{message} on {volume} ({free} free)");
+        assert_eq!(
+            template.slots().collect::<Vec<_>>(),
+            ["message", "volume", "free"]
+        );
+    }
+
+    #[test]
+    fn renders_values_and_keeps_missing_slots_visible() {
+        let template = Template::parse("{message} on {volume} ({free} free)");
+        assert_eq!(
+            template.render_string(fields),
+            "disk almost full on C: ({free} free)"
+        );
+        let rendered = template.render(fields);
+        assert_eq!(rendered.last(), Some(&0));
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let template = Template::parse("{{volume}} is {volume}}}");
+        assert_eq!(template.slots().collect::<Vec<_>>(), ["volume"]);
+        assert_eq!(template.render_string(fields), "{volume} is C:}");
+    }
+
+    #[test]
+    fn an_unclosed_brace_is_literal() {
+        let template = Template::parse("free: {free");
+        assert_eq!(template.slots().count(), 0);
+        assert_eq!(template.render_string(fields), "free: {free");
+    }
+}
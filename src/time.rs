@@ -0,0 +1,120 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Renders a duration measured with the monotonic clock.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+/// Renders a wall-clock timestamp as RFC 3339 in UTC.
+pub fn format_system_time(time: SystemTime) -> String {
+    let (secs, millis) = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs(), d.subsec_millis()),
+        Err(_) => (0, 0),
+    };
+
+    let days = i64::try_from(secs / 86_400).unwrap_or_default();
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Parses an RFC 3339 timestamp in UTC as written by the event log, e.g.
+/// `2024-05-01T12:00:01.1234567Z`.
+pub fn parse_system_time(s: &str) -> Option<SystemTime> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        let digits = &fraction[..fraction.len().min(9)];
+        digits.parse::<u32>().ok()? * 10u32.pow(9 - u32::try_from(digits.len()).ok()?)
+    };
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_utc_timestamps_with_milliseconds() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_714_564_801_482);
+        assert_eq!(format_system_time(time), "2024-05-01T12:00:01.482Z");
+        assert_eq!(format_system_time(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        // 2000 is a leap year, 1900 is not.
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(format_system_time(leap_day), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn times_before_the_epoch_render_as_the_epoch() {
+        let time = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(format_system_time(time), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn parses_event_log_timestamps() {
+        assert_eq!(
+            parse_system_time("2024-05-01T12:00:01.1234567Z"),
+            Some(UNIX_EPOCH + Duration::new(1_714_564_801, 123_456_700))
+        );
+        assert_eq!(
+            parse_system_time("2000-02-29T00:00:00Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_782_400))
+        );
+        assert_eq!(parse_system_time("2024-05-01T12:00:01"), None);
+        assert_eq!(parse_system_time("2024-05-01 12:00:01Z"), None);
+    }
+
+    #[test]
+    fn parses_what_it_formats() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_714_564_801_482);
+        assert_eq!(parse_system_time(&format_system_time(time)), Some(time));
+    }
+
+    #[test]
+    fn formats_durations_in_seconds() {
+        assert_eq!(format_duration(Duration::from_millis(1204)), "1.204s");
+        assert_eq!(format_duration(Duration::ZERO), "0.000s");
+    }
+}
@@ -0,0 +1,323 @@
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::Layer;
+use winapi::shared::evntprov::{
+    EventProviderSetTraits, EventSetInformation, EventWriteTransfer,
+    EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA, EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA,
+    EVENT_DESCRIPTOR,
+};
+
+use crate::error::Error;
+use crate::etw::{data_descriptor, etw_level, FieldVisitor, Provider};
+use crate::guid::format_guid;
+use crate::normalize::FieldNameNormalization;
+use crate::record::FieldValue;
+
+/// Channel of TraceLogging events, which are not defined in a manifest.
+const TRACELOGGING_CHANNEL: u8 = 11;
+
+/// TraceLogging field types (`TlgIn*` and `TlgOut*` of `TraceLoggingProvider.h`).
+const IN_ANSISTRING: u8 = 2;
+const IN_INT64: u8 = 9;
+const IN_UINT64: u8 = 10;
+const IN_DOUBLE: u8 = 12;
+const IN_BOOL32: u8 = 13;
+const OUT_UTF8: u8 = 35;
+/// Set on an input type that is followed by an output type.
+const CHAIN_FLAG: u8 = 0x80;
+
+/// Namespace of provider IDs derived from provider names, as used by `EventSource` and
+/// the TraceLogging tools.
+const PROVIDER_NAMESPACE: [u8; 16] = [
+    0x48, 0x2C, 0x2D, 0xB2, 0xC3, 0x90, 0x47, 0xC8, 0x87, 0xF8, 0x1A, 0x15, 0xBF, 0xC1, 0x30, 0xFB,
+];
+
+/// A layer writing events to an ETW TraceLogging provider instead of the event log.
+///
+/// TraceLogging events are self-describing: each field is written with its name and type
+/// (strings as UTF-8, integers, floats and booleans as such), so tools such as WPA,
+/// `tracelog` or `PerfView` show them without a manifest or message file. The event name
+/// is the name of the callsite, the level is mapped to the ETW level, and `target`
+/// is added as a field. Nothing is formatted while no trace session listens.
+///
+/// ```ignore
+/// let layer = TraceLoggingLayer::new("MyCompany.MyService")?;
+/// println!("provider {}", layer.provider_id());
+/// tracing_subscriber::registry().with(layer).init();
+/// ```
+///
+/// The provider ID is derived from the name like `EventSource` does, so a session can be
+/// started with `tracelog -start trace -guid *MyCompany.MyService`; use
+/// [`TraceLoggingLayer::with_provider_id`] for a fixed ID instead.
+///
+/// Of the options of [`EventLogLayer`](crate::EventLogLayer) only the maximum level and the
+/// field name normalization carry over. Event IDs, the reserved `eventlog.*` fields, the
+/// missing message policy, span fields, layouts, templates, value mappings, sampling, rate
+/// limits, duplicate coalescing and flood protection do not apply: an `id` field and the
+/// reserved fields are written as ordinary fields, and an event without a message is
+/// written without a `message` field.
+#[derive(Debug)]
+pub struct TraceLoggingLayer {
+    provider: Provider,
+    /// Provider traits: their size, the nul-terminated name.
+    traits: Vec<u8>,
+    keyword: u64,
+    max_level: LevelFilter,
+    normalization: Option<FieldNameNormalization>,
+}
+
+impl TraceLoggingLayer {
+    /// Registers the provider `name` with the ID derived from its name.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Register`] if `EventRegister` fails.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        Self::with_provider_id(name, provider_id(name))
+    }
+
+    /// Registers the provider `name` with the ID `id`.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Register`] if `EventRegister` fails.
+    pub fn with_provider_id(name: &str, id: u128) -> Result<Self, Error> {
+        let provider = Provider::register(name, id)?;
+        let mut traits = Vec::with_capacity(name.len() + 3);
+        traits.extend_from_slice(&[0, 0]);
+        traits.extend_from_slice(name.as_bytes());
+        traits.push(0);
+        let size = u16::try_from(traits.len()).unwrap_or(u16::MAX);
+        traits[..2].copy_from_slice(&size.to_le_bytes());
+        unsafe {
+            EventSetInformation(
+                provider.handle,
+                EventProviderSetTraits,
+                traits.as_mut_ptr().cast(),
+                u32::from(size),
+            );
+        }
+
+        Ok(Self {
+            provider,
+            traits,
+            keyword: 0,
+            max_level: LevelFilter::TRACE,
+            normalization: None,
+        })
+    }
+
+    /// Keyword of every event, for sessions that filter by keyword. Events have no
+    /// keyword by default, which every session receives.
+    #[must_use]
+    pub const fn with_keyword(mut self, keyword: u64) -> Self {
+        self.keyword = keyword;
+        self
+    }
+
+    /// Most verbose level written, see
+    /// [`EventLogLayer::with_max_level`](crate::EventLogLayer::with_max_level).
+    #[must_use]
+    pub const fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Normalizes the field names like
+    /// [`EventLogLayer::with_field_name_normalization`](crate::EventLogLayer::with_field_name_normalization).
+    #[must_use]
+    pub fn with_field_name_normalization(mut self, normalization: FieldNameNormalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    #[must_use]
+    pub fn provider_name(&self) -> &str {
+        &self.provider.name
+    }
+
+    /// The provider ID formatted as a GUID, e.g. for `tracelog -guid`.
+    #[must_use]
+    pub fn provider_id(&self) -> String {
+        format_guid(self.provider.id)
+    }
+
+    fn write(&self, level: u8, name: &str, fields: &[(String, FieldValue)]) {
+        let mut meta = vec![0, 0, 0];
+        meta.extend_from_slice(name.as_bytes());
+        meta.push(0);
+        let mut data = Vec::new();
+        for (name, value) in fields {
+            meta.extend_from_slice(name.as_bytes());
+            meta.push(0);
+            match value {
+                FieldValue::Str(value) => {
+                    meta.extend_from_slice(&[IN_ANSISTRING | CHAIN_FLAG, OUT_UTF8]);
+                    data.extend_from_slice(value.replace('\0', "").as_bytes());
+                    data.push(0);
+                }
+                FieldValue::I64(value) => {
+                    meta.push(IN_INT64);
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::U64(value) => {
+                    meta.push(IN_UINT64);
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::F64(value) => {
+                    meta.push(IN_DOUBLE);
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::Bool(value) => {
+                    meta.push(IN_BOOL32);
+                    data.extend_from_slice(&u32::from(*value).to_le_bytes());
+                }
+            }
+        }
+        let Ok(size) = u16::try_from(meta.len()) else {
+            return;
+        };
+        meta[..2].copy_from_slice(&size.to_le_bytes());
+
+        let descriptor = EVENT_DESCRIPTOR {
+            Id: 0,
+            Version: 0,
+            Channel: TRACELOGGING_CHANNEL,
+            Level: level,
+            Opcode: 0,
+            Task: 0,
+            Keyword: self.keyword,
+        };
+        let mut descriptors = [
+            data_descriptor(&self.traits, EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA),
+            data_descriptor(&meta, EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA),
+            data_descriptor(&data, 0),
+        ];
+        unsafe {
+            EventWriteTransfer(
+                self.provider.handle,
+                &descriptor,
+                std::ptr::null(),
+                std::ptr::null(),
+                3,
+                descriptors.as_mut_ptr(),
+            );
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for TraceLoggingLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.level() <= &self.max_level {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(
+        &self,
+        metadata: &Metadata<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) -> bool {
+        metadata.level() <= &self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+        let level = etw_level(*metadata.level());
+        if !self.provider.enabled(level, self.keyword) {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::new(self.normalization.as_ref());
+        event.record(&mut visitor);
+        let target = visitor.log.target(metadata).to_owned();
+        visitor
+            .fields
+            .push(("target".to_owned(), FieldValue::Str(target)));
+        self.write(level, metadata.name(), &visitor.fields);
+    }
+}
+
+/// The provider ID `EventSource` derives from `name`: a name-based GUID over the
+/// uppercased name in big-endian UTF-16.
+#[must_use]
+pub fn provider_id(name: &str) -> u128 {
+    let mut input = PROVIDER_NAMESPACE.to_vec();
+    for unit in name.to_uppercase().encode_utf16() {
+        input.extend_from_slice(&unit.to_be_bytes());
+    }
+    let hash = sha1(&input);
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    bytes[7] = (bytes[7] & 0x0F) | 0x50;
+
+    // The first three groups of the GUID are little-endian in the hash.
+    bytes[..4].reverse();
+    bytes[4..6].reverse();
+    bytes[6..8].reverse();
+    u128::from_be_bytes(bytes)
+}
+
+fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(input.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut hash = [0; 20];
+    for (bytes, word) in hash.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
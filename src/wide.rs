@@ -0,0 +1,59 @@
+use std::fmt;
+
+#[cfg(windows)]
+use crate::error::Error;
+
+pub fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// A nul-terminated UTF-16 string as passed to `ReportEventW` and friends.
+///
+/// The message owns its buffer and always ends in a nul, whatever it was built from, so
+/// the pointer from [`WideMessage::as_ptr`] is a valid `PCWSTR` for as long as the
+/// message is alive. Every write of this crate goes through this type; custom sinks and
+/// direct writers can use it to get the same conversion.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WideMessage(Vec<u16>);
+
+impl WideMessage {
+    /// Encodes `text`.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidMessage`] if `text` contains a nul character, which
+    /// would cut the description short.
+    #[cfg(windows)]
+    pub fn new(text: &str) -> Result<Self, Error> {
+        if text.contains('\0') {
+            return Err(Error::InvalidMessage);
+        }
+        Ok(Self(to_wide(text)))
+    }
+
+    /// Takes UTF-16 `units`, appending the terminating nul if it is missing.
+    #[must_use]
+    pub fn from_utf16(mut units: Vec<u16>) -> Self {
+        if units.last() != Some(&0) {
+            units.push(0);
+        }
+        Self(units)
+    }
+
+    /// The UTF-16 units without the terminating nul.
+    #[must_use]
+    pub fn as_units(&self) -> &[u16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    /// A pointer to the nul-terminated string, valid until the message is dropped.
+    #[must_use]
+    pub fn as_ptr(&self) -> *const u16 {
+        self.0.as_ptr()
+    }
+}
+
+impl fmt::Display for WideMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf16_lossy(self.as_units()))
+    }
+}
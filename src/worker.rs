@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::flood::Occurrence;
+use crate::output::Output;
+use crate::stats::Counters;
+use crate::wide::to_wide;
+
+/// Writes events on a dedicated thread instead of inside `on_event`, so a slow Event Log
+/// service does not stall the application.
+///
+/// Events are queued in a bounded channel. If the queue is full, events are dropped and
+/// a WARN event (ID 1003 by default) reports how many once the worker catches up, unless
+/// the queue is configured to block instead, for good or up to a timeout.
+#[derive(Debug, Clone, Copy)]
+pub struct NonBlocking {
+    queue_size: usize,
+    lossy: bool,
+    wait: Option<(Level, Duration)>,
+    id: u32,
+}
+
+impl NonBlocking {
+    pub const DEFAULT_ID: u32 = 1003;
+    pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+    #[must_use]
+    pub const fn new(queue_size: usize) -> Self {
+        Self {
+            queue_size,
+            lossy: true,
+            wait: None,
+            id: Self::DEFAULT_ID,
+        }
+    }
+
+    /// Whether to drop events when the queue is full (the default) or to wait for room.
+    #[must_use]
+    pub const fn lossy(mut self, lossy: bool) -> Self {
+        self.lossy = lossy;
+        self
+    }
+
+    /// Lets events of `level` or more severe wait up to `timeout` for room in a full queue
+    /// before they are dropped, trading a little latency in bursts for not losing them.
+    /// Less severe events are dropped right away. Waits are counted in
+    /// [`EventLogStats::queue_waits`](crate::EventLogStats::queue_waits).
+    ///
+    /// Only applies to a lossy queue: with [`NonBlocking::lossy(false)`](Self::lossy)
+    /// every event waits for room without a timeout.
+    #[must_use]
+    pub const fn block_for(mut self, level: Level, timeout: Duration) -> Self {
+        self.wait = Some((level, timeout));
+        self
+    }
+
+    /// Event ID of the dropped events summary.
+    #[must_use]
+    pub const fn id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+impl Default for NonBlocking {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_QUEUE_SIZE)
+    }
+}
+
+#[derive(Debug)]
+enum Message {
+    Event(Occurrence),
+    Stop,
+}
+
+/// The writer thread of a [`NonBlocking`] layer. Dropping it writes the queued events and
+/// waits for the thread to finish.
+#[derive(Debug)]
+pub struct Worker {
+    config: NonBlocking,
+    source: String,
+    dropped: Arc<AtomicU64>,
+    stats: Arc<Counters>,
+    stopper: Stopper,
+}
+
+/// Stops a [`Worker`] from outside the layer, e.g. from a guard.
+#[derive(Debug, Clone)]
+pub struct Stopper {
+    sender: SyncSender<Message>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Stopper {
+    /// Writes the queued events, including those queued while it stops, and waits for the
+    /// worker to finish. Events sent afterwards are handed back to be written directly.
+    pub fn stop(&self) {
+        let thread = self
+            .thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(thread) = thread {
+            let _ = self.sender.send(Message::Stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Worker {
+    pub fn spawn(config: NonBlocking, output: Output) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(config.queue_size);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let source = output.log_name.clone();
+        let stats = Arc::clone(&output.stats);
+
+        let counter = Arc::clone(&dropped);
+        let thread = thread::Builder::new()
+            .name("eventlog-writer".to_owned())
+            .spawn(move || {
+                let write = |occurrence: &Occurrence| {
+                    output.report_occurrence(occurrence);
+                    let dropped = counter.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        let summary = format!(
+                            "ID: {}\n\nmessage: event log queue full, {dropped} events dropped\n",
+                            config.id
+                        );
+                        output.report(
+                            config.id,
+                            Level::WARN,
+                            None,
+                            0,
+                            &to_wide(&summary),
+                            &[],
+                            &[],
+                        );
+                    }
+                };
+                for message in &receiver {
+                    let Message::Event(occurrence) = message else {
+                        break;
+                    };
+                    write(&occurrence);
+                }
+                // Events other threads queued behind the stop message.
+                for message in receiver.try_iter() {
+                    if let Message::Event(occurrence) = message {
+                        write(&occurrence);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            config,
+            source,
+            dropped,
+            stats,
+            stopper: Stopper {
+                sender,
+                thread: Arc::new(Mutex::new(Some(thread))),
+            },
+        })
+    }
+
+    pub fn stopper(&self) -> Stopper {
+        self.stopper.clone()
+    }
+
+    /// Queues `occurrence`, returning it if the worker is gone and it must be written
+    /// directly.
+    pub fn send(&self, occurrence: Occurrence) -> Option<Occurrence> {
+        let unsent = |message| match message {
+            Message::Event(occurrence) => Some(occurrence),
+            Message::Stop => None,
+        };
+        let sender = &self.stopper.sender;
+        let wait = self
+            .config
+            .wait
+            .filter(|(level, _)| occurrence.level <= *level);
+        let message = Message::Event(occurrence);
+        if !self.config.lossy {
+            return sender.send(message).err().and_then(|e| unsent(e.0));
+        }
+        let result = match (sender.try_send(message), wait) {
+            (Err(TrySendError::Full(message)), Some((_, timeout))) => {
+                self.stats.queue_wait();
+                send_within(sender, message, timeout)
+            }
+            (result, _) => result,
+        };
+        match result {
+            Ok(()) => None,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                self.stats.queue_overflow();
+                diagnostics::emit(|| Diagnostic::QueueFull {
+                    source: self.source.clone(),
+                });
+                None
+            }
+            Err(TrySendError::Disconnected(message)) => unsent(message),
+        }
+    }
+}
+
+/// Retries sending `message` until it is queued or `timeout` elapsed; the standard
+/// channel has no timed send.
+fn send_within(
+    sender: &SyncSender<Message>,
+    mut message: Message,
+    timeout: Duration,
+) -> Result<(), TrySendError<Message>> {
+    let deadline = Instant::now() + timeout;
+    let mut pause = Duration::from_micros(50);
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(TrySendError::Full(message));
+        }
+        thread::sleep(pause.min(left));
+        pause = (pause * 2).min(Duration::from_millis(5));
+        match sender.try_send(message) {
+            Err(TrySendError::Full(unsent)) => message = unsent,
+            result => return result,
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stopper.stop();
+    }
+}
@@ -0,0 +1,86 @@
+use std::io;
+use tracing::Level;
+
+use crate::handle::EventLogHandle;
+use crate::wide::to_wide;
+
+/// An [`io::Write`] adapter that writes every line as an event.
+///
+/// Useful to funnel the output of spawned child processes into the event log. Bytes are
+/// buffered until a newline; incomplete lines are written on `flush` and on drop. Empty
+/// lines are skipped. Lines the event log rejects are reported as an [`io::Error`].
+#[derive(Debug)]
+pub struct EventLogWriter {
+    handle: EventLogHandle,
+    level: Level,
+    id: u32,
+    origin: Option<String>,
+    buffer: Vec<u8>,
+}
+
+impl EventLogWriter {
+    #[must_use]
+    pub const fn new(handle: EventLogHandle, level: Level, id: u32) -> Self {
+        Self {
+            handle,
+            level,
+            id,
+            origin: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Written as the `source` of every event, e.g. `"robocopy stdout"`.
+    #[must_use]
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    fn emit(&self, line: &[u8]) -> io::Result<()> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        let mut msg = format!("ID: {}\n\n", self.id);
+        if let Some(origin) = &self.origin {
+            msg.push_str(&format!("source: {origin}\n"));
+        }
+        msg.push_str(&format!("message: {line}\n"));
+
+        if self.handle.report(self.id, self.level, &to_wide(&msg)) {
+            Ok(())
+        } else {
+            Err(io::Error::other("failed to write to event log"))
+        }
+    }
+}
+
+impl io::Write for EventLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.emit(&line)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            self.emit(&line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EventLogWriter {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+    }
+}